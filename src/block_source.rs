@@ -0,0 +1,287 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::{BlockSourceKind, Config};
+use crate::ratelimit::{read_json_text, RateLimiter};
+
+/// 最新区块高度的数据来源
+#[async_trait]
+pub trait BlockSource: Send + Sync {
+    /// 来源名称，用于日志
+    fn name(&self) -> &'static str;
+
+    /// 获取最新区块高度（未应用偏移）
+    async fn latest_height(&self, client: &Client, limiter: &RateLimiter) -> Result<u64>;
+
+    /// 获取最新区块的出块时间，用于检测本机时钟偏差
+    async fn latest_block_time(&self, client: &Client, limiter: &RateLimiter) -> Result<DateTime<Utc>>;
+}
+
+/// `Timestamp::Now` 的存储键：twox128("Timestamp") ++ twox128("Now")
+const TIMESTAMP_NOW_KEY: &str = "0xf0c365c3cf59d671eb72da0e7a4113c49f1f0515f462cdcf84e0f1d6045dfcbb";
+
+/// 解码 `Timestamp::Now` 的存储值：SCALE 编码的 u64 毫秒（小端）
+fn decode_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    let bytes = hex::decode(value.trim_start_matches("0x"))?;
+    let millis: [u8; 8] = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| anyhow!("Timestamp::Now 应为 8 字节，实际 {} 字节", bytes.len()))?;
+    Utc.timestamp_millis_opt(u64::from_le_bytes(millis) as i64)
+        .single()
+        .ok_or_else(|| anyhow!("Timestamp::Now 超出范围"))
+}
+
+/// 各网络最新区块高度的短期缓存，跨轮次共享
+///
+/// 缓存的是未应用偏移的原始高度，SNAPSHOT_OFFSET 在使用时再扣除；TTL 为 0 时不缓存
+pub struct HeightCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, u64)>>,
+    /// 各网络见过的最高原始高度，不受 TTL 影响，用于发现上游返回的高度倒退
+    highest: Mutex<HashMap<String, u64>>,
+}
+
+/// 允许的高度倒退块数：负载均衡后的不同节点同步进度可能略有差异
+pub const HEIGHT_REGRESSION_TOLERANCE: u64 = 100;
+
+impl HeightCache {
+    pub fn new(ttl: Duration) -> Self {
+        HeightCache { ttl, entries: Mutex::new(HashMap::new()), highest: Mutex::new(HashMap::new()) }
+    }
+
+    /// 记录某网络刚取到的原始高度；比此前见过的最高高度低出 HEIGHT_REGRESSION_TOLERANCE 以上时
+    /// 不记录，返回此前的最高高度
+    pub fn observe(&self, network: &str, height: u64) -> Result<(), u64> {
+        let mut highest = self.highest.lock().unwrap();
+        let seen = highest.entry(network.to_string()).or_insert(height);
+        if height.saturating_add(HEIGHT_REGRESSION_TOLERANCE) < *seen {
+            return Err(*seen);
+        }
+        *seen = (*seen).max(height);
+        Ok(())
+    }
+
+    /// 取未过期的缓存高度
+    pub fn get(&self, network: &str) -> Option<u64> {
+        self.get_at(network, Instant::now())
+    }
+
+    /// 写入某网络刚取到的原始高度
+    pub fn insert(&self, network: &str, height: u64) {
+        self.insert_at(network, height, Instant::now());
+    }
+
+    fn get_at(&self, network: &str, now: Instant) -> Option<u64> {
+        let entries = self.entries.lock().unwrap();
+        let &(fetched_at, height) = entries.get(network)?;
+        (now.saturating_duration_since(fetched_at) < self.ttl).then_some(height)
+    }
+
+    fn insert_at(&self, network: &str, height: u64, now: Instant) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        self.entries.lock().unwrap().insert(network.to_string(), (now, height));
+    }
+}
+
+/// 按配置选择区块高度来源
+pub fn from_config(cfg: &Config) -> Box<dyn BlockSource> {
+    match cfg.block_source {
+        BlockSourceKind::Subscan => Box::new(SubscanBlockSource {
+            base_url: cfg.subscan_base_url.clone(),
+            api_key: cfg.subscan_api_key.as_ref().map(|k| k.expose().to_string()),
+        }),
+        BlockSourceKind::Rpc => Box::new(RpcBlockSource {
+            rpc_url: cfg.rpc_url.clone(),
+        }),
+    }
+}
+
+/// 通过 Subscan metadata 接口获取区块高度
+pub struct SubscanBlockSource {
+    pub base_url: String,
+    /// 为空时不带 X-API-Key 匿名访问，限流更严格
+    pub api_key: Option<String>,
+}
+
+#[async_trait]
+impl BlockSource for SubscanBlockSource {
+    fn name(&self) -> &'static str {
+        "subscan"
+    }
+
+    async fn latest_height(&self, client: &Client, limiter: &RateLimiter) -> Result<u64> {
+        let resp = self.metadata(client, limiter).await?;
+        // 偏移由调用方在缓存之后统一扣除
+        parse_block_height(&resp, 0)
+    }
+
+    async fn latest_block_time(&self, client: &Client, limiter: &RateLimiter) -> Result<DateTime<Utc>> {
+        let resp = self.metadata(client, limiter).await?;
+        // blockTime 为秒级 Unix 时间戳，字符串或数字
+        let block_time = &resp["data"]["blockTime"];
+        let secs = block_time
+            .as_i64()
+            .or_else(|| block_time.as_str().and_then(|s| s.parse().ok()))
+            .ok_or_else(|| anyhow!("blockTime not found"))?;
+        Utc.timestamp_opt(secs, 0).single().ok_or_else(|| anyhow!("blockTime 超出范围：{}", secs))
+    }
+}
+
+impl SubscanBlockSource {
+    async fn metadata(&self, client: &Client, limiter: &RateLimiter) -> Result<Value> {
+        let mut req = client
+            .post(format!("{}/api/scan/metadata", self.base_url))
+            .header("Content-Type", "application/json")
+            .body("{}");
+        if let Some(api_key) = &self.api_key {
+            req = req.header("X-API-Key", api_key);
+        }
+        let (_, text) = read_json_text(limiter.send(client, req).await?, "Subscan").await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+/// 从 Subscan metadata 响应中解析 `data.blockNum` 并扣除 `offset`
+///
+/// `offset` 不小于高度时结果为 0，由调用方按 MIN_SNAPSHOT_HEIGHT 拒绝；blockNum 缺失或不是十进制整数时报错
+pub fn parse_block_height(json: &Value, offset: u64) -> Result<u64> {
+    let block_num = &json["data"]["blockNum"];
+    let height = match block_num {
+        Value::String(s) => s
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| anyhow!("blockNum 不是整数：{:?}", s))?,
+        Value::Number(n) => n.as_u64().ok_or_else(|| anyhow!("blockNum 不是非负整数：{}", n))?,
+        Value::Null => anyhow::bail!("blockNum not found"),
+        other => anyhow::bail!("blockNum 类型不正确：{}", other),
+    };
+    Ok(height.saturating_sub(offset))
+}
+
+/// 通过节点 JSON-RPC 读取最新已最终确认的区块高度，无需 API Key
+pub struct RpcBlockSource {
+    pub rpc_url: String,
+}
+
+impl RpcBlockSource {
+    /// 发送一次 JSON-RPC 调用并返回 result 字段
+    async fn call(&self, client: &Client, limiter: &RateLimiter, method: &str, params: Value) -> Result<Value> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let resp = limiter.send(client, client.post(&self.rpc_url).json(&body)).await?;
+        let (_, text) = read_json_text(resp, "RPC").await?;
+        let resp: Value = serde_json::from_str(&text)?;
+        if let Some(err) = resp.get("error") {
+            anyhow::bail!("RPC {} 返回错误：{}", method, err);
+        }
+        resp.get("result")
+            .cloned()
+            .ok_or_else(|| anyhow!("RPC {} 响应缺少 result 字段", method))
+    }
+}
+
+#[async_trait]
+impl BlockSource for RpcBlockSource {
+    fn name(&self) -> &'static str {
+        "rpc"
+    }
+
+    async fn latest_height(&self, client: &Client, limiter: &RateLimiter) -> Result<u64> {
+        let head = self.call(client, limiter, "chain_getFinalizedHead", json!([])).await?;
+        let header = self.call(client, limiter, "chain_getHeader", json!([head])).await?;
+
+        // 区块号以十六进制字符串返回，例如 "0x1a2b3c"
+        let number = header["number"]
+            .as_str()
+            .ok_or_else(|| anyhow!("区块头缺少 number 字段"))?;
+        Ok(u64::from_str_radix(number.trim_start_matches("0x"), 16)?)
+    }
+
+    async fn latest_block_time(&self, client: &Client, limiter: &RateLimiter) -> Result<DateTime<Utc>> {
+        // 不指定区块即读取最佳块的状态，比最终确认块更接近当前时间
+        let value = self.call(client, limiter, "state_getStorage", json!([TIMESTAMP_NOW_KEY])).await?;
+        decode_timestamp(value.as_str().ok_or_else(|| anyhow!("Timestamp::Now 存储为空"))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_hit_within_ttl() {
+        let cache = HeightCache::new(Duration::from_secs(60));
+        let t0 = Instant::now();
+        cache.insert_at("polkadot", 100, t0);
+        assert_eq!(cache.get_at("polkadot", t0 + Duration::from_secs(59)), Some(100));
+        assert_eq!(cache.get_at("kusama", t0), None);
+    }
+
+    #[test]
+    fn cache_expires_after_ttl() {
+        let cache = HeightCache::new(Duration::from_secs(60));
+        let t0 = Instant::now();
+        cache.insert_at("polkadot", 100, t0);
+        assert_eq!(cache.get_at("polkadot", t0 + Duration::from_secs(60)), None);
+
+        cache.insert_at("polkadot", 130, t0 + Duration::from_secs(60));
+        assert_eq!(cache.get_at("polkadot", t0 + Duration::from_secs(61)), Some(130));
+    }
+
+    #[test]
+    fn timestamp_storage_key_and_value() {
+        let key = [
+            sp_core::hashing::twox_128(b"Timestamp"),
+            sp_core::hashing::twox_128(b"Now"),
+        ]
+        .concat();
+        assert_eq!(format!("0x{}", hex::encode(key)), TIMESTAMP_NOW_KEY);
+
+        // 1_700_000_000_123 毫秒的小端编码
+        let at = decode_timestamp("0x7b68e5cf8b010000").unwrap();
+        assert_eq!(at.timestamp_millis(), 1_700_000_000_123);
+        assert!(decode_timestamp("0x7b8b").is_err());
+    }
+
+    #[test]
+    fn block_height_offset_arithmetic() {
+        let metadata = |block_num: Value| json!({ "code": 0, "data": { "blockNum": block_num } });
+        assert_eq!(parse_block_height(&metadata(json!("21000000")), 50).unwrap(), 20_999_950);
+        assert_eq!(parse_block_height(&metadata(json!(21_000_000)), 0).unwrap(), 21_000_000);
+        // 偏移等于或大于高度时饱和为 0
+        assert_eq!(parse_block_height(&metadata(json!("50")), 50).unwrap(), 0);
+        assert_eq!(parse_block_height(&metadata(json!("10")), 50).unwrap(), 0);
+
+        assert!(parse_block_height(&metadata(json!("latest")), 0).is_err());
+        assert!(parse_block_height(&metadata(json!("-1")), 0).is_err());
+        assert!(parse_block_height(&metadata(json!(1.5)), 0).is_err());
+        assert!(parse_block_height(&json!({ "data": {} }), 0).is_err());
+    }
+
+    #[test]
+    fn regressed_height_is_rejected() {
+        let cache = HeightCache::new(Duration::ZERO);
+        assert_eq!(cache.observe("polkadot", 20_000_000), Ok(()));
+        assert_eq!(cache.observe("polkadot", 20_000_000 - HEIGHT_REGRESSION_TOLERANCE), Ok(()));
+        assert_eq!(cache.observe("polkadot", 12), Err(20_000_000));
+        assert_eq!(cache.observe("polkadot", 20_000_500), Ok(()));
+        assert_eq!(cache.observe("polkadot", 20_000_000), Err(20_000_500));
+        assert_eq!(cache.observe("kusama", 12), Ok(()));
+    }
+
+    #[test]
+    fn zero_ttl_disables_cache() {
+        let cache = HeightCache::new(Duration::ZERO);
+        let t0 = Instant::now();
+        cache.insert_at("polkadot", 100, t0);
+        assert_eq!(cache.get_at("polkadot", t0), None);
+    }
+}
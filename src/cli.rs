@@ -0,0 +1,411 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use log::warn;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use tdao_referenda_sync::block_source::HeightCache;
+use tdao_referenda_sync::config::{BlockSourceKind, Config};
+use tdao_referenda_sync::db::Db;
+use tdao_referenda_sync::export;
+use tdao_referenda_sync::models::{OpenSquareNewProposalRequest, SubSquareReferendum};
+use tdao_referenda_sync::ratelimit::RateLimiter;
+use tdao_referenda_sync::service::{build_proposal, fetch_snapshot_heights, refresh_proposal};
+use tdao_referenda_sync::signing::{
+    canonical_json, canonical_value, sign_request, signer_address, verify_payload, Signer,
+};
+
+/// 命令行模式
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// 默认：启动定时同步循环
+    Run,
+    /// 打印已同步的公投及其元数据后退出
+    List { json: bool },
+    /// 本地签名自检：签名示例提案并验签，不访问任何外部服务
+    SelfTest,
+    /// 只重试 failed_publishes 中记录的发布失败公投后退出
+    RetryFailed,
+    /// 打印数据库统计（条数、编号范围、缺口、最近同步时间）后退出
+    DbStatus { json: bool },
+    /// 删除单条公投的同步记录，下一轮会重新发布；未带 `--yes` 时只预览不删除
+    Unsync { index: u32, confirmed: bool },
+    /// 校验配置、签名密钥、数据库和上游服务连通性后退出，不发布也不写库
+    ConfigCheck,
+    /// 把保存的请求体重新 POST 到 OpenSquare；`resign` 时刷新时间与快照并用当前密钥重新签名
+    Replay { path: String, resign: bool },
+    /// 把已同步记录、空间发布记录和拉取游标导出为 JSON 文件，用于迁移数据库
+    Export { path: String },
+    /// 导入 `--export` 生成的文件，已存在的记录保持不变
+    Import { path: String },
+}
+
+impl Command {
+    /// 解析命令行参数（不含程序名）
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut list = false;
+        let mut json = false;
+        let mut selftest = false;
+        let mut retry_failed = false;
+        let mut db_status = false;
+        let mut unsync = None;
+        let mut yes = false;
+        let mut config_check = false;
+        let mut replay = None;
+        let mut resign = false;
+        let mut export = None;
+        let mut import = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--list" => list = true,
+                "--json" => json = true,
+                "--selftest" | "--verify-signature" => selftest = true,
+                "--retry-failed" => retry_failed = true,
+                "--db-status" => db_status = true,
+                "--unsync" => {
+                    let value = args.next().ok_or_else(|| anyhow::anyhow!("--unsync 需要一个公投编号"))?;
+                    let index = value
+                        .parse::<u32>()
+                        .map_err(|_| anyhow::anyhow!("--unsync 的公投编号不合法：{}", value))?;
+                    unsync = Some(index);
+                }
+                "--yes" => yes = true,
+                "--config-check" => config_check = true,
+                "--replay" => {
+                    let path = args.next().ok_or_else(|| anyhow::anyhow!("--replay 需要一个请求体文件路径"))?;
+                    replay = Some(path);
+                }
+                "--resign" => resign = true,
+                "--export" => {
+                    export = Some(args.next().ok_or_else(|| anyhow::anyhow!("--export 需要一个输出文件路径"))?);
+                }
+                "--import" => {
+                    import = Some(args.next().ok_or_else(|| anyhow::anyhow!("--import 需要一个导出文件路径"))?);
+                }
+                other => anyhow::bail!(
+                    "未知参数：{}（可用：--list [--json]、--db-status [--json]、--selftest、--retry-failed、\
+                     --unsync <index> [--yes]、--config-check、--replay <path> [--resign]、\
+                     --export <path>、--import <path>）",
+                    other
+                ),
+            }
+        }
+        if json && !list && !db_status {
+            anyhow::bail!("--json 需要与 --list 或 --db-status 一起使用");
+        }
+        if yes && unsync.is_none() {
+            anyhow::bail!("--yes 需要与 --unsync 一起使用");
+        }
+        if resign && replay.is_none() {
+            anyhow::bail!("--resign 需要与 --replay 一起使用");
+        }
+        let modes = [
+            list,
+            selftest,
+            retry_failed,
+            db_status,
+            unsync.is_some(),
+            config_check,
+            replay.is_some(),
+            export.is_some(),
+            import.is_some(),
+        ];
+        if modes.iter().filter(|&&m| m).count() > 1 {
+            anyhow::bail!(
+                "--list、--db-status、--selftest、--retry-failed、--unsync、--config-check、--replay、\
+                 --export、--import 只能选择其一"
+            );
+        }
+        Ok(if let Some(path) = export {
+            Command::Export { path }
+        } else if let Some(path) = import {
+            Command::Import { path }
+        } else if let Some(path) = replay {
+            Command::Replay { path, resign }
+        } else if config_check {
+            Command::ConfigCheck
+        } else if let Some(index) = unsync {
+            Command::Unsync { index, confirmed: yes }
+        } else if list {
+            Command::List { json }
+        } else if db_status {
+            Command::DbStatus { json }
+        } else if selftest {
+            Command::SelfTest
+        } else if retry_failed {
+            Command::RetryFailed
+        } else {
+            Command::Run
+        })
+    }
+}
+
+/// `--list`：打印已同步公投，默认对齐表格，`--json` 时输出 JSON 数组
+pub async fn list(db: &Db, json: bool) -> Result<()> {
+    let records = db.list_all_referenda().await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:>8}  {:>5}  {:>10}  {:<19}  {:<10}  TITLE",
+        "INDEX", "TRACK", "SNAPSHOT", "SYNCED_AT", "STATE"
+    );
+    for r in &records {
+        let opt = |v: Option<String>| v.unwrap_or_else(|| "-".into());
+        println!(
+            "{:>8}  {:>5}  {:>10}  {:<19}  {:<10}  {}",
+            r.referendum_index,
+            opt(r.track_id.map(|t| t.to_string())),
+            opt(r.snapshot_height.map(|h| h.to_string())),
+            opt(r.synced_at.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())),
+            opt(r.state.clone()),
+            r.title.as_deref().unwrap_or("-"),
+        );
+    }
+    println!("共 {} 条", records.len());
+    Ok(())
+}
+
+/// `--db-status` 输出的数据库统计
+#[derive(Debug, Serialize)]
+struct DbStatus {
+    synced_count: u64,
+    min_index: Option<u32>,
+    max_index: Option<u32>,
+    gaps: Vec<u32>,
+    last_sync_time: Option<DateTime<Utc>>,
+    pending_retries: usize,
+    /// 发布时的链上状态 -> 条数，状态列上线前的记录计为 `unknown`
+    by_state: BTreeMap<String, u64>,
+}
+
+/// `--db-status`：打印数据库统计，默认对齐表格，`--json` 时输出 JSON 对象
+pub async fn db_status(db: &Db, json: bool) -> Result<()> {
+    let status = DbStatus {
+        synced_count: db.count_synced().await?,
+        min_index: db.min_synced_index().await?,
+        max_index: db.max_synced_index().await?,
+        gaps: db.find_gaps().await?,
+        last_sync_time: db.last_sync_time().await?,
+        pending_retries: db.list_failed_publishes().await?.len(),
+        by_state: db
+            .count_by_state()
+            .await?
+            .into_iter()
+            .map(|(state, count)| (state.unwrap_or_else(|| "unknown".into()), count))
+            .collect(),
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    let opt = |v: Option<String>| v.unwrap_or_else(|| "-".into());
+    println!("{:<16}  {}", "SYNCED_COUNT", status.synced_count);
+    println!("{:<16}  {}", "MIN_INDEX", opt(status.min_index.map(|i| i.to_string())));
+    println!("{:<16}  {}", "MAX_INDEX", opt(status.max_index.map(|i| i.to_string())));
+    println!("{:<16}  {} {:?}", "GAPS", status.gaps.len(), status.gaps);
+    println!(
+        "{:<16}  {}",
+        "LAST_SYNC",
+        opt(status.last_sync_time.map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string()))
+    );
+    println!("{:<16}  {}", "FAILED_PUBLISHES", status.pending_retries);
+    let by_state: Vec<String> = status.by_state.iter().map(|(s, n)| format!("{}={}", s, n)).collect();
+    println!("{:<16}  {}", "BY_STATE", opt((!by_state.is_empty()).then(|| by_state.join(", "))));
+    Ok(())
+}
+
+/// `--unsync <index>`：删除公投的同步记录及关联的空间发布、失败记录，下一轮同步会重新发布
+///
+/// 未带 `--yes` 时只打印将被删除的记录
+pub async fn unsync(db: &Db, index: u32, confirmed: bool) -> Result<()> {
+    let Some(record) = db.get_referendum(index).await? else {
+        anyhow::bail!("公投 #{} 不在已同步记录中", index);
+    };
+    println!("公投 #{}：{}", index, record.title.as_deref().unwrap_or("-"));
+    if !confirmed {
+        println!("⚠️ 未删除：确认后请加上 --yes 重新执行，下一轮同步会重新发布该公投");
+        return Ok(());
+    }
+    db.delete_referendum(index).await?;
+    warn!("🗑 已删除公投 #{} 的同步记录，下一轮同步会重新发布", index);
+    println!("✅ 已删除公投 #{} 的同步记录", index);
+    Ok(())
+}
+
+/// `--export <path>`：把同步状态写入 JSON 文件
+pub async fn export(db: &Db, path: &str) -> Result<()> {
+    let file = export::export(db).await?;
+    std::fs::write(path, serde_json::to_vec_pretty(&file)?).with_context(|| format!("无法写入 {}", path))?;
+    println!(
+        "✅ 已导出 {} 条公投、{} 条空间发布记录到 {}（schema_version {}）",
+        file.referenda.len(),
+        file.space_publishes.len(),
+        path,
+        file.schema_version
+    );
+    Ok(())
+}
+
+/// `--import <path>`：导入 `--export` 生成的文件，可重复执行
+pub async fn import(db: &Db, path: &str) -> Result<()> {
+    let raw = std::fs::read(path).with_context(|| format!("无法读取 {}", path))?;
+    let file = export::parse(&raw).with_context(|| format!("{} 不是有效的导出文件", path))?;
+    let summary = export::import(db, &file).await?;
+    println!(
+        "✅ 已导入 {} 条公投（{} 条已存在，跳过）、{} 条空间发布记录",
+        summary.referenda, summary.skipped_referenda, summary.space_publishes
+    );
+    if let Some(cursor) = file.cursor {
+        println!("🧭 拉取游标至少为 #{}", cursor);
+    }
+    Ok(())
+}
+
+/// 打印单项检查结果，返回是否通过
+fn report_check<T>(name: &str, result: Result<T>) -> bool {
+    match result {
+        Ok(_) => {
+            println!("✅ {}", name);
+            true
+        }
+        Err(e) => {
+            println!("❌ {}：{:#}", name, e);
+            false
+        }
+    }
+}
+
+/// `--replay <path>`：读取保存的 `OpenSquareNewProposalRequest` 并 POST 到 OpenSquare，打印响应
+///
+/// 默认原样发送保存的签名；`--resign` 时刷新开始/结束时间、时间戳和快照高度，用当前密钥重新签名
+pub async fn replay(client: &Client, limiter: &RateLimiter, cfg: &Config, path: &str, resign: bool) -> Result<()> {
+    let raw = std::fs::read(path).with_context(|| format!("无法读取 {}", path))?;
+    let mut request: OpenSquareNewProposalRequest = serde_json::from_slice(&raw)
+        .with_context(|| format!("{} 不是有效的 OpenSquare 请求体", path))?;
+    println!("公投提案：{}（空间 {}）", request.data.title, request.data.space);
+
+    if resign {
+        let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type)?;
+        let snapshots = fetch_snapshot_heights(client, limiter, &HeightCache::new(Duration::ZERO), cfg).await?;
+        let mut data = request.data;
+        refresh_proposal(&mut data, cfg, snapshots.heights);
+        request = sign_request(&keypair, data, cfg.network.ss58_format())?;
+        println!("已用 {} 重新签名", request.address);
+    } else {
+        println!("使用保存的签名（签名地址 {}）", request.address);
+    }
+
+    let url = format!("{}/api/{}/proposals", cfg.opensquare_base_url, request.data.space);
+    let res = limiter
+        .send(client, client.post(&url).json(&canonical_value(&request)?))
+        .await?;
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+    println!("HTTP {}", status);
+    println!("{}", body);
+    if !status.is_success() {
+        anyhow::bail!("❌ OpenSquare 拒绝了重放的提案（HTTP {}）", status);
+    }
+    println!("✅ 重放成功");
+    Ok(())
+}
+
+/// 由助记词派生签名地址，设置了 `SIGNER_ADDRESS` 时一并核对
+fn check_signer(cfg: &Config) -> Result<String> {
+    let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type)?;
+    let address = signer_address(&keypair, cfg.network.ss58_format())?;
+    if let Some(expected) = cfg.signer_address.as_ref().filter(|e| **e != address) {
+        anyhow::bail!("派生地址 {} 与 SIGNER_ADDRESS {} 不一致", address, expected);
+    }
+    Ok(address)
+}
+
+/// 确认上游服务可达：收到任意非 5xx 响应即视为可达
+async fn ping(client: &Client, url: &str) -> Result<()> {
+    let status = client.get(url).send().await?.status();
+    if status.is_server_error() {
+        anyhow::bail!("{} 返回 HTTP {}", url, status);
+    }
+    Ok(())
+}
+
+/// `--config-check`：逐项校验配置、签名密钥、数据库和上游服务，全部通过时返回 true
+///
+/// 只做只读检查：不发布提案，不建表也不写库
+pub async fn config_check() -> bool {
+    let cfg = match Config::from_env() {
+        Ok(cfg) => {
+            println!("✅ 配置加载");
+            cfg
+        }
+        Err(e) => {
+            println!("❌ 配置加载：{:#}", e);
+            return false;
+        }
+    };
+
+    let mut ok = report_check("助记词派生签名地址", check_signer(&cfg));
+
+    ok &= report_check("Postgres 连接", Db::connect(&cfg.postgres_url, 1, cfg.postgres_tls, cfg.postgres_ca_cert.as_deref()).await);
+
+    let client = match cfg.http_client() {
+        Ok(client) => client,
+        Err(e) => {
+            println!("❌ HTTP 客户端：{:#}", e);
+            return false;
+        }
+    };
+    let mut targets = vec![("SubSquare", cfg.subsquare_base_url.clone()), ("OpenSquare", cfg.opensquare_base_url.clone())];
+    match cfg.block_source {
+        BlockSourceKind::Subscan => targets.push(("Subscan", cfg.subscan_base_url.clone())),
+        BlockSourceKind::Rpc => targets.push(("RPC", cfg.rpc_url.clone())),
+    }
+    for (name, url) in targets {
+        ok &= report_check(&format!("{} 可达（{}）", name, url), ping(&client, &url).await);
+    }
+
+    println!("{}", if ok { "✅ 全部检查通过" } else { "❌ 存在未通过的检查" });
+    ok
+}
+
+/// `--selftest`：用配置的助记词签名一份示例提案，本地验签并核对签名地址
+pub fn selftest(cfg: &Config) -> Result<()> {
+    let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type)?;
+
+    let sample: SubSquareReferendum = serde_json::from_value(json!({
+        "referendumIndex": 0,
+        "title": "selftest",
+        "content": "selftest",
+        "track": 0,
+        "state": { "name": "Deciding" },
+    }))?;
+    let data = build_proposal(&sample, cfg, &cfg.open_square_spaces[0], HashMap::from([(cfg.network.name().to_string(), 0)]));
+    let request = sign_request(&keypair, data, cfg.network.ss58_format())?;
+
+    println!("密钥算法：{}", keypair.key_type());
+    println!("签名地址：{}", request.address);
+    println!("签名：{}", request.signature);
+
+    let payload = canonical_json(&request.data)?;
+    if !verify_payload(&keypair, &payload, &request.signature)? {
+        anyhow::bail!("❌ 签名校验失败");
+    }
+    println!("✅ 签名校验通过");
+
+    match &cfg.signer_address {
+        Some(expected) if expected != &request.address => {
+            anyhow::bail!("❌ 派生地址 {} 与 SIGNER_ADDRESS {} 不一致", request.address, expected);
+        }
+        Some(_) => println!("✅ 派生地址与 SIGNER_ADDRESS 一致"),
+        None => println!("ℹ️ 未设置 SIGNER_ADDRESS，跳过地址核对"),
+    }
+    Ok(())
+}
@@ -1,59 +1,1487 @@
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
+use chrono::NaiveTime;
+use chrono_tz::Tz;
+use log::{info, warn};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::models::{ReferendumStatus, Track};
+use crate::transform::{ContentFooter, ProposalTransformer, TitlePrefix};
+use crate::network::Network;
+use crate::notify::{NotifyKind, NotifyOn};
+
 
 
 /// 全局配置，从环境变量中加载，允许 .env 文件覆盖
 ///
 /// 可配置项:
-/// - OPEN_SQUARE_SPACE: OpenSquare 空间名称
-/// - POSTGRES_URL: PostgreSQL 连接串
+/// - OPEN_SQUARE_SPACE: OpenSquare 空间名称，逗号分隔可同时发布到多个空间（必填，不能为空）
+/// - POSTGRES_URL: PostgreSQL 连接串，带 `sslmode=require` 时使用 TLS 连接
+/// - POSTGRES_TLS: 强制以 TLS 连接 Postgres（云数据库如 RDS、Supabase），默认 false 即本地开发的明文连接
+/// - POSTGRES_CA_CERT: TLS 连接额外信任的 PEM 格式 CA 证书路径，未设置时只信任系统根证书
 /// - HTTP_TIMEOUT_SECS: HTTP 请求超时时间（秒）
-/// - SNAPSHOT_OFFSET: 块高度偏移
+/// - HTTP_CONNECT_TIMEOUT_SECS: 建立连接的超时时间（秒），与整体请求超时分开，默认 5 且不超过 HTTP_TIMEOUT_SECS
+/// - HTTP_POOL_MAX_IDLE_PER_HOST: 每个主机保留的最大空闲连接数，未设置时不限制（reqwest 默认）
+/// - HTTP_POOL_IDLE_TIMEOUT_SECS: 空闲连接的保活时间（秒），0 表示不回收，未设置时为 reqwest 默认的 90 秒
+/// - SNAPSHOT_OFFSET: 块高度偏移，latest_minus_offset 模式下从最新高度中扣除
+/// - MIN_SNAPSHOT_HEIGHT: 最新高度减偏移后的快照高度下限，低于它时视为上游异常、本轮不发布，默认 1（拒绝 0）；
+///   原始高度比此前见过的低出 100 块以上时同样视为异常
+/// - SNAPSHOT_MODE: NETWORK 所在链的快照高度取法，SNAPSHOT_NETWORKS 中的其他网络始终使用最新高度减偏移；
+///   默认 latest_minus_offset：
+///   - latest_minus_offset：最新已确认高度减 SNAPSHOT_OFFSET，无需额外数据，但同一公投在不同时间发布快照不同，
+///     持仓可在提交后、快照前调整
+///   - at_submission：固定为公投提交时的区块（SubSquare 的 indexer.blockHeight），对所有投票者最公平，
+///     但早于提交后才入场的持仓无法投票；缺少提交高度的公投本轮发布失败
+///   - fixed:<block>：所有提案都使用同一指定高度，适合回放或测试，需要手动维护
 /// - MNEMONIC: 用于签名的助记词
-/// - SUBSCAN_API_KEY: Subscan API Key
+/// - SUBSCAN_API_KEY: Subscan API Key，可不设置：此时匿名访问 Subscan，限流更严格，适合本地测试
+/// - MNEMONIC_FILE / SUBSCAN_API_KEY_FILE: 从文件读取对应密钥（Docker / Kubernetes secrets），去掉末尾空白；
+///   不能与同名的直接变量同时设置
 /// - PAGE_SIZE: 每次拉取公投条数，默认 50
+/// - DB_CONNECT_RETRIES: 启动时连接 Postgres 失败的重试次数，等待数据库就绪，默认 5，0 表示不重试
+/// - DB_CONNECT_BACKOFF_SECS: 启动连接重试的初始退避时间（秒），每次翻倍，单次最长 60 秒，默认 2
+/// - DB_INSERT_RETRIES: 写库遇到瞬时错误时的重试次数，默认 3
+/// - DB_INSERT_BACKOFF_MS: 写库重试的初始退避时间（毫秒），默认 500
+/// - PENDING_INSERTS_FILE: 已发布但写库失败的公投的落盘文件，重启后据此补写而不是重复发布；
+///   未设置或为空时只保存在内存中（默认）
+/// - INCLUDE_NONCE: 是否在签名载荷中加入唯一 nonce，默认 false
+/// - MAX_CONTENT_CHARS: 提案正文最大字符数，超出时截断摘要，默认 20000
+/// - SUMMARY_PREFERENCE: 摘要来源偏好 author / ai / longest，未设置时按 CONTENT_SOURCE
+/// - CONTENT_SOURCE: 摘要与原文的先后 summary_then_content / content_then_summary / content_only / summary_only，
+///   默认 summary_then_content；不能与 SUMMARY_PREFERENCE 同时设置
+/// - CONTENT_TYPE: 提案正文格式 markdown / plain，默认 markdown；plain 时发布前去掉正文中的 markdown 语法。
+///   正文由 markdown 拼成，尚不支持渲染为 html，设为 html 时启动报错
+/// - SET_DISCUSSION_LINK: 是否填写提案的 discussion 链接，默认 false
+/// - DISCUSSION_LINK_PATTERN: discussion 链接模板，`{index}` 替换为公投编号，默认 SubSquare 公投页
+/// - SUBSQUARE_BASE_URL: SubSquare API 地址，默认按 NETWORK 取 https://<network>-api.subsquare.io
+/// - SUBSCAN_BASE_URL: Subscan API 地址，默认按 NETWORK 取 https://<network>.api.subscan.io
+/// - OPENSQUARE_BASE_URL: OpenSquare 地址，默认 https://voting.opensquare.io
+/// - PUBLISH_CONCURRENCY: 同时发布提案的最大并发数，默认 1（逐条串行）
+/// - PUBLISH_ORDER: 每轮按公投编号发布的顺序 asc / desc，默认 asc（编号小的先发布，OpenSquare 列表中按自然顺序出现）；
+///   PUBLISH_CONCURRENCY 大于 1 时只保证开始发布的顺序
+/// - RATE_LIMIT_RPS: 每个目标主机每秒最多请求数，未设置时不限流
+/// - UPDATE_ON_TITLE_CHANGE: 已同步公投的上游标题/内容变化时提示需要更新，默认 false
+/// - ALLOW_UPDATES: 上游标题/内容变化时签名并推送提案更新到 OpenSquare（按保存的提案 CID），
+///   隐含 UPDATE_ON_TITLE_CHANGE 的检测；CID 未知（该功能上线前发布）的提案只提示，默认 false
+/// - EXTRA_HEADERS: 附加到每个请求的头，`Key: Value` 形式，多项以 `;` 或 `,` 分隔
+/// - BLOCK_SOURCE: 快照区块高度来源 subscan / rpc，默认 subscan
+/// - RPC_URL: rpc 来源使用的节点 JSON-RPC 地址，默认按 NETWORK 取官方节点
+/// - DB_POOL_MAX_SIZE: Postgres 连接池最大连接数，默认 4
+/// - VERIFY_SPACE: 启动时向 OpenSquare 确认空间存在，默认 false
+/// - NOTIFY_WEBHOOK_URL: 发布结果通知的 webhook 地址，未设置时不通知
+/// - NOTIFY_ON: 通知哪些结果 all / failures / none，默认 all
+/// - NOTIFY_KIND: 通知载荷格式 slack / discord / raw，默认 raw
+/// - VOTING_THRESHOLD: 资产投票门槛，单位为 planck（1 DOT = 10^10 planck），须为非负整数，未设置时不下发
+/// - VOTE_MULTIPLIER: 资产投票权重倍数，未设置时不下发
+/// - DEDUP_AGAINST_OPENSQUARE: 发布前拉取 OpenSquare 空间已有提案参与去重，默认 false；只统计当前签名地址发布、
+///   且标题与本服务格式（含 PUBLISHED_TITLE_PREFIX / TITLE_PREFIX / TITLE_SUFFIX）完全一致的提案
+/// - SNAPSHOT_NETWORKS: 需要快照高度的网络，逗号分隔，`name` 使用 BLOCK_SOURCE，
+///   `name=<rpc_url>` 使用该节点的 JSON-RPC，默认为 NETWORK；第一个为主网络
+/// - SNAPSHOT_PARTIAL_FAILURE: 部分网络取不到高度时 skip（跳过该条目）或 fail（中止本轮），默认 fail
+/// - KEY_TYPE: 签名密钥算法 sr25519 / ed25519，默认 sr25519；决定由 MNEMONIC 派生的地址和签名方式
+/// - SIGNER_ADDRESS: 预期的签名地址，`--selftest` 时与助记词派生的地址核对
+/// - IGNORE_INDICES: 永不同步的公投编号，逗号分隔，支持 `100-110` 形式的闭区间
+/// - NETWORK: 同步的网络 polkadot / kusama，默认 polkadot；决定签名地址的 SS58 前缀、代币与各服务默认地址
+/// - PROPOSAL_VERSION: 提案载荷的 version 字段，默认 5，OpenSquare 升级提案格式时调整
+/// - NETWORKS_CONFIG_VERSION: networksConfig 的 version 字段，默认 4
+/// - TOKEN_SYMBOL: networksConfig 及资产中的代币符号，默认按 NETWORK 取原生代币
+/// - TOKEN_DECIMALS: 代币精度，不超过 18，默认按 NETWORK 取原生代币精度
+/// - STRATEGIES: 投票策略，逗号分隔，可选 one-person-one-vote / balance-of / quadratic-balance-of / biased-voting，
+///   默认 one-person-one-vote
+/// - CONTENT_TEMPLATE: 提案正文模板，支持 `{url}`、`{summary}`、`{index}`、`{track}`、`{title}` 占位符，
+///   内容按 markdown 原样输出不做转义；未设置时为 `{url}` + 空行 + `{summary}`
+/// - RETRY_MAX_ATTEMPTS: `--retry-failed` 对单条公投的最大发布尝试次数，默认 5
+/// - RETRY_BACKOFF_SECS: `--retry-failed` 的初始退避时间（秒），每失败一次翻倍，默认 300
+/// - TITLE_PREFIX / TITLE_SUFFIX: 加在提案标题前后的标记（如 DAO 名称），以空格与标题分隔，未设置时不加
+/// - PUBLISHED_TITLE_PREFIX: 签名前才加在标题前的标记（内置转换器，见 `transform`），只出现在 OpenSquare 上，
+///   本地记录的标题不含它
+/// - CONTENT_FOOTER: 签名前追加在正文末尾的文字（内置转换器），如免责声明；在 PUBLISHED_TITLE_PREFIX 之后应用
+/// - TIMEZONE: IANA 时区名（如 Asia/Shanghai），设置后提案开始时间对齐到该时区当天零点；
+///   未设置时开始时间为当前时间
+/// - PROXY_URL: 所有出站请求使用的代理地址；未设置时沿用 HTTPS_PROXY / HTTP_PROXY / ALL_PROXY 环境变量
+/// - DANGER_ACCEPT_INVALID_CERTS: 跳过 TLS 证书校验，仅用于自签名证书的内部测试环境，默认 false
+/// - SNAPSHOT_CACHE_SECS: 各网络最新区块高度的缓存时间（秒），期间的多轮同步复用同一高度，默认 0（不缓存）
+/// - ACCESSIBILITY: 投票范围 whitelist / public，默认 whitelist；public 时不下发白名单
+/// - AUTH_REJECTION_THRESHOLD: 一轮内 OpenSquare 以 401/403 拒绝达到该次数时中止本轮并发送告警，默认 3
+/// - CIRCUIT_BREAKER_THRESHOLD: 一轮内连续发布失败达到该次数时熔断，本轮剩余公投留到下一轮，任一成功即清零；
+///   默认 5，0 表示不熔断
+/// - MAX_CLOCK_SKEW_SECS: 启动时比较本机时间与链上最新区块时间，偏差超过该秒数时告警，默认 300，0 表示不检查
+/// - CLOCK_SKEW_ABORT_SECS: 偏差超过该秒数时拒绝启动，未设置时只告警
+/// - STARTUP_JITTER_SECS: 首次同步前随机等待的最长时间（秒），默认 0
+/// - TICK_JITTER_SECS: 每轮同步前随机等待的最长时间（秒），默认 0
+/// - JITTER_SEED: 抖动随机数种子，设置后延迟序列可复现，未设置时使用系统熵
+/// - PUBLISH_WINDOW: 允许发布的时段 `HH:MM-HH:MM`（按 TIMEZONE，未设置时为 UTC），支持跨午夜；
+///   时段外仍拉取和去重，但推迟发布新公投，未设置时不限制
+/// - IDEMPOTENCY_HEADER: 发布请求携带幂等键的请求头名（如 Idempotency-Key），取决于 OpenSquare 是否支持；
+///   未设置时只在日志中打印幂等键，便于关联重试
+/// - MAX_REFERENDUM_AGE_DAYS: 只发布链上提交时间在该天数以内的公投，取不到提交时间的照常发布；
+///   未设置时不限制
+/// - REQUIRE_DECISION_DEPOSIT: 跳过尚未缴纳决策押金的公投（onchainData 中没有 decisionDeposit 即视为未缴纳），
+///   这类公投多为垃圾提案、不会进入 Deciding，默认 false
+/// - SYNC_STATES: 需要发布的公投链上状态，逗号分隔，不区分大小写，默认 Deciding,Confirming
+/// - SANITIZE_CONTENT: 发布前清理正文中的 HTML 标签、data: URI 和多余空行，默认 true
+/// - INCLUDE_SPEND_DETAILS: 国库支出公投在正文开头加一行申请金额和受益人（按 TOKEN_SYMBOL / TOKEN_DECIMALS 格式化），
+///   没有支出数据的公投不受影响，默认 false
+/// - INCLUDE_TRACK_IN_CONTENT: 在正文开头加一行 track 全名（如 `Track: Big Spender`），位于支出行之前，默认 false
+/// - CURSOR_LOOKBACK: 拉取游标的回看窗口（公投编号数），每轮重新检查游标以下这么多条较早的公投，
+///   更早的不再拉取；默认 50
+/// - FETCH_MODE: 拉取方式 fixed_page（默认，按 CURSOR_LOOKBACK 回看窗口翻页）/ incremental（按编号倒序翻页，
+///   遇到本地已同步的编号即停止，假定同步是连续的；不回看窗口内推迟发布的较早公投）
+/// - DEAD_LETTER_DIR: 被 OpenSquare 拒绝的已签名请求体的保存目录，每次拒绝一个 JSON 文件，未设置时不保存
+/// - DEAD_LETTER_MAX_FILES: DEAD_LETTER_DIR 中最多保留的文件数，超出时删除最旧的，默认 100
+/// - TRACK_CONFIG: 按 track 覆盖投票设置的 JSON 对象，键为 track 编号，值可含 vote_duration_days（投票天数，
+///   默认 30）、choices（选项，默认 Aye / Nay / Abstain）、threshold（同 VOTING_THRESHOLD）；
+///   如 `{"34":{"vote_duration_days":45},"30":{"vote_duration_days":7}}`，未覆盖的项沿用全局设置
+/// - DEFER_UNTITLED: SubSquare 尚无标题的公投暂不发布，留待之后有标题时再同步，默认 false
+#[derive(Debug, Clone)]
 pub struct Config {
-    pub open_square_space: String,
+    pub open_square_spaces: Vec<String>,
     pub postgres_url: String,
+    pub postgres_tls: bool,
+    pub postgres_ca_cert: Option<String>,
     pub http_timeout: Duration,
+    pub http_connect_timeout: Duration,
+    pub http_pool_max_idle_per_host: Option<usize>,
+    pub http_pool_idle_timeout: Option<Duration>,
     pub snapshot_offset: u64,
-    pub mnemonic: String,
-    pub subscan_api_key: String,
+    pub min_snapshot_height: u64,
+    pub snapshot_mode: SnapshotMode,
+    pub mnemonic: Secret,
+    pub subscan_api_key: Option<Secret>,
     pub page_size: usize,
+    pub db_connect_retries: u32,
+    pub db_connect_backoff: Duration,
+    pub db_insert_retries: u32,
+    pub db_insert_backoff: Duration,
+    pub pending_inserts_file: Option<String>,
+    pub include_nonce: bool,
+    pub max_content_chars: usize,
+    pub summary_preference: SummaryPreference,
+    pub content_source: ContentSource,
+    pub content_type: ContentType,
+    pub set_discussion_link: bool,
+    pub discussion_link_pattern: Option<String>,
+    pub subsquare_base_url: String,
+    pub subscan_base_url: String,
+    pub opensquare_base_url: String,
+    pub publish_concurrency: usize,
+    pub publish_order: PublishOrder,
+    pub rate_limit_rps: Option<u32>,
+    pub update_on_title_change: bool,
+    pub allow_updates: bool,
+    pub extra_headers: Vec<(String, String)>,
+    pub block_source: BlockSourceKind,
+    pub rpc_url: String,
+    pub db_pool_max_size: usize,
+    pub verify_space: bool,
+    pub notify_webhook_url: Option<String>,
+    pub notify_on: NotifyOn,
+    pub notify_kind: NotifyKind,
+    pub voting_threshold: Option<String>,
+    pub vote_multiplier: Option<u32>,
+    pub dedup_against_opensquare: bool,
+    pub snapshot_networks: Vec<SnapshotNetwork>,
+    pub snapshot_partial_failure: SnapshotFailureMode,
+    pub key_type: KeyType,
+    pub signer_address: Option<String>,
+    pub network: Network,
+    pub ignore_indices: Vec<RangeInclusive<u32>>,
+    pub proposal_version: String,
+    pub networks_config_version: String,
+    pub token_symbol: String,
+    pub token_decimals: u8,
+    pub strategies: Vec<String>,
+    pub defer_untitled: bool,
+    pub content_template: Option<String>,
+    pub retry_max_attempts: u32,
+    pub retry_backoff: Duration,
+    pub title_prefix: Option<String>,
+    pub title_suffix: Option<String>,
+    /// PUBLISHED_TITLE_PREFIX，除构造内置转换器外，还用于识别 OpenSquare 上本服务发布的标题
+    pub published_title_prefix: Option<String>,
+    /// 签名前依次作用于提案的转换器，内置的由环境变量配置，嵌入方可追加
+    pub transformers: Vec<Arc<dyn ProposalTransformer>>,
+    pub timezone: Option<Tz>,
+    pub proxy_url: Option<String>,
+    pub danger_accept_invalid_certs: bool,
+    pub snapshot_cache_ttl: Duration,
+    pub accessibility: Accessibility,
+    pub auth_rejection_threshold: usize,
+    pub circuit_breaker_threshold: usize,
+    pub max_clock_skew: Option<Duration>,
+    pub clock_skew_abort: Option<Duration>,
+    pub startup_jitter: Duration,
+    pub tick_jitter: Duration,
+    pub jitter_seed: Option<u64>,
+    pub publish_window: Option<PublishWindow>,
+    pub idempotency_header: Option<String>,
+    pub max_referendum_age: Option<Duration>,
+    pub require_decision_deposit: bool,
+    pub sync_states: Vec<ReferendumStatus>,
+    pub sanitize_content: bool,
+    pub include_spend_details: bool,
+    pub include_track_in_content: bool,
+    pub cursor_lookback: u32,
+    pub fetch_mode: FetchMode,
+    pub dead_letter_dir: Option<String>,
+    pub dead_letter_max_files: usize,
+    pub track_overrides: HashMap<u16, TrackOverride>,
+}
+
+impl Config {
+    /// 公投编号是否在 IGNORE_INDICES 中
+    pub fn is_ignored(&self, referendum_index: u32) -> bool {
+        self.ignore_indices.iter().any(|r| r.contains(&referendum_index))
+    }
+
+    /// 构建 HTTP 客户端，带上标识自身的 User-Agent 和配置的附加请求头
+    ///
+    /// reqwest 默认读取 HTTPS_PROXY / HTTP_PROXY / ALL_PROXY，显式配置的 PROXY_URL 优先
+    pub fn http_client(&self) -> anyhow::Result<Client> {
+        let mut headers = HeaderMap::new();
+        for (key, value) in &self.extra_headers {
+            headers.insert(HeaderName::from_bytes(key.as_bytes())?, HeaderValue::from_str(value)?);
+        }
+        let mut builder = Client::builder()
+            .timeout(self.http_timeout)
+            .connect_timeout(self.http_connect_timeout)
+            .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+            .default_headers(headers);
+        if let Some(max_idle) = self.http_pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(idle_timeout) = self.http_pool_idle_timeout {
+            // 0 表示空闲连接永不过期
+            builder = builder.pool_idle_timeout((!idle_timeout.is_zero()).then_some(idle_timeout));
+        }
+        if let Some(proxy_url) = &self.proxy_url {
+            info!("🌐 出站请求使用 PROXY_URL 配置的代理");
+            builder = builder.proxy(reqwest::Proxy::all(proxy_url)?);
+        }
+        if self.danger_accept_invalid_certs {
+            warn!("🚨 已启用 DANGER_ACCEPT_INVALID_CERTS：不校验 TLS 证书，连接不安全，仅限内部测试环境使用");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// 提案的投票范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Accessibility {
+    /// 只有白名单地址可以投票
+    #[default]
+    Whitelist,
+    /// 任何人都可以投票，不下发白名单
+    Public,
+}
+
+impl Accessibility {
+    /// networksConfig.accessibility 中使用的取值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Accessibility::Whitelist => "whitelist",
+            Accessibility::Public => "public",
+        }
+    }
+}
+
+impl FromStr for Accessibility {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "whitelist" => Ok(Accessibility::Whitelist),
+            "public" => Ok(Accessibility::Public),
+            other => anyhow::bail!("ACCESSIBILITY 取值无效：{}（可选 whitelist / public）", other),
+        }
+    }
+}
+
+/// 敏感配置值（助记词、API Key），Debug 输出时打码，需要明文时调用 `expose`
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    /// 取出明文，仅在实际使用密钥时调用
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// 允许发布提案的每日时段，`start > end` 时表示跨午夜
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublishWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl PublishWindow {
+    /// 时刻是否落在时段内（含起点，不含终点）
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl fmt::Display for PublishWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+impl FromStr for PublishWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |v: &str| {
+            NaiveTime::parse_from_str(v.trim(), "%H:%M")
+                .map_err(|_| anyhow::anyhow!("PUBLISH_WINDOW 格式错误，应为 HH:MM-HH:MM：{}", s))
+        };
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow::anyhow!("PUBLISH_WINDOW 格式错误，应为 HH:MM-HH:MM：{}", s))?;
+        let window = PublishWindow { start: parse(start)?, end: parse(end)? };
+        if window.start == window.end {
+            anyhow::bail!("PUBLISH_WINDOW 起止时间不能相同：{}", s);
+        }
+        Ok(window)
+    }
+}
+
+/// 需要获取快照高度的网络
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotNetwork {
+    /// snapshotHeights 中的键，例如 `polkadot`
+    pub name: String,
+    /// 为空时使用 BLOCK_SOURCE 配置的来源
+    pub rpc_url: Option<String>,
+}
+
+/// 单个 track 的投票设置覆盖，见 TRACK_CONFIG
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrackOverride {
+    /// 投票天数
+    pub vote_duration_days: Option<u32>,
+    /// 投票选项
+    pub choices: Option<Vec<String>>,
+    /// 资产投票门槛（planck）
+    pub threshold: Option<String>,
+}
+
+/// NETWORK 所在链的快照高度取法，见 SNAPSHOT_MODE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnapshotMode {
+    /// 最新已确认高度减 SNAPSHOT_OFFSET
+    #[default]
+    LatestMinusOffset,
+    /// 公投提交时的区块
+    AtSubmission,
+    /// 固定的区块高度
+    Fixed(u64),
+}
+
+impl SnapshotMode {
+    /// 由本轮的最新高度（已扣除 SNAPSHOT_OFFSET）和公投提交高度求快照高度
+    ///
+    /// at_submission 模式下缺少提交高度时返回错误，不回退到最新高度，避免静默改变快照语义
+    pub fn resolve(self, latest_minus_offset: u64, submitted_at: Option<u64>) -> anyhow::Result<u64> {
+        match self {
+            SnapshotMode::LatestMinusOffset => Ok(latest_minus_offset),
+            SnapshotMode::AtSubmission => {
+                submitted_at.ok_or_else(|| anyhow::anyhow!("SubSquare 未返回公投的提交区块高度"))
+            }
+            SnapshotMode::Fixed(block) => Ok(block),
+        }
+    }
+}
+
+impl FromStr for SnapshotMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        if let Some(block) = s.strip_prefix("fixed:") {
+            return block
+                .trim()
+                .parse()
+                .map(SnapshotMode::Fixed)
+                .map_err(|_| anyhow::anyhow!("SNAPSHOT_MODE 中的区块高度无效：{}", block));
+        }
+        match s.as_str() {
+            "" | "latest_minus_offset" => Ok(SnapshotMode::LatestMinusOffset),
+            "at_submission" => Ok(SnapshotMode::AtSubmission),
+            other => anyhow::bail!(
+                "SNAPSHOT_MODE 取值无效：{}（可选 latest_minus_offset / at_submission / fixed:<block>）",
+                other
+            ),
+        }
+    }
+}
+
+/// 每轮待发布公投的发布顺序，见 PUBLISH_ORDER
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PublishOrder {
+    /// 编号从小到大
+    #[default]
+    Asc,
+    /// 编号从大到小
+    Desc,
+}
+
+impl FromStr for PublishOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "asc" => Ok(PublishOrder::Asc),
+            "desc" => Ok(PublishOrder::Desc),
+            other => anyhow::bail!("PUBLISH_ORDER 取值无效：{}（可选 asc / desc）", other),
+        }
+    }
+}
+
+/// SubSquare 公投列表的拉取方式，见 FETCH_MODE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FetchMode {
+    /// 按拉取游标的回看窗口翻页，没有游标时只拉第 1 页
+    #[default]
+    FixedPage,
+    /// 倒序翻页直到遇到已同步的编号
+    Incremental,
+}
+
+impl FromStr for FetchMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "fixed_page" => Ok(FetchMode::FixedPage),
+            "incremental" => Ok(FetchMode::Incremental),
+            other => anyhow::bail!("FETCH_MODE 取值无效：{}（可选 fixed_page / incremental）", other),
+        }
+    }
+}
+
+/// 签名密钥算法，见 KEY_TYPE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyType {
+    #[default]
+    Sr25519,
+    Ed25519,
+}
+
+impl FromStr for KeyType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "sr25519" => Ok(KeyType::Sr25519),
+            "ed25519" => Ok(KeyType::Ed25519),
+            other => anyhow::bail!("KEY_TYPE 取值无效：{}（可选 sr25519 / ed25519）", other),
+        }
+    }
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KeyType::Sr25519 => "sr25519",
+            KeyType::Ed25519 => "ed25519",
+        })
+    }
+}
+
+/// 部分网络快照高度获取失败时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnapshotFailureMode {
+    /// 中止本轮同步
+    #[default]
+    Fail,
+    /// 跳过取不到高度的网络
+    Skip,
+}
+
+impl FromStr for SnapshotFailureMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "fail" => Ok(SnapshotFailureMode::Fail),
+            "skip" => Ok(SnapshotFailureMode::Skip),
+            other => anyhow::bail!("SNAPSHOT_PARTIAL_FAILURE 取值无效：{}（可选 skip / fail）", other),
+        }
+    }
+}
+
+/// 解析 `polkadot,assethub=https://...` 形式的快照网络列表
+fn parse_snapshot_networks(raw: &str) -> anyhow::Result<Vec<SnapshotNetwork>> {
+    let networks = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (name, rpc_url) = match item.split_once('=') {
+                Some((name, url)) => {
+                    let url = url.trim();
+                    reqwest::Url::parse(url).map_err(|e| {
+                        anyhow::anyhow!("SNAPSHOT_NETWORKS 中 {} 的 RPC 地址不合法：{}（{}）", name, url, e)
+                    })?;
+                    (name.trim(), Some(url.to_string()))
+                }
+                None => (item, None),
+            };
+            Ok(SnapshotNetwork { name: name.to_string(), rpc_url })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if networks.is_empty() {
+        anyhow::bail!("SNAPSHOT_NETWORKS 至少需要一个网络");
+    }
+    Ok(networks)
+}
+
+/// OpenSquare 支持的投票策略
+const KNOWN_STRATEGIES: &[&str] = &["one-person-one-vote", "balance-of", "quadratic-balance-of", "biased-voting"];
+
+/// 解析逗号分隔的投票策略列表，至少需要一个且必须是已知策略
+fn parse_strategies(raw: &str) -> anyhow::Result<Vec<String>> {
+    let mut strategies = Vec::new();
+    for item in raw.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        if !KNOWN_STRATEGIES.contains(&item) {
+            anyhow::bail!("STRATEGIES 中的策略无效：{}（可选 {}）", item, KNOWN_STRATEGIES.join(" / "));
+        }
+        if !strategies.iter().any(|s| s == item) {
+            strategies.push(item.to_string());
+        }
+    }
+    if strategies.is_empty() {
+        anyhow::bail!("STRATEGIES 至少需要一个投票策略");
+    }
+    Ok(strategies)
+}
+
+/// 解析 `1,5,100-110` 形式的公投编号列表，单个编号视为长度为 1 的区间
+fn parse_index_ranges(raw: &str) -> anyhow::Result<Vec<RangeInclusive<u32>>> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let parse = |v: &str| {
+                v.trim()
+                    .parse::<u32>()
+                    .map_err(|_| anyhow::anyhow!("IGNORE_INDICES 中的编号不合法：{}", item))
+            };
+            let (start, end) = match item.split_once('-') {
+                Some((a, b)) => (parse(a)?, parse(b)?),
+                None => {
+                    let v = parse(item)?;
+                    (v, v)
+                }
+            };
+            if start > end {
+                anyhow::bail!("IGNORE_INDICES 中的区间起点大于终点：{}", item);
+            }
+            Ok(start..=end)
+        })
+        .collect()
+}
+
+/// 快照区块高度来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockSourceKind {
+    /// Subscan metadata 接口，需要 API Key
+    #[default]
+    Subscan,
+    /// 节点 JSON-RPC（chain_getFinalizedHead + chain_getHeader）
+    Rpc,
+}
+
+impl FromStr for BlockSourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "subscan" => Ok(BlockSourceKind::Subscan),
+            "rpc" => Ok(BlockSourceKind::Rpc),
+            other => anyhow::bail!("BLOCK_SOURCE 取值无效：{}（可选 subscan / rpc）", other),
+        }
+    }
+}
+
+/// 正文摘要来源偏好
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SummaryPreference {
+    /// 默认：AI 摘要，缺失时回退到原始内容
+    #[default]
+    Default,
+    /// 优先作者摘要，其次原始内容
+    Author,
+    /// 优先 AI 摘要
+    Ai,
+    /// 取所有可用来源中最长的一个
+    Longest,
+}
+
+impl FromStr for SummaryPreference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" => Ok(SummaryPreference::Default),
+            "author" => Ok(SummaryPreference::Author),
+            "ai" => Ok(SummaryPreference::Ai),
+            "longest" => Ok(SummaryPreference::Longest),
+            other => anyhow::bail!("SUMMARY_PREFERENCE 取值无效：{}（可选 author / ai / longest）", other),
+        }
+    }
+}
+
+/// 正文中摘要与原始内容的取用顺序，仅在 SUMMARY_PREFERENCE 未设置时生效
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContentSource {
+    /// 默认：先摘要，缺失时回退到原始内容
+    #[default]
+    SummaryThenContent,
+    /// 先原始内容，缺失时回退到摘要
+    ContentThenSummary,
+    /// 只用原始内容
+    ContentOnly,
+    /// 只用摘要
+    SummaryOnly,
+}
+
+impl FromStr for ContentSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "summary_then_content" => Ok(ContentSource::SummaryThenContent),
+            "content_then_summary" => Ok(ContentSource::ContentThenSummary),
+            "content_only" => Ok(ContentSource::ContentOnly),
+            "summary_only" => Ok(ContentSource::SummaryOnly),
+            other => anyhow::bail!(
+                "CONTENT_SOURCE 取值无效：{}（可选 summary_then_content / content_then_summary / content_only / summary_only）",
+                other
+            ),
+        }
+    }
+}
+
+/// 提案正文格式，即 OpenSquare 提案的 contentType
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContentType {
+    #[default]
+    Markdown,
+    /// 纯文本，发布前去掉正文中的 markdown 语法
+    Plain,
+}
+
+impl ContentType {
+    /// 提案载荷 contentType 字段的取值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentType::Markdown => "markdown",
+            ContentType::Plain => "plain",
+        }
+    }
+}
+
+impl FromStr for ContentType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "markdown" => Ok(ContentType::Markdown),
+            "plain" => Ok(ContentType::Plain),
+            "html" => anyhow::bail!("CONTENT_TYPE=html 尚不支持：正文是 markdown，未渲染为 HTML（可选 markdown / plain）"),
+            other => anyhow::bail!("CONTENT_TYPE 取值无效：{}（可选 markdown / plain）", other),
+        }
+    }
+}
+
+/// 解析 `Key: Value; Key2: Value2` 形式的请求头列表
+fn parse_headers(raw: &str) -> anyhow::Result<Vec<(String, String)>> {
+    raw.split([';', ','])
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (key, value) = item
+                .split_once(':')
+                .ok_or_else(|| anyhow::anyhow!("EXTRA_HEADERS 格式错误，应为 `Key: Value`：{}", item))?;
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// 解析 SYNC_STATES，去重并保持配置顺序，不允许为空
+fn parse_sync_states(raw: &str) -> anyhow::Result<Vec<ReferendumStatus>> {
+    let mut states = Vec::new();
+    for item in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let state: ReferendumStatus = item
+            .parse()
+            .map_err(|e| anyhow::anyhow!("SYNC_STATES 取值无效：{}", e))?;
+        if !states.contains(&state) {
+            states.push(state);
+        }
+    }
+    if states.is_empty() {
+        anyhow::bail!("SYNC_STATES 不能为空");
+    }
+    Ok(states)
+}
+
+/// 解析 TRACK_CONFIG：键须为已知 track 编号，天数须为正，选项至少两个且不重复，门槛须为非负整数
+fn parse_track_config(raw: &str) -> anyhow::Result<HashMap<u16, TrackOverride>> {
+    let entries: HashMap<String, TrackOverride> = serde_json::from_str(raw)
+        .map_err(|e| anyhow::anyhow!("TRACK_CONFIG 不是有效的 JSON 对象：{}", e))?;
+    let mut overrides = HashMap::new();
+    for (key, mut entry) in entries {
+        let track_id = key
+            .trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|&id| Track::from_id(id).is_some())
+            .ok_or_else(|| anyhow::anyhow!("TRACK_CONFIG 中的 track 编号无效：{}", key))?;
+        if entry.vote_duration_days == Some(0) {
+            anyhow::bail!("TRACK_CONFIG 中 track {} 的 vote_duration_days 须为正整数", track_id);
+        }
+        if let Some(choices) = &mut entry.choices {
+            *choices = choices.iter().map(|c| c.trim().to_string()).collect();
+            let mut unique = choices.clone();
+            unique.sort();
+            unique.dedup();
+            if choices.len() < 2 || unique.len() != choices.len() || choices.iter().any(String::is_empty) {
+                anyhow::bail!("TRACK_CONFIG 中 track {} 的 choices 至少需要两个不重复的非空选项", track_id);
+            }
+        }
+        if let Some(threshold) = &mut entry.threshold {
+            *threshold = threshold.trim().to_string();
+            threshold.parse::<u128>().map_err(|_| {
+                anyhow::anyhow!(
+                    "TRACK_CONFIG 中 track {} 的 threshold 必须是以 planck 为单位的非负整数：{}",
+                    track_id,
+                    threshold
+                )
+            })?;
+        }
+        overrides.insert(track_id, entry);
+    }
+    Ok(overrides)
+}
+
+/// 配置项的来源：覆盖值优先，未覆盖的按 `use_env` 读取进程环境变量或视为未设置
+struct Vars<'a> {
+    overrides: &'a HashMap<String, String>,
+    use_env: bool,
+}
+
+impl Vars<'_> {
+    /// 读取单个配置项，语义与 `env::var` 相同
+    fn var(&self, name: &str) -> Result<String, env::VarError> {
+        match self.overrides.get(name) {
+            Some(v) => Ok(v.clone()),
+            None if self.use_env => env::var(name),
+            None => Err(env::VarError::NotPresent),
+        }
+    }
+
+    /// 读取 URL 型配置项并校验格式，返回去掉末尾 `/` 的地址
+    fn url(&self, name: &str, default: &str) -> anyhow::Result<String> {
+        let raw = self.var(name).unwrap_or_else(|_| default.into());
+        reqwest::Url::parse(&raw)
+            .map_err(|e| anyhow::anyhow!("{} 不是合法的 URL：{}（{}）", name, raw, e))?;
+        Ok(raw.trim_end_matches('/').to_string())
+    }
+
+    /// 读取字符串型配置项，未设置时取默认值，设置为空白时报错
+    fn non_empty(&self, name: &str, default: &str) -> anyhow::Result<String> {
+        let value = self.var(name).unwrap_or_else(|_| default.into()).trim().to_string();
+        if value.is_empty() {
+            anyhow::bail!("{} 不能为空", name);
+        }
+        Ok(value)
+    }
+
+    /// 读取密钥：`NAME` 直接给出，或 `NAME_FILE` 指向的文件内容（去掉末尾空白），均未设置时返回 None
+    fn secret(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let file_var = format!("{}_FILE", name);
+        let direct = self.var(name).ok();
+        let path = self.var(&file_var).ok().filter(|p| !p.trim().is_empty());
+        match (direct, path) {
+            (Some(_), Some(_)) => anyhow::bail!("{} 与 {} 不能同时设置", name, file_var),
+            (Some(value), None) => Ok(Some(value)),
+            (None, Some(path)) => {
+                let path = path.trim();
+                let value = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("无法读取 {} 指向的文件 {}：{}", file_var, path, e))?;
+                Ok(Some(value.trim_end().to_string()))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// 解析布尔型配置项，支持 true/false、1/0、yes/no
+    fn flag(&self, name: &str, default: bool) -> bool {
+        match self.var(name) {
+            Ok(v) => matches!(v.trim().to_ascii_lowercase().as_str(), "true" | "1" | "yes" | "on"),
+            Err(_) => default,
+        }
+    }
+}
+
+/// 加载当前目录下的 .env 文件，返回生效的 PROFILE
+///
+/// 进程环境变量设置了 PROFILE（如 dev / staging / prod）时先加载 `.env.<profile>`，再加载 `.env`；
+/// 已存在的变量不会被后加载的文件覆盖，因此优先级为 进程环境变量 > `.env.<profile>` > `.env`。
+/// PROFILE 对应的文件不存在时报错，避免误用基础配置连到错误的环境
+pub fn load_dotenv() -> anyhow::Result<Option<String>> {
+    let profile = env::var("PROFILE").ok().filter(|p| !p.trim().is_empty());
+    load_dotenv_from(Path::new("."), profile.as_deref())?;
+    Ok(profile)
+}
+
+fn load_dotenv_from(dir: &Path, profile: Option<&str>) -> anyhow::Result<()> {
+    if let Some(profile) = profile {
+        let profile = profile.trim();
+        if !profile.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            anyhow::bail!("PROFILE 只能包含字母、数字、- 和 _：{}", profile);
+        }
+        let path = dir.join(format!(".env.{}", profile));
+        dotenv::from_path(&path)
+            .map_err(|e| anyhow::anyhow!("无法加载 PROFILE={} 的配置文件 {}：{}", profile, path.display(), e))?;
+    }
+    // 基础 .env 可以不存在
+    let _ = dotenv::from_path(dir.join(".env"));
+    Ok(())
+}
+
+/// 由 PUBLISHED_TITLE_PREFIX / CONTENT_FOOTER 构造内置转换器，按此顺序应用
+fn builtin_transformers(vars: &Vars, published_title_prefix: Option<&str>) -> Vec<Arc<dyn ProposalTransformer>> {
+    let mut transformers: Vec<Arc<dyn ProposalTransformer>> = Vec::new();
+    if let Some(prefix) = published_title_prefix {
+        transformers.push(Arc::new(TitlePrefix(prefix.to_string())));
+    }
+    if let Some(footer) = vars.var("CONTENT_FOOTER").ok().filter(|s| !s.trim().is_empty()) {
+        transformers.push(Arc::new(ContentFooter(footer.trim().to_string())));
+    }
+    transformers
 }
 
 impl Config {
     /// 从环境变量加载配置，未设置时使用默认值
     pub fn from_env() -> anyhow::Result<Self> {
-        // 如果存在 .env 文件，优先加载
-        let _ = dotenv::dotenv();
+        // 如果存在 .env 文件（及 PROFILE 对应的 .env.<profile>），优先加载
+        load_dotenv()?;
+        Self::from_env_with_overrides(HashMap::new())
+    }
+
+    /// 与 `from_env` 相同，但 `overrides` 中的键优先于环境变量，且不加载 .env
+    ///
+    /// 供测试和嵌入方注入配置，无需修改进程级的环境变量
+    pub fn from_env_with_overrides(overrides: HashMap<String, String>) -> anyhow::Result<Self> {
+        Self::load(Vars { overrides: &overrides, use_env: true })
+    }
 
-        let open_square_space = env::var("OPEN_SQUARE_SPACE").unwrap_or_else(|_| "".into());
-        let postgres_url = env::var("POSTGRES_URL")?;
-        let http_timeout_secs: u64 = env::var("HTTP_TIMEOUT_SECS")
+    /// 只从 `vars` 读取配置，不读进程环境变量，也不加载 .env
+    ///
+    /// 结果不受进程环境（如开发者 shell 中导出的 TITLE_PREFIX）影响，测试应使用它构造配置
+    pub fn from_vars(vars: HashMap<String, String>) -> anyhow::Result<Self> {
+        Self::load(Vars { overrides: &vars, use_env: false })
+    }
+
+    fn load(vars: Vars) -> anyhow::Result<Self> {
+
+        let mut open_square_spaces: Vec<String> = Vec::new();
+        for space in vars.var("OPEN_SQUARE_SPACE").unwrap_or_default().split(',').map(str::trim) {
+            if !space.is_empty() && !open_square_spaces.iter().any(|s| s == space) {
+                open_square_spaces.push(space.to_string());
+            }
+        }
+        if open_square_spaces.is_empty() {
+            anyhow::bail!("OPEN_SQUARE_SPACE 未设置或为空，无法确定发布到哪个 OpenSquare 空间");
+        }
+        let postgres_url = vars.var("POSTGRES_URL")?;
+        let http_timeout_secs: u64 = vars.var("HTTP_TIMEOUT_SECS")
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(10);
-        let snapshot_offset: u64 = env::var("SNAPSHOT_OFFSET")
+        let http_connect_timeout_secs: u64 = vars.var("HTTP_CONNECT_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(5)
+            .min(http_timeout_secs);
+        let http_pool_max_idle_per_host = match vars.var("HTTP_POOL_MAX_IDLE_PER_HOST") {
+            Ok(v) if !v.trim().is_empty() => Some(v.trim().parse::<usize>().map_err(|_| {
+                anyhow::anyhow!("HTTP_POOL_MAX_IDLE_PER_HOST 须为非负整数：{}", v.trim())
+            })?),
+            _ => None,
+        };
+        let http_pool_idle_timeout = match vars.var("HTTP_POOL_IDLE_TIMEOUT_SECS") {
+            Ok(v) if !v.trim().is_empty() => Some(Duration::from_secs(v.trim().parse().map_err(|_| {
+                anyhow::anyhow!("HTTP_POOL_IDLE_TIMEOUT_SECS 须为非负整数（秒）：{}", v.trim())
+            })?)),
+            _ => None,
+        };
+        let snapshot_offset: u64 = vars.var("SNAPSHOT_OFFSET")
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(50);
-        let mnemonic = env::var("MNEMONIC")?;
-        let subscan_api_key = env::var("SUBSCAN_API_KEY")?;
-        let page_size: usize = env::var("PAGE_SIZE")
+        let mnemonic = Secret::new(
+            vars.secret("MNEMONIC")?
+                .ok_or_else(|| anyhow::anyhow!("必须设置 MNEMONIC 或 MNEMONIC_FILE"))?,
+        );
+        let block_source: BlockSourceKind = vars.var("BLOCK_SOURCE").unwrap_or_default().parse()?;
+        // 只有使用 Subscan 作为区块来源时才强制要求 API Key
+        let subscan_api_key = vars
+            .secret("SUBSCAN_API_KEY")?
+            .filter(|k| !k.trim().is_empty())
+            .map(Secret::new);
+        let page_size: usize = vars.var("PAGE_SIZE")
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(50);
+        let db_connect_retries: u32 = vars.var("DB_CONNECT_RETRIES")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(5);
+        let db_connect_backoff_secs: u64 = vars.var("DB_CONNECT_BACKOFF_SECS")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(2);
+        let db_insert_retries: u32 = vars.var("DB_INSERT_RETRIES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3);
+        let db_insert_backoff_ms: u64 = vars.var("DB_INSERT_BACKOFF_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(500);
+        let include_nonce = vars.flag("INCLUDE_NONCE", false);
+        let max_content_chars: usize = vars.var("MAX_CONTENT_CHARS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(20000);
+        let summary_preference: SummaryPreference = vars.var("SUMMARY_PREFERENCE")
+            .unwrap_or_default()
+            .parse()?;
+        let content_source: ContentSource = vars.var("CONTENT_SOURCE").unwrap_or_default().parse()?;
+        if summary_preference != SummaryPreference::Default && content_source != ContentSource::default() {
+            anyhow::bail!("SUMMARY_PREFERENCE 与 CONTENT_SOURCE 不能同时设置");
+        }
+        let content_type: ContentType = vars.var("CONTENT_TYPE").unwrap_or_default().parse()?;
+        let set_discussion_link = vars.flag("SET_DISCUSSION_LINK", false);
+        let discussion_link_pattern = vars.var("DISCUSSION_LINK_PATTERN")
+            .ok()
+            .filter(|s| !s.trim().is_empty());
+        let network: Network = vars.var("NETWORK").unwrap_or_default().parse()?;
+        let subsquare_base_url = vars.url("SUBSQUARE_BASE_URL", network.subsquare_api_url())?;
+        let subscan_base_url = vars.url("SUBSCAN_BASE_URL", network.subscan_api_url())?;
+        let opensquare_base_url = vars.url("OPENSQUARE_BASE_URL", "https://voting.opensquare.io")?;
+        let publish_concurrency: usize = vars.var("PUBLISH_CONCURRENCY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1)
+            .max(1);
+        let publish_order: PublishOrder = vars.var("PUBLISH_ORDER").unwrap_or_default().parse()?;
+        let key_type: KeyType = vars.var("KEY_TYPE").unwrap_or_default().parse()?;
+        let fetch_mode: FetchMode = vars.var("FETCH_MODE").unwrap_or_default().parse()?;
+        let rate_limit_rps: Option<u32> = vars.var("RATE_LIMIT_RPS")
+            .ok()
+            .and_then(|s| s.parse().ok());
+        let update_on_title_change = vars.flag("UPDATE_ON_TITLE_CHANGE", false);
+        let allow_updates = vars.flag("ALLOW_UPDATES", false);
+        let extra_headers = parse_headers(&vars.var("EXTRA_HEADERS").unwrap_or_default())?;
+        let idempotency_header = match vars.var("IDEMPOTENCY_HEADER") {
+            Ok(v) if !v.trim().is_empty() => {
+                let name = v.trim();
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| anyhow::anyhow!("IDEMPOTENCY_HEADER 不是合法的请求头名：{}", name))?;
+                Some(name.to_string())
+            }
+            _ => None,
+        };
+        let rpc_url = vars.url("RPC_URL", network.rpc_url())?;
+        let notify_webhook_url = match vars.var("NOTIFY_WEBHOOK_URL") {
+            Ok(v) if !v.trim().is_empty() => Some(vars.url("NOTIFY_WEBHOOK_URL", "")?),
+            _ => None,
+        };
+        let notify_on: NotifyOn = vars.var("NOTIFY_ON").unwrap_or_default().parse()?;
+        let snapshot_networks = parse_snapshot_networks(
+            &vars.var("SNAPSHOT_NETWORKS").unwrap_or_else(|_| network.name().into()),
+        )?;
+        let snapshot_partial_failure: SnapshotFailureMode = vars.var("SNAPSHOT_PARTIAL_FAILURE")
+            .unwrap_or_default()
+            .parse()?;
+        let notify_kind: NotifyKind = vars.var("NOTIFY_KIND").unwrap_or_default().parse()?;
+        let voting_threshold = match vars.var("VOTING_THRESHOLD") {
+            Ok(v) if !v.trim().is_empty() => {
+                let v = v.trim();
+                v.parse::<u128>().map_err(|_| {
+                    anyhow::anyhow!("VOTING_THRESHOLD 必须是以 planck 为单位的非负整数：{}", v)
+                })?;
+                Some(v.to_string())
+            }
+            _ => None,
+        };
+        let vote_multiplier = match vars.var("VOTE_MULTIPLIER") {
+            Ok(v) if !v.trim().is_empty() => Some(
+                v.trim()
+                    .parse::<u32>()
+                    .map_err(|_| anyhow::anyhow!("VOTE_MULTIPLIER 必须是正整数：{}", v))?,
+            ),
+            _ => None,
+        };
+        let token_symbol = match vars.var("TOKEN_SYMBOL") {
+            Ok(v) if !v.trim().is_empty() => v.trim().to_string(),
+            _ => network.symbol().to_string(),
+        };
+        let token_decimals = match vars.var("TOKEN_DECIMALS") {
+            Ok(v) if !v.trim().is_empty() => {
+                let d = v
+                    .trim()
+                    .parse::<u8>()
+                    .map_err(|_| anyhow::anyhow!("TOKEN_DECIMALS 必须是非负整数：{}", v))?;
+                if d > 18 {
+                    anyhow::bail!("TOKEN_DECIMALS 不能超过 18：{}", d);
+                }
+                d
+            }
+            _ => network.decimals(),
+        };
+        let published_title_prefix = vars
+            .var("PUBLISHED_TITLE_PREFIX")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        let retry_max_attempts: u32 = vars.var("RETRY_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(5);
+        let retry_backoff_secs: u64 = vars.var("RETRY_BACKOFF_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(300);
+        let timezone = match vars.var("TIMEZONE") {
+            Ok(v) if !v.trim().is_empty() => Some(v.trim().parse::<Tz>().map_err(|e| {
+                anyhow::anyhow!("TIMEZONE 不是合法的 IANA 时区名：{}（{}）", v.trim(), e)
+            })?),
+            _ => None,
+        };
+        let proxy_url = match vars.var("PROXY_URL") {
+            Ok(v) if !v.trim().is_empty() => Some(vars.url("PROXY_URL", "")?),
+            _ => None,
+        };
+        let snapshot_cache_secs: u64 = vars.var("SNAPSHOT_CACHE_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        let circuit_breaker_threshold: usize = vars.var("CIRCUIT_BREAKER_THRESHOLD")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(5);
+        let max_clock_skew_secs: u64 = vars.var("MAX_CLOCK_SKEW_SECS")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(300);
+        let clock_skew_abort = match vars.var("CLOCK_SKEW_ABORT_SECS") {
+            Ok(v) if !v.trim().is_empty() => Some(Duration::from_secs(v.trim().parse().map_err(|_| {
+                anyhow::anyhow!("CLOCK_SKEW_ABORT_SECS 须为非负整数（秒）：{}", v.trim())
+            })?)),
+            _ => None,
+        };
+        let auth_rejection_threshold: usize = vars.var("AUTH_REJECTION_THRESHOLD")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3)
+            .max(1);
+        let startup_jitter_secs: u64 = vars.var("STARTUP_JITTER_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        let tick_jitter_secs: u64 = vars.var("TICK_JITTER_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        let db_pool_max_size: usize = vars.var("DB_POOL_MAX_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(4)
+            .max(1);
 
         Ok(Config {
-            open_square_space,
+            open_square_spaces,
             postgres_url,
+            postgres_tls: vars.flag("POSTGRES_TLS", false),
+            postgres_ca_cert: vars.var("POSTGRES_CA_CERT").ok().filter(|s| !s.trim().is_empty()),
             http_timeout: Duration::from_secs(http_timeout_secs),
+            http_connect_timeout: Duration::from_secs(http_connect_timeout_secs),
+            http_pool_max_idle_per_host,
+            http_pool_idle_timeout,
             snapshot_offset,
+            min_snapshot_height: vars
+                .var("MIN_SNAPSHOT_HEIGHT")
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(1),
+            snapshot_mode: vars.var("SNAPSHOT_MODE").unwrap_or_default().parse()?,
             mnemonic,
             subscan_api_key,
             page_size,
+            db_connect_retries,
+            db_connect_backoff: Duration::from_secs(db_connect_backoff_secs),
+            db_insert_retries,
+            db_insert_backoff: Duration::from_millis(db_insert_backoff_ms),
+            pending_inserts_file: vars
+                .var("PENDING_INSERTS_FILE")
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty()),
+            include_nonce,
+            max_content_chars,
+            summary_preference,
+            content_source,
+            content_type,
+            set_discussion_link,
+            discussion_link_pattern,
+            subsquare_base_url,
+            subscan_base_url,
+            opensquare_base_url,
+            publish_concurrency,
+            publish_order,
+            rate_limit_rps,
+            update_on_title_change,
+            allow_updates,
+            extra_headers,
+            block_source,
+            rpc_url,
+            db_pool_max_size,
+            verify_space: vars.flag("VERIFY_SPACE", false),
+            notify_webhook_url,
+            notify_on,
+            notify_kind,
+            voting_threshold,
+            vote_multiplier,
+            dedup_against_opensquare: vars.flag("DEDUP_AGAINST_OPENSQUARE", false),
+            snapshot_networks,
+            snapshot_partial_failure,
+            key_type,
+            signer_address: vars.var("SIGNER_ADDRESS").ok().filter(|s| !s.trim().is_empty()),
+            network,
+            ignore_indices: parse_index_ranges(&vars.var("IGNORE_INDICES").unwrap_or_default())?,
+            proposal_version: vars.non_empty("PROPOSAL_VERSION", "5")?,
+            networks_config_version: vars.non_empty("NETWORKS_CONFIG_VERSION", "4")?,
+            token_symbol,
+            token_decimals,
+            strategies: parse_strategies(
+                &vars.var("STRATEGIES").unwrap_or_else(|_| "one-person-one-vote".into()),
+            )?,
+            defer_untitled: vars.flag("DEFER_UNTITLED", false),
+            content_template: vars.var("CONTENT_TEMPLATE").ok().filter(|s| !s.trim().is_empty()),
+            retry_max_attempts,
+            retry_backoff: Duration::from_secs(retry_backoff_secs),
+            title_prefix: vars.var("TITLE_PREFIX").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+            title_suffix: vars.var("TITLE_SUFFIX").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+            transformers: builtin_transformers(&vars, published_title_prefix.as_deref()),
+            published_title_prefix,
+            timezone,
+            proxy_url,
+            danger_accept_invalid_certs: vars.flag("DANGER_ACCEPT_INVALID_CERTS", false),
+            snapshot_cache_ttl: Duration::from_secs(snapshot_cache_secs),
+            accessibility: vars.var("ACCESSIBILITY").unwrap_or_default().parse()?,
+            auth_rejection_threshold,
+            circuit_breaker_threshold,
+            max_clock_skew: (max_clock_skew_secs > 0).then(|| Duration::from_secs(max_clock_skew_secs)),
+            clock_skew_abort,
+            startup_jitter: Duration::from_secs(startup_jitter_secs),
+            tick_jitter: Duration::from_secs(tick_jitter_secs),
+            jitter_seed: vars.var("JITTER_SEED").ok().and_then(|s| s.trim().parse().ok()),
+            publish_window: match vars.var("PUBLISH_WINDOW") {
+                Ok(v) if !v.trim().is_empty() => Some(v.parse()?),
+                _ => None,
+            },
+            idempotency_header,
+            max_referendum_age: match vars.var("MAX_REFERENDUM_AGE_DAYS") {
+                Ok(v) if !v.trim().is_empty() => {
+                    let days: u64 = v.trim().parse().map_err(|_| {
+                        anyhow::anyhow!("MAX_REFERENDUM_AGE_DAYS 须为非负整数：{}", v.trim())
+                    })?;
+                    Some(Duration::from_secs(days * 24 * 60 * 60))
+                }
+                _ => None,
+            },
+            require_decision_deposit: vars.flag("REQUIRE_DECISION_DEPOSIT", false),
+            sync_states: parse_sync_states(
+                &vars.var("SYNC_STATES").unwrap_or_else(|_| "Deciding,Confirming".into()),
+            )?,
+            sanitize_content: vars.flag("SANITIZE_CONTENT", true),
+            include_spend_details: vars.flag("INCLUDE_SPEND_DETAILS", false),
+            include_track_in_content: vars.flag("INCLUDE_TRACK_IN_CONTENT", false),
+            cursor_lookback: vars
+                .var("CURSOR_LOOKBACK")
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(50),
+            fetch_mode,
+            dead_letter_dir: vars.var("DEAD_LETTER_DIR").ok().filter(|s| !s.trim().is_empty()),
+            dead_letter_max_files: vars
+                .var("DEAD_LETTER_MAX_FILES")
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(100)
+                .max(1),
+            track_overrides: match vars.var("TRACK_CONFIG") {
+                Ok(v) if !v.trim().is_empty() => parse_track_config(&v)?,
+                _ => HashMap::new(),
+            },
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn fixed_mode() {
+        let mode: SnapshotMode = "fixed: 123456".parse().unwrap();
+        assert_eq!(mode, SnapshotMode::Fixed(123_456));
+        assert_eq!(mode.resolve(999_950, Some(900)).unwrap(), 123_456);
+        assert!("fixed:abc".parse::<SnapshotMode>().is_err());
+        assert!("latest".parse::<SnapshotMode>().is_err());
+    }
+
+    #[test]
+    fn at_submission_requires_height() {
+        let mode: SnapshotMode = "at_submission".parse().unwrap();
+        assert_eq!(mode.resolve(950, Some(900)).unwrap(), 900);
+        assert!(mode.resolve(950, None).is_err());
+    }
+
+    #[test]
+    fn track_config_validates_track_ids_and_values() {
+        let overrides = parse_track_config(
+            r#"{"34": {"vote_duration_days": 45, "threshold": " 10000000000 "}, "30": {"choices": ["Yes", " No "]}}"#,
+        )
+        .unwrap();
+        assert_eq!(overrides[&34].vote_duration_days, Some(45));
+        assert_eq!(overrides[&34].threshold.as_deref(), Some("10000000000"));
+        assert_eq!(overrides[&30].choices, Some(vec!["Yes".to_string(), "No".to_string()]));
+
+        assert!(parse_track_config(r#"{"99": {"vote_duration_days": 7}}"#).is_err());
+        assert!(parse_track_config(r#"{"BigSpender": {"vote_duration_days": 7}}"#).is_err());
+        assert!(parse_track_config(r#"{"34": {"vote_duration_days": 0}}"#).is_err());
+        assert!(parse_track_config(r#"{"34": {"choices": ["Aye"]}}"#).is_err());
+        assert!(parse_track_config(r#"{"34": {"choices": ["Aye", "Aye"]}}"#).is_err());
+        assert!(parse_track_config(r#"{"34": {"threshold": "-1"}}"#).is_err());
+        assert!(parse_track_config(r#"{"34": {"duration": 7}}"#).is_err());
+        assert!(parse_track_config("[]").is_err());
+    }
+
+    #[test]
+    fn sync_states_dedup_and_reject_unknown() {
+        assert_eq!(
+            parse_sync_states("deciding, Confirming,DECIDING").unwrap(),
+            vec![ReferendumStatus::Deciding, ReferendumStatus::Confirming]
+        );
+        assert!(parse_sync_states(" , ").is_err());
+        assert!(parse_sync_states("Deciding,Voting").is_err());
+    }
+
+    fn required() -> HashMap<String, String> {
+        HashMap::from([
+            ("OPEN_SQUARE_SPACE".to_string(), "twodao".to_string()),
+            ("POSTGRES_URL".to_string(), "postgres://tdao@127.0.0.1/tdao".to_string()),
+            ("MNEMONIC".to_string(), "bottom drive obey lake curtain smoke basket hold race lonely fit walk".to_string()),
+            ("SUBSCAN_API_KEY".to_string(), "subscan-secret-key".to_string()),
+        ])
+    }
+
+    #[test]
+    fn http_pool_settings() {
+        let cfg = Config::from_vars(required()).unwrap();
+        assert_eq!(cfg.http_pool_max_idle_per_host, None);
+        assert_eq!(cfg.http_pool_idle_timeout, None);
+
+        let mut vars = required();
+        vars.insert("HTTP_TIMEOUT_SECS".into(), "3".into());
+        vars.insert("HTTP_POOL_MAX_IDLE_PER_HOST".into(), "8".into());
+        vars.insert("HTTP_POOL_IDLE_TIMEOUT_SECS".into(), "30".into());
+        let cfg = Config::from_vars(vars.clone()).unwrap();
+        assert_eq!(cfg.http_connect_timeout, Duration::from_secs(3));
+        assert_eq!(cfg.http_pool_max_idle_per_host, Some(8));
+        assert_eq!(cfg.http_pool_idle_timeout, Some(Duration::from_secs(30)));
+        assert!(cfg.http_client().is_ok());
+
+        vars.insert("HTTP_POOL_MAX_IDLE_PER_HOST".into(), "many".into());
+        assert!(Config::from_vars(vars).is_err());
+    }
+
+    #[test]
+    fn content_type_defaults_to_markdown() {
+        assert_eq!(Config::from_vars(required()).unwrap().content_type, ContentType::Markdown);
+        let mut vars = required();
+        vars.insert("CONTENT_TYPE".into(), " Plain ".into());
+        assert_eq!(Config::from_vars(vars.clone()).unwrap().content_type, ContentType::Plain);
+        vars.insert("CONTENT_TYPE".into(), "rst".into());
+        assert!(Config::from_vars(vars.clone()).is_err());
+        vars.insert("CONTENT_TYPE".into(), "html".into());
+        let err = Config::from_vars(vars).unwrap_err();
+        assert!(err.to_string().contains("尚不支持"), "{}", err);
+    }
+
+    #[test]
+    fn pending_inserts_stay_in_memory_unless_a_file_is_set() {
+        assert_eq!(Config::from_vars(required()).unwrap().pending_inserts_file, None);
+        let mut vars = required();
+        vars.insert("PENDING_INSERTS_FILE".into(), "  ".into());
+        assert_eq!(Config::from_vars(vars.clone()).unwrap().pending_inserts_file, None);
+        vars.insert("PENDING_INSERTS_FILE".into(), " /var/lib/tdao/pending.json ".into());
+        assert_eq!(
+            Config::from_vars(vars).unwrap().pending_inserts_file.as_deref(),
+            Some("/var/lib/tdao/pending.json")
+        );
+    }
+
+    #[test]
+    fn key_type_defaults_to_sr25519() {
+        assert_eq!(Config::from_vars(required()).unwrap().key_type, KeyType::Sr25519);
+        let mut vars = required();
+        vars.insert("KEY_TYPE".into(), "ED25519".into());
+        assert_eq!(Config::from_vars(vars.clone()).unwrap().key_type, KeyType::Ed25519);
+        vars.insert("KEY_TYPE".into(), "ecdsa".into());
+        assert!(Config::from_vars(vars).is_err());
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let mut vars = required();
+        vars.insert("PAGE_SIZE".into(), "7".into());
+        vars.insert("OPEN_SQUARE_SPACE".into(), "a, b, a".into());
+        let cfg = Config::from_vars(vars).unwrap();
+        assert_eq!(cfg.page_size, 7);
+        assert_eq!(cfg.open_square_spaces, vec!["a", "b"]);
+        assert_eq!(cfg.subscan_api_key.unwrap().expose(), "subscan-secret-key");
+    }
+
+    #[test]
+    fn debug_redacts_secrets() {
+        let cfg = Config::from_vars(required()).unwrap();
+        let debug = format!("{:?}", cfg.clone());
+        assert!(!debug.contains("bottom drive"));
+        assert!(!debug.contains("subscan-secret-key"));
+        assert!(debug.contains("mnemonic: <redacted>"));
+    }
+
+    #[test]
+    fn from_vars_ignores_process_env() {
+        env::set_var("TITLE_SUFFIX", "from-shell");
+        let hermetic = Config::from_vars(required()).unwrap();
+        let layered = Config::from_env_with_overrides(required()).unwrap();
+        env::remove_var("TITLE_SUFFIX");
+        assert_eq!(hermetic.title_suffix, None);
+        assert_eq!(layered.title_suffix.as_deref(), Some("from-shell"));
+    }
+
+    #[test]
+    fn secrets_can_be_read_from_files() {
+        let path = env::temp_dir().join(format!("tdao-mnemonic-{}", std::process::id()));
+        std::fs::write(&path, "file words here\n\n").unwrap();
+        let mut vars = required();
+        vars.remove("MNEMONIC");
+        vars.insert("MNEMONIC_FILE".into(), path.display().to_string());
+        let cfg = Config::from_vars(vars.clone()).unwrap();
+        assert_eq!(cfg.mnemonic.expose(), "file words here");
+
+        vars.insert("MNEMONIC".into(), "direct words".into());
+        let err = Config::from_vars(vars).unwrap_err();
+        assert!(err.to_string().contains("不能同时设置"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn profile_file_overrides_base_env() {
+        let dir = env::temp_dir().join(format!("tdao-profile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(".env"), "TDAO_TEST_PROFILE_A=base\nTDAO_TEST_PROFILE_B=base\nTDAO_TEST_PROFILE_C=base\n").unwrap();
+        std::fs::write(dir.join(".env.staging"), "TDAO_TEST_PROFILE_B=staging\nTDAO_TEST_PROFILE_C=staging\n").unwrap();
+        env::set_var("TDAO_TEST_PROFILE_C", "process");
+
+        load_dotenv_from(&dir, Some("staging")).unwrap();
+        assert_eq!(env::var("TDAO_TEST_PROFILE_A").unwrap(), "base");
+        assert_eq!(env::var("TDAO_TEST_PROFILE_B").unwrap(), "staging");
+        assert_eq!(env::var("TDAO_TEST_PROFILE_C").unwrap(), "process");
+
+        assert!(load_dotenv_from(&dir, Some("prod")).is_err());
+        assert!(load_dotenv_from(&dir, Some("../staging")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn subscan_api_key_is_optional() {
+        let mut vars = required();
+        vars.insert("SUBSCAN_API_KEY".into(), " ".into());
+        let cfg = Config::from_vars(vars).unwrap();
+        assert_eq!(cfg.block_source, BlockSourceKind::Subscan);
+        assert!(cfg.subscan_api_key.is_none());
+    }
+
+    #[test]
+    fn unreadable_secret_file_is_an_error() {
+        let mut vars = required();
+        vars.remove("SUBSCAN_API_KEY");
+        vars.insert("SUBSCAN_API_KEY_FILE".into(), "/nonexistent/tdao/subscan-key".into());
+        let err = Config::from_vars(vars).unwrap_err();
+        assert!(err.to_string().contains("SUBSCAN_API_KEY_FILE"));
+    }
+
+    #[test]
+    fn daytime_window() {
+        let w: PublishWindow = "09:00-21:00".parse().unwrap();
+        assert!(w.contains(at(9, 0)));
+        assert!(w.contains(at(20, 59)));
+        assert!(!w.contains(at(21, 0)));
+        assert!(!w.contains(at(3, 0)));
+    }
+
+    #[test]
+    fn window_across_midnight() {
+        let w: PublishWindow = "22:00-06:00".parse().unwrap();
+        assert!(w.contains(at(23, 30)));
+        assert!(w.contains(at(5, 59)));
+        assert!(!w.contains(at(12, 0)));
+    }
+
+    #[test]
+    fn invalid_window_is_rejected() {
+        assert!("09:00".parse::<PublishWindow>().is_err());
+        assert!("9am-5pm".parse::<PublishWindow>().is_err());
+        assert!("09:00-09:00".parse::<PublishWindow>().is_err());
+    }
+}
@@ -1,60 +1,1173 @@
 
-use tokio_postgres::{Client, NoTls};
-use tokio::task;
-use anyhow::Result;
-use log::error; 
+use deadpool_postgres::{Manager, ManagerConfig, Object, Pool, RecyclingMethod};
+use native_tls::{Certificate, TlsConnector};
+use postgres_native_tls::MakeTlsConnector;
+use tokio_postgres::config::SslMode;
+use tokio_postgres::{NoTls, Row};
+use tokio_postgres::error::SqlState;
+use tokio::time::sleep;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{error, warn, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use uuid::Uuid;
 
-/// 数据库客户端封装
+/// 待写入的公投记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewReferendum {
+    pub referendum_index: u32,
+    pub track_id: u16,
+    /// 实际发布的提案标题
+    pub title: String,
+    /// SubSquare 返回的摘要
+    pub content_summary: Option<String>,
+    /// 发布时使用的快照块高度
+    pub snapshot_height: u64,
+    /// 签名载荷中的 nonce，后续更新提案时引用
+    pub nonce: Option<String>,
+    /// 发布时标题和正文的哈希，用于检测上游变化
+    pub content_hash: String,
+    /// 发布时的链上状态，如 Deciding
+    pub state: String,
+}
+
+/// 已同步的公投记录；早期只存编号的行其余字段为空
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReferendumRecord {
+    pub referendum_index: u32,
+    pub track_id: Option<u16>,
+    pub title: Option<String>,
+    pub content_summary: Option<String>,
+    pub snapshot_height: Option<u64>,
+    pub nonce: Option<String>,
+    /// 同步时间；该列上线前已存在的行为空
+    pub synced_at: Option<DateTime<Utc>>,
+    pub content_hash: Option<String>,
+    /// 发布时的链上状态；该列上线前已存在的行为空
+    pub state: Option<String>,
+}
+
+/// 公投发布到单个 OpenSquare 空间的记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpacePublish {
+    pub referendum_index: u32,
+    pub space: String,
+    pub cid: Option<String>,
+    pub published_at: DateTime<Utc>,
+}
+
+/// `list_referenda` 按公投编号排序的方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// 分页查询的一页记录及表中总条数
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferendaPage {
+    pub records: Vec<ReferendumRecord>,
+    pub total: u64,
+}
+
+/// 发布失败、等待 `--retry-failed` 重试的公投
+#[derive(Debug, Clone)]
+pub struct FailedPublish {
+    pub referendum_index: u32,
+    pub last_error: String,
+    pub attempt_count: u32,
+    pub last_attempt_at: DateTime<Utc>,
+}
+
+/// sync_events 审计记录的事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncEventKind {
+    /// 从 SubSquare 拉取了一批公投
+    Fetched,
+    /// 跳过某条公投，detail 为原因
+    Skipped,
+    /// 推迟发布（无标题或不在发布时段）
+    Deferred,
+    /// 发布到某个空间成功，detail 含空间与 CID
+    Published,
+    /// 更新已发布的提案（ALLOW_UPDATES），detail 含空间与新旧 CID
+    Updated,
+    /// 发布失败，detail 为错误信息
+    Failed,
+}
+
+impl SyncEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncEventKind::Fetched => "fetched",
+            SyncEventKind::Skipped => "skipped",
+            SyncEventKind::Deferred => "deferred",
+            SyncEventKind::Published => "published",
+            SyncEventKind::Updated => "updated",
+            SyncEventKind::Failed => "failed",
+        }
+    }
+}
+
+/// 获取连接失败时的重连次数
+const RECONNECT_RETRIES: u32 = 3;
+
+/// 重连的初始退避时间
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// 启动时连接重试的单次退避上限
+const MAX_CONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// 启动连接第 `attempt` 次（从 0 开始）失败后的等待时间：`backoff` 起按 2 倍递增，不超过 MAX_CONNECT_BACKOFF
+fn connect_backoff(backoff: Duration, attempt: u32) -> Duration {
+    backoff.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_CONNECT_BACKOFF)
+}
+
+/// 数据库连接池封装
 pub struct Db {
-    client: Client,
+    pool: Pool,
+    /// 已发布成功但写库失败的公投记录，下一轮同步时补写
+    pending_inserts: Mutex<Vec<NewReferendum>>,
+    /// 待补写队列的落盘文件（PENDING_INSERTS_FILE），进程重启后从中恢复，避免重复发布
+    pending_file: Option<PathBuf>,
+    /// 测试中注入的写库失败次数，每次 `insert_referendum` 消耗一次
+    #[cfg(test)]
+    injected_insert_failures: std::sync::atomic::AtomicU32,
+}
+
+/// 查询 referenda 完整记录时使用的列，顺序与 `record_from_row` 对应
+const RECORD_COLUMNS: &str =
+    "referendum_index, track_id, title, content_summary, snapshot_height, nonce, synced_at, content_hash, state";
+
+/// 将一行查询结果映射为 `ReferendumRecord`
+fn record_from_row(r: &Row) -> ReferendumRecord {
+    ReferendumRecord {
+        referendum_index: r.get::<_, i32>(0) as u32,
+        track_id: r.get::<_, Option<i32>>(1).map(|t| t as u16),
+        title: r.get(2),
+        content_summary: r.get(3),
+        snapshot_height: r.get::<_, Option<i64>>(4).map(|h| h as u64),
+        nonce: r.get(5),
+        synced_at: r.get(6),
+        content_hash: r.get(7),
+        state: r.get(8),
+    }
+}
+
+/// 判断错误是否为可重试的瞬时数据库错误（死锁、序列化冲突、连接中断等）
+fn is_transient(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<tokio_postgres::Error>() {
+        Some(e) if e.is_closed() => true,
+        Some(e) => match e.code() {
+            Some(code) => *code == SqlState::T_R_DEADLOCK_DETECTED
+                || *code == SqlState::T_R_SERIALIZATION_FAILURE,
+            // 没有 SQLSTATE 时只有底层 IO 错误（连接被重置等）可重试，协议或类型转换错误重试也无济于事
+            None => std::error::Error::source(e).is_some_and(|s| s.is::<std::io::Error>()),
+        },
+        None => err.downcast_ref::<std::io::Error>().is_some(),
+    }
+}
+
+/// 构建 Postgres 的 TLS 连接器，使用系统根证书，并额外信任 `ca_cert` 指定的 CA 证书
+fn tls_connector(ca_cert: Option<&str>) -> Result<MakeTlsConnector> {
+    let mut builder = TlsConnector::builder();
+    if let Some(path) = ca_cert {
+        let pem = std::fs::read(path).with_context(|| format!("读取 POSTGRES_CA_CERT 文件 {} 失败", path))?;
+        builder.add_root_certificate(
+            Certificate::from_pem(&pem).with_context(|| format!("POSTGRES_CA_CERT 文件 {} 不是有效的 PEM 证书", path))?,
+        );
+    }
+    Ok(MakeTlsConnector::new(builder.build()?))
 }
 
 impl Db {
-    /// 连接数据库并启动后台连接任务
-    pub async fn connect(db_url: &str) -> Result<Self> {
-        let (client, conn) = tokio_postgres::connect(db_url, NoTls).await?;
-        task::spawn(async move {
-            if let Err(e) = conn.await {
-                error!("❗️ Postgres 连接错误：{}", e);
+    /// 构建连接池并立即取一条连接验证数据库可达
+    ///
+    /// 连接归还时执行一次校验查询（Verified 回收），断开的连接会被丢弃重建，
+    /// 取代原先单连接后台任务中的错误日志
+    ///
+    /// `tls` 为 true 或连接串带 `sslmode=require` 时走 TLS，`ca_cert` 为额外信任的
+    /// PEM 格式 CA 证书路径（如云数据库的根证书）；否则沿用 `NoTls`，便于本地开发
+    pub async fn connect(db_url: &str, max_size: usize, tls: bool, ca_cert: Option<&str>) -> Result<Self> {
+        let mut pg_config: tokio_postgres::Config = db_url.parse()?;
+        let manager_config = ManagerConfig { recycling_method: RecyclingMethod::Verified };
+        let manager = if tls || pg_config.get_ssl_mode() == SslMode::Require {
+            // 显式开启时不允许服务端拒绝 TLS 后退回明文
+            pg_config.ssl_mode(SslMode::Require);
+            info!("🔒 Postgres 使用 TLS 连接");
+            Manager::from_config(pg_config, tls_connector(ca_cert)?, manager_config)
+        } else {
+            Manager::from_config(pg_config, NoTls, manager_config)
+        };
+        let pool = Pool::builder(manager).max_size(max_size).build()?;
+        let db = Db::from_pool(pool);
+        drop(db.client().await?);
+        Ok(db)
+    }
+
+    fn from_pool(pool: Pool) -> Self {
+        Db {
+            pool,
+            pending_inserts: Mutex::new(Vec::new()),
+            pending_file: None,
+            #[cfg(test)]
+            injected_insert_failures: Default::default(),
+        }
+    }
+
+    /// 把待补写队列落盘到 `path`，并载入上次进程退出时遗留的记录
+    ///
+    /// 写库失败时数据库多半也不可用，因此队列存到本地文件而不是数据库；文件不存在时视为空队列
+    pub fn with_pending_file(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let restored: Vec<NewReferendum> = match std::fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw)
+                .with_context(|| format!("PENDING_INSERTS_FILE {} 不是有效的待补写队列", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(anyhow::Error::new(e).context(format!("无法读取 PENDING_INSERTS_FILE {}", path.display()))),
+        };
+        if !restored.is_empty() {
+            warn!("🗄 从 {} 恢复 {} 条待补写的公投", path.display(), restored.len());
+        }
+        self.pending_inserts = Mutex::new(restored);
+        self.pending_file = Some(path);
+        Ok(self)
+    }
+
+    /// 把当前队列写入 PENDING_INSERTS_FILE，队列为空时删除文件；写文件失败只记日志，内存中的队列仍然有效
+    fn persist_pending(&self, pending: &[NewReferendum]) {
+        let Some(path) = &self.pending_file else { return };
+        let written = if pending.is_empty() {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        } else {
+            serde_json::to_vec_pretty(pending)
+                .map_err(anyhow::Error::from)
+                .and_then(|raw| std::fs::write(path, raw).map_err(anyhow::Error::from))
+        };
+        if let Err(e) = written {
+            error!("❌ 无法写入待补写队列文件 {}，重启后这些公投可能被重复发布：{:#}", path.display(), e);
+        }
+    }
+
+    /// 与 `connect` 相同，但连接失败时最多重试 `retries` 次（DB_CONNECT_RETRIES）
+    ///
+    /// 容器编排中数据库常比应用晚几秒就绪，启动时重试可避免进程反复崩溃重启；
+    /// 连接串无法解析时直接返回错误，不做重试
+    pub async fn connect_with_retry(
+        db_url: &str,
+        max_size: usize,
+        tls: bool,
+        ca_cert: Option<&str>,
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<Self> {
+        db_url.parse::<tokio_postgres::Config>().context("POSTGRES_URL 无法解析")?;
+        let mut attempt = 0;
+        loop {
+            match Self::connect(db_url, max_size, tls, ca_cert).await {
+                Ok(db) => {
+                    if attempt > 0 {
+                        info!("🗄 第 {} 次尝试连接 Postgres 成功", attempt + 1);
+                    }
+                    return Ok(db);
+                }
+                Err(e) if attempt < retries => {
+                    let delay = connect_backoff(backoff, attempt);
+                    attempt += 1;
+                    warn!("⏳ 连接 Postgres 失败（第 {}/{} 次重试将在 {:?} 后进行）：{:#}", attempt, retries, delay, e);
+                    sleep(delay).await;
+                }
+                Err(e) => return Err(e.context(format!("连接 Postgres 失败，已重试 {} 次", retries))),
+            }
+        }
+    }
+
+    /// 从连接池取一条连接，失败时按指数退避重试
+    ///
+    /// 断开的连接在取出时校验失败会被丢弃并重建，因此数据库恢复后无需重启进程；
+    /// 重试耗尽时返回明确的“数据库不可用”错误，由下一轮同步再次尝试
+    async fn client(&self) -> Result<Object> {
+        let mut attempt = 0;
+        loop {
+            match self.pool.get().await {
+                Ok(client) => return Ok(client),
+                Err(e) if attempt < RECONNECT_RETRIES => {
+                    let delay = RECONNECT_BACKOFF * 2u32.pow(attempt);
+                    attempt += 1;
+                    warn!("❗️ 获取 Postgres 连接失败（{:?} 后第 {} 次重连）：{}", delay, attempt, e);
+                    sleep(delay).await;
+                }
+                Err(e) => {
+                    error!("❗️ Postgres 连接失败，已重试 {} 次：{}", RECONNECT_RETRIES, e);
+                    return Err(anyhow::anyhow!("数据库不可用：{}", e));
+                }
             }
-        });
-        Ok(Db { client })
+        }
     }
 
     /// 初始化数据库表和索引
     pub async fn init_schema(&self) -> Result<()> {
-        self.client.execute(
+        let client = self.client().await?;
+        client.execute(
             "CREATE TABLE IF NOT EXISTS referenda (
                 id SERIAL PRIMARY KEY,
                 referendum_index INTEGER UNIQUE
             )",
             &[],
         ).await?;
-        self.client.execute(
+        client.execute(
             "CREATE UNIQUE INDEX IF NOT EXISTS idx_referendum_index \
              ON referenda (referendum_index)",
             &[],
         ).await?;
+        client.batch_execute(
+            "ALTER TABLE referenda ADD COLUMN IF NOT EXISTS nonce TEXT;
+             ALTER TABLE referenda ADD COLUMN IF NOT EXISTS track_id INTEGER;
+             ALTER TABLE referenda ADD COLUMN IF NOT EXISTS title TEXT;
+             ALTER TABLE referenda ADD COLUMN IF NOT EXISTS content_summary TEXT;
+             ALTER TABLE referenda ADD COLUMN IF NOT EXISTS snapshot_height BIGINT;
+             ALTER TABLE referenda ADD COLUMN IF NOT EXISTS synced_at TIMESTAMPTZ;
+             ALTER TABLE referenda ALTER COLUMN synced_at SET DEFAULT now();
+             ALTER TABLE referenda ADD COLUMN IF NOT EXISTS content_hash TEXT;
+             ALTER TABLE referenda ADD COLUMN IF NOT EXISTS state TEXT;",
+        ).await?;
+        client.execute(
+            "CREATE TABLE IF NOT EXISTS space_publishes (
+                referendum_index INTEGER NOT NULL,
+                space TEXT NOT NULL,
+                published_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                PRIMARY KEY (referendum_index, space)
+            )",
+            &[],
+        ).await?;
+        client.execute("ALTER TABLE space_publishes ADD COLUMN IF NOT EXISTS cid TEXT", &[]).await?;
+        client.execute(
+            "CREATE TABLE IF NOT EXISTS failed_publishes (
+                referendum_index INTEGER PRIMARY KEY,
+                last_error TEXT NOT NULL,
+                attempt_count INTEGER NOT NULL DEFAULT 1,
+                last_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )",
+            &[],
+        ).await?;
+        client.execute(
+            "CREATE TABLE IF NOT EXISTS sync_state (
+                name TEXT PRIMARY KEY,
+                value BIGINT NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )",
+            &[],
+        ).await?;
+        client.batch_execute(
+            "CREATE TABLE IF NOT EXISTS sync_events (
+                id BIGSERIAL PRIMARY KEY,
+                run_id UUID NOT NULL,
+                kind TEXT NOT NULL,
+                referendum_index INTEGER,
+                detail TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            );
+            CREATE INDEX IF NOT EXISTS idx_sync_events_run_id ON sync_events (run_id);
+            CREATE INDEX IF NOT EXISTS idx_sync_events_referendum_index ON sync_events (referendum_index);",
+        ).await?;
         Ok(())
     }
 
     /// 获取已同步的所有公投编号（按编号升序）
     pub async fn get_existing_indices(&self) -> Result<Vec<i32>> {
-        let rows = self.client
+        let rows = self.client().await?
             .query("SELECT referendum_index FROM referenda ORDER BY referendum_index", &[])
             .await?;
         Ok(rows.iter().map(|r| r.get(0)).collect())
     }
 
-    /// 插入新的公投编号记录
-    pub async fn insert_referendum(&self, referendum_index: u32) -> Result<u64> {
+    /// 从候选编号中筛出尚未同步的，保持输入顺序
+    ///
+    /// 只查询候选编号本身（`= ANY($1)`），不必每轮把全部已同步编号载入内存
+    pub async fn filter_unsynced(&self, indices: &[u32]) -> Result<Vec<u32>> {
+        if indices.is_empty() {
+            return Ok(Vec::new());
+        }
+        let candidates: Vec<i32> = indices.iter().map(|&i| i as i32).collect();
+        let rows = self.client().await?
+            .query(
+                "SELECT referendum_index FROM referenda WHERE referendum_index = ANY($1)",
+                &[&candidates],
+            )
+            .await?;
+        let synced: HashSet<u32> = rows.iter().map(|r| r.get::<_, i32>(0) as u32).collect();
+        Ok(indices.iter().copied().filter(|i| !synced.contains(i)).collect())
+    }
+
+    /// 已同步的公投条数
+    pub async fn count_synced(&self) -> Result<u64> {
+        let row = self.client().await?
+            .query_one("SELECT count(*) FROM referenda", &[])
+            .await?;
+        Ok(row.get::<_, i64>(0) as u64)
+    }
+
+    /// 插入新的公投记录，编号已存在时不做任何修改
+    ///
+    /// 返回实际插入的行数：1 表示新写入，0 表示该编号已存在（如并发运行或重试）
+    pub async fn insert_referendum(&self, record: &NewReferendum) -> Result<u64> {
+        #[cfg(test)]
+        if self
+            .injected_insert_failures
+            .fetch_update(std::sync::atomic::Ordering::SeqCst, std::sync::atomic::Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+        {
+            return Err(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "注入的写库失败").into());
+        }
+        let idx = record.referendum_index as i32;
+        let track_id = record.track_id as i32;
+        let snapshot_height = record.snapshot_height as i64;
+        let count = self.client().await?
+            .execute(
+                "INSERT INTO referenda \
+                 (referendum_index, track_id, title, content_summary, snapshot_height, nonce, content_hash, state, synced_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, now()) \
+                 ON CONFLICT (referendum_index) DO NOTHING",
+                &[
+                    &idx,
+                    &track_id,
+                    &record.title,
+                    &record.content_summary,
+                    &snapshot_height,
+                    &record.nonce,
+                    &record.content_hash,
+                    &record.state,
+                ],
+            )
+            .await?;
+        Ok(count)
+    }
+
+    /// 按导出的完整记录插入公投，保留原同步时间，已存在时不做修改；返回插入行数
+    pub async fn import_referendum(&self, record: &ReferendumRecord) -> Result<u64> {
+        let idx = record.referendum_index as i32;
+        let track_id = record.track_id.map(|t| t as i32);
+        let snapshot_height = record.snapshot_height.map(|h| h as i64);
+        let count = self.client().await?
+            .execute(
+                "INSERT INTO referenda \
+                 (referendum_index, track_id, title, content_summary, snapshot_height, nonce, content_hash, state, synced_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+                 ON CONFLICT (referendum_index) DO NOTHING",
+                &[
+                    &idx,
+                    &track_id,
+                    &record.title,
+                    &record.content_summary,
+                    &snapshot_height,
+                    &record.nonce,
+                    &record.content_hash,
+                    &record.state,
+                    &record.synced_at,
+                ],
+            )
+            .await?;
+        Ok(count)
+    }
+
+    /// 查询全部空间发布记录（按编号、空间排序）
+    pub async fn list_space_publishes(&self) -> Result<Vec<SpacePublish>> {
+        let rows = self.client().await?
+            .query(
+                "SELECT referendum_index, space, cid, published_at FROM space_publishes \
+                 ORDER BY referendum_index, space",
+                &[],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|r| SpacePublish {
+                referendum_index: r.get::<_, i32>(0) as u32,
+                space: r.get(1),
+                cid: r.get(2),
+                published_at: r.get(3),
+            })
+            .collect())
+    }
+
+    /// 按导出的记录插入空间发布记录，保留原发布时间，已存在时不做修改；返回插入行数
+    pub async fn import_space_publish(&self, publish: &SpacePublish) -> Result<u64> {
+        let idx = publish.referendum_index as i32;
+        let count = self.client().await?
+            .execute(
+                "INSERT INTO space_publishes (referendum_index, space, cid, published_at) \
+                 VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+                &[&idx, &publish.space, &publish.cid, &publish.published_at],
+            )
+            .await?;
+        Ok(count)
+    }
+
+    /// 查询单条已同步的公投记录
+    pub async fn get_referendum(&self, referendum_index: u32) -> Result<Option<ReferendumRecord>> {
         let idx = referendum_index as i32;
-        let count = self.client
+        let sql = format!("SELECT {} FROM referenda WHERE referendum_index = $1", RECORD_COLUMNS);
+        let row = self.client().await?
+            .query_opt(sql.as_str(), &[&idx])
+            .await?;
+        Ok(row.as_ref().map(record_from_row))
+    }
+
+    /// 查询全部已同步的公投记录（按编号升序）
+    pub async fn list_all_referenda(&self) -> Result<Vec<ReferendumRecord>> {
+        let sql = format!("SELECT {} FROM referenda ORDER BY referendum_index", RECORD_COLUMNS);
+        let rows = self.client().await?
+            .query(sql.as_str(), &[])
+            .await?;
+        Ok(rows.iter().map(record_from_row).collect())
+    }
+
+    /// 分页读取已同步记录，按编号排序后跳过 `offset` 条、最多返回 `limit` 条，同时返回总条数
+    pub async fn list_referenda(&self, limit: u32, offset: u32, order: SortOrder) -> Result<ReferendaPage> {
+        let client = self.client().await?;
+        let sql = format!(
+            "SELECT {} FROM referenda ORDER BY referendum_index {} LIMIT $1 OFFSET $2",
+            RECORD_COLUMNS,
+            order.as_sql()
+        );
+        let rows = client.query(sql.as_str(), &[&i64::from(limit), &i64::from(offset)]).await?;
+        let total = client.query_one("SELECT count(*) FROM referenda", &[]).await?;
+        Ok(ReferendaPage {
+            records: rows.iter().map(record_from_row).collect(),
+            total: total.get::<_, i64>(0) as u64,
+        })
+    }
+
+    /// 按发布时的链上状态统计已同步条数，状态列为空的旧记录归为 None
+    pub async fn count_by_state(&self) -> Result<Vec<(Option<String>, u64)>> {
+        let rows = self.client().await?
+            .query("SELECT state, count(*) FROM referenda GROUP BY state ORDER BY state NULLS LAST", &[])
+            .await?;
+        Ok(rows.iter().map(|r| (r.get(0), r.get::<_, i64>(1) as u64)).collect())
+    }
+
+    /// 最近一次写入同步记录的时间，没有任何带时间的记录时返回 None
+    pub async fn last_sync_time(&self) -> Result<Option<DateTime<Utc>>> {
+        let row = self.client().await?
+            .query_one("SELECT max(synced_at) FROM referenda", &[])
+            .await?;
+        Ok(row.get(0))
+    }
+
+    /// 拉取游标：上一轮完整处理过的最大公投编号，尚未记录时返回 None
+    pub async fn get_cursor(&self) -> Result<Option<u32>> {
+        let row = self.client().await?
+            .query_opt("SELECT value FROM sync_state WHERE name = 'cursor'", &[])
+            .await?;
+        Ok(row.map(|r| r.get::<_, i64>(0) as u32))
+    }
+
+    /// 推进拉取游标，只会前进不会后退
+    pub async fn set_cursor(&self, cursor: u32) -> Result<()> {
+        self.client().await?
             .execute(
-                "INSERT INTO referenda (referendum_index) VALUES ($1)",
+                "INSERT INTO sync_state (name, value) VALUES ('cursor', $1) \
+                 ON CONFLICT (name) DO UPDATE \
+                 SET value = GREATEST(sync_state.value, EXCLUDED.value), updated_at = now()",
+                &[&(cursor as i64)],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 已同步的最大公投编号（高水位），表为空时返回 None
+    pub async fn max_synced_index(&self) -> Result<Option<u32>> {
+        let row = self.client().await?
+            .query_one("SELECT max(referendum_index) FROM referenda", &[])
+            .await?;
+        Ok(row.get::<_, Option<i32>>(0).map(|i| i as u32))
+    }
+
+    /// 已同步的最小公投编号，表为空时返回 None
+    pub async fn min_synced_index(&self) -> Result<Option<u32>> {
+        let row = self.client().await?
+            .query_one("SELECT min(referendum_index) FROM referenda", &[])
+            .await?;
+        Ok(row.get::<_, Option<i32>>(0).map(|i| i as u32))
+    }
+
+    /// 已同步编号区间 `[min, max]` 内缺失的公投编号（按编号升序），通常意味着发布失败需要补发
+    pub async fn find_gaps(&self) -> Result<Vec<u32>> {
+        let rows = self.client().await?
+            .query(
+                "SELECT s.i FROM (SELECT min(referendum_index) AS lo, max(referendum_index) AS hi FROM referenda) b
+                 CROSS JOIN generate_series(b.lo, b.hi) AS s(i)
+                 WHERE NOT EXISTS (SELECT 1 FROM referenda r WHERE r.referendum_index = s.i)
+                 ORDER BY s.i",
+                &[],
+            )
+            .await?;
+        Ok(rows.iter().map(|r| r.get::<_, i32>(0) as u32).collect())
+    }
+
+    /// 删除公投的同步记录及其空间发布、发布失败记录，返回是否删除了同步记录
+    pub async fn delete_referendum(&self, referendum_index: u32) -> Result<bool> {
+        let idx = referendum_index as i32;
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let count = tx.execute("DELETE FROM referenda WHERE referendum_index = $1", &[&idx]).await?;
+        tx.execute("DELETE FROM space_publishes WHERE referendum_index = $1", &[&idx]).await?;
+        tx.execute("DELETE FROM failed_publishes WHERE referendum_index = $1", &[&idx]).await?;
+        tx.commit().await?;
+        Ok(count > 0)
+    }
+
+    /// 追加一条审计事件，只插入不修改；`index` 为空表示整轮的事件（如拉取）
+    pub async fn log_event(
+        &self,
+        run_id: Uuid,
+        kind: SyncEventKind,
+        referendum_index: Option<u32>,
+        detail: &str,
+    ) -> Result<()> {
+        let idx = referendum_index.map(|i| i as i32);
+        self.client().await?
+            .execute(
+                "INSERT INTO sync_events (run_id, kind, referendum_index, detail) VALUES ($1, $2, $3, $4)",
+                &[&run_id, &kind.as_str(), &idx, &detail],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 公投已发布成功的 OpenSquare 空间
+    pub async fn published_spaces(&self, referendum_index: u32) -> Result<Vec<String>> {
+        let idx = referendum_index as i32;
+        let rows = self.client().await?
+            .query("SELECT space FROM space_publishes WHERE referendum_index = $1", &[&idx])
+            .await?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
+    }
+
+    /// 记录公投已发布到某个空间及 OpenSquare 返回的提案 CID
+    ///
+    /// 重复记录时只在新的 CID 非空时覆盖，不会用空值抹掉已知的 CID
+    pub async fn record_space_publish(&self, referendum_index: u32, space: &str, cid: Option<&str>) -> Result<()> {
+        let idx = referendum_index as i32;
+        self.client().await?
+            .execute(
+                "INSERT INTO space_publishes (referendum_index, space, cid) VALUES ($1, $2, $3) \
+                 ON CONFLICT (referendum_index, space) DO UPDATE SET \
+                 cid = COALESCE(EXCLUDED.cid, space_publishes.cid)",
+                &[&idx, &space, &cid],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 公投在各空间已知的提案 CID（空间, CID），CID 列上线前发布的记录不包含在内
+    pub async fn published_cids(&self, referendum_index: u32) -> Result<Vec<(String, String)>> {
+        let idx = referendum_index as i32;
+        let rows = self.client().await?
+            .query(
+                "SELECT space, cid FROM space_publishes \
+                 WHERE referendum_index = $1 AND cid IS NOT NULL ORDER BY space",
                 &[&idx],
             )
             .await?;
-        Ok(count)
+        Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+    }
+
+    /// 提案更新后刷新本地记录的标题和内容哈希
+    pub async fn update_content(&self, referendum_index: u32, title: &str, content_hash: &str) -> Result<()> {
+        let idx = referendum_index as i32;
+        self.client().await?
+            .execute(
+                "UPDATE referenda SET title = $2, content_hash = $3 WHERE referendum_index = $1",
+                &[&idx, &title, &content_hash],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 记录一次发布失败，已有记录时累加尝试次数并更新错误信息
+    pub async fn record_publish_failure(&self, referendum_index: u32, error: &str) -> Result<()> {
+        let idx = referendum_index as i32;
+        self.client().await?
+            .execute(
+                "INSERT INTO failed_publishes (referendum_index, last_error, attempt_count, last_attempt_at) \
+                 VALUES ($1, $2, 1, now()) \
+                 ON CONFLICT (referendum_index) DO UPDATE SET \
+                 last_error = EXCLUDED.last_error, \
+                 attempt_count = failed_publishes.attempt_count + 1, \
+                 last_attempt_at = now()",
+                &[&idx, &error],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 发布成功后清除失败记录
+    pub async fn clear_publish_failure(&self, referendum_index: u32) -> Result<()> {
+        let idx = referendum_index as i32;
+        self.client().await?
+            .execute("DELETE FROM failed_publishes WHERE referendum_index = $1", &[&idx])
+            .await?;
+        Ok(())
+    }
+
+    /// 查询全部发布失败记录（按编号升序）
+    pub async fn list_failed_publishes(&self) -> Result<Vec<FailedPublish>> {
+        let rows = self.client().await?
+            .query(
+                "SELECT referendum_index, last_error, attempt_count, last_attempt_at \
+                 FROM failed_publishes ORDER BY referendum_index",
+                &[],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|r| FailedPublish {
+                referendum_index: r.get::<_, i32>(0) as u32,
+                last_error: r.get(1),
+                attempt_count: r.get::<_, i32>(2) as u32,
+                last_attempt_at: r.get(3),
+            })
+            .collect())
+    }
+
+    /// 插入公投记录，遇到瞬时错误时按指数退避重试
+    pub async fn insert_referendum_with_retry(
+        &self,
+        record: &NewReferendum,
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<u64> {
+        let mut attempt = 0;
+        loop {
+            match self.insert_referendum(record).await {
+                Ok(count) => return Ok(count),
+                Err(e) if attempt < retries && is_transient(&e) => {
+                    let delay = backoff * 2u32.saturating_pow(attempt);
+                    attempt += 1;
+                    warn!(
+                        "⚠️ 写入公投 #{} 失败（第 {} 次重试，{:?} 后）：{}",
+                        record.referendum_index, attempt, delay, e
+                    );
+                    sleep(delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// 记录待补写的公投，配置了 PENDING_INSERTS_FILE 时同时落盘
+    pub fn queue_pending_insert(&self, record: NewReferendum) {
+        let mut pending = self.pending_inserts.lock().unwrap();
+        if !pending.iter().any(|p| p.referendum_index == record.referendum_index) {
+            pending.push(record);
+            self.persist_pending(&pending);
+        }
+    }
+
+    /// 当前待补写的公投编号
+    pub fn pending_inserts(&self) -> Vec<u32> {
+        self.pending_inserts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|p| p.referendum_index)
+            .collect()
+    }
+
+    /// 补写上一轮遗留的公投编号，失败的继续留在队列中，返回成功条数
+    pub async fn reconcile_pending_inserts(&self, retries: u32, backoff: Duration) -> usize {
+        // 先不清空落盘文件：补写途中进程退出时，文件里的记录下次启动仍能恢复
+        let pending = std::mem::take(&mut *self.pending_inserts.lock().unwrap());
+        if pending.is_empty() {
+            return 0;
+        }
+        let mut reconciled = 0;
+        for record in pending {
+            match self.insert_referendum_with_retry(&record, retries, backoff).await {
+                Ok(0) => info!("🗄 待补写的公投 #{} 已在库中，跳过", record.referendum_index),
+                Ok(_) => {
+                    reconciled += 1;
+                    info!("🗄 已补写待插入的公投 #{}", record.referendum_index);
+                }
+                Err(e) => {
+                    error!("❌ 补写公投 #{} 仍然失败：{:?}", record.referendum_index, e);
+                    self.pending_inserts.lock().unwrap().push(record);
+                }
+            }
+        }
+        let pending = self.pending_inserts.lock().unwrap();
+        self.persist_pending(&pending);
+        reconciled
+    }
+}
+
+/// 供本模块和 service 的测试共用的数据库夹具
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+
+    /// 测试用数据库：每个测试独占一个随机命名的 schema，结束时整体删除
+    ///
+    /// 连接串取自 TEST_POSTGRES_URL。用到它的测试都标为 `#[ignore]`，需用 `cargo test -- --ignored` 显式运行；
+    /// 运行时未设置该变量直接 panic，不会在没连库的情况下报告通过
+    pub(crate) struct TestDb {
+        pub(crate) db: Db,
+        pub(crate) schema: String,
+        pub(crate) admin: tokio_postgres::Client,
+    }
+
+    impl TestDb {
+        pub(crate) async fn new() -> Self {
+            let url = std::env::var("TEST_POSTGRES_URL").expect("未设置 TEST_POSTGRES_URL，无法运行需要数据库的测试");
+            let base: tokio_postgres::Config = url.parse().expect("TEST_POSTGRES_URL 不是合法的连接串");
+            let (admin, conn) = base.connect(NoTls).await.expect("连接测试数据库失败");
+            tokio::spawn(conn);
+
+            let schema = format!("test_{}", uuid::Uuid::new_v4().simple());
+            admin.batch_execute(&format!("CREATE SCHEMA {}", schema)).await.unwrap();
+
+            // 连接池中的每条连接都把 search_path 指向该 schema，表和索引互不干扰
+            let mut pg_config = base.clone();
+            pg_config.options(format!("-c search_path={}", schema));
+            let manager = Manager::from_config(
+                pg_config,
+                NoTls,
+                ManagerConfig { recycling_method: RecyclingMethod::Verified },
+            );
+            let pool = Pool::builder(manager).max_size(2).build().unwrap();
+            let db = Db::from_pool(pool);
+            db.init_schema().await.unwrap();
+            TestDb { db, schema, admin }
+        }
+
+        /// 按编号写入若干条公投记录
+        pub(crate) async fn seed(&self, indices: &[u32]) {
+            for &i in indices {
+                self.db.insert_referendum(&record(i)).await.unwrap();
+            }
+        }
+
+        pub(crate) async fn cleanup(self) {
+            self.db.pool.close();
+            self.admin
+                .batch_execute(&format!("DROP SCHEMA {} CASCADE", self.schema))
+                .await
+                .unwrap();
+        }
+    }
+
+    pub(crate) fn record(referendum_index: u32) -> NewReferendum {
+        NewReferendum {
+            referendum_index,
+            track_id: 0,
+            title: format!("[Root] #{} - test", referendum_index),
+            content_summary: None,
+            snapshot_height: 1,
+            nonce: None,
+            content_hash: String::new(),
+            state: "Deciding".into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_support::{record, TestDb};
+
+    /// 不连接数据库的 Db：连接池惰性建立，只要注入的失败次数覆盖全部尝试就不会真正访问数据库
+    fn offline_db() -> Db {
+        let config: tokio_postgres::Config = "postgres://tdao@127.0.0.1:1/tdao".parse().unwrap();
+        let manager = Manager::from_config(config, NoTls, ManagerConfig { recycling_method: RecyclingMethod::Verified });
+        Db::from_pool(Pool::builder(manager).max_size(1).build().unwrap())
+    }
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tdao-pending-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn only_io_errors_are_transient_outside_postgres() {
+        let reset = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert!(is_transient(&reset.into()));
+        assert!(!is_transient(&anyhow::anyhow!("invalid input")));
+    }
+
+    #[tokio::test]
+    async fn failed_reconcile_keeps_queue_on_disk_across_restarts() {
+        let path = temp_file("restart");
+        let db = offline_db().with_pending_file(&path).unwrap();
+        db.queue_pending_insert(record(42));
+        db.queue_pending_insert(record(42));
+        assert_eq!(db.pending_inserts(), vec![42]);
+
+        // 模拟重启：新进程从文件恢复队列，补写再次失败时记录仍留在文件中
+        let restarted = offline_db().with_pending_file(&path).unwrap();
+        assert_eq!(restarted.pending_inserts(), vec![42]);
+        restarted.injected_insert_failures.store(2, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(restarted.reconcile_pending_inserts(1, Duration::from_millis(1)).await, 0);
+        assert_eq!(restarted.injected_insert_failures.load(std::sync::atomic::Ordering::SeqCst), 0);
+        let on_disk: Vec<NewReferendum> = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(on_disk, vec![record(42)]);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(offline_db().with_pending_file(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(offline_db().with_pending_file(&path).unwrap().pending_inserts().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn transient_insert_failure_is_retried_and_drains_queue() {
+        let t = TestDb::new().await;
+        let path = temp_file("drain");
+        let TestDb { db, schema, admin } = t;
+        let db = db.with_pending_file(&path).unwrap();
+        db.queue_pending_insert(record(8));
+        assert!(path.exists());
+
+        db.injected_insert_failures.store(1, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(db.reconcile_pending_inserts(2, Duration::from_millis(1)).await, 1);
+        assert!(db.pending_inserts().is_empty());
+        assert!(!path.exists());
+        assert_eq!(db.get_existing_indices().await.unwrap(), vec![8]);
+        TestDb { db, schema, admin }.cleanup().await;
+    }
+
+    #[test]
+    fn connect_backoff_doubles_up_to_cap() {
+        let base = Duration::from_secs(2);
+        assert_eq!(connect_backoff(base, 0), Duration::from_secs(2));
+        assert_eq!(connect_backoff(base, 1), Duration::from_secs(4));
+        assert_eq!(connect_backoff(base, 4), Duration::from_secs(32));
+        assert_eq!(connect_backoff(base, 5), MAX_CONNECT_BACKOFF);
+        assert_eq!(connect_backoff(base, 40), MAX_CONNECT_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn invalid_url_is_not_retried() {
+        let started = std::time::Instant::now();
+        let err = Db::connect_with_retry("not a url", 1, false, None, 5, Duration::from_secs(10))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("POSTGRES_URL"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn state_is_stored_and_counted() {
+        let t = TestDb::new().await;
+        t.seed(&[1, 2]).await;
+        let mut confirming = record(3);
+        confirming.state = "Confirming".into();
+        t.db.insert_referendum(&confirming).await.unwrap();
+
+        assert_eq!(t.db.get_referendum(3).await.unwrap().unwrap().state.as_deref(), Some("Confirming"));
+        assert_eq!(
+            t.db.count_by_state().await.unwrap(),
+            vec![(Some("Confirming".into()), 1), (Some("Deciding".into()), 2)]
+        );
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn referenda_are_paginated() {
+        let t = TestDb::new().await;
+        let page = t.db.list_referenda(10, 0, SortOrder::Asc).await.unwrap();
+        assert_eq!(page, ReferendaPage { records: vec![], total: 0 });
+
+        t.seed(&[4, 1, 3, 2, 5]).await;
+        let indices = |p: &ReferendaPage| p.records.iter().map(|r| r.referendum_index).collect::<Vec<_>>();
+
+        let full = t.db.list_referenda(2, 0, SortOrder::Asc).await.unwrap();
+        assert_eq!((indices(&full), full.total), (vec![1, 2], 5));
+        let partial = t.db.list_referenda(2, 4, SortOrder::Asc).await.unwrap();
+        assert_eq!((indices(&partial), partial.total), (vec![5], 5));
+        let past_end = t.db.list_referenda(2, 10, SortOrder::Asc).await.unwrap();
+        assert_eq!((indices(&past_end), past_end.total), (vec![], 5));
+        let desc = t.db.list_referenda(3, 0, SortOrder::Desc).await.unwrap();
+        assert_eq!(indices(&desc), vec![5, 4, 3]);
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn existing_indices_are_sorted() {
+        let t = TestDb::new().await;
+        t.seed(&[7, 3, 5]).await;
+        assert_eq!(t.db.get_existing_indices().await.unwrap(), vec![3, 5, 7]);
+        assert_eq!(t.db.count_synced().await.unwrap(), 3);
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn filter_unsynced_matches_full_scan() {
+        let t = TestDb::new().await;
+        t.seed(&[2, 4, 6]).await;
+        let candidates = [6, 1, 4, 5, 9];
+        let existing = t.db.get_existing_indices().await.unwrap();
+        let expected: Vec<u32> = candidates
+            .iter()
+            .copied()
+            .filter(|i| !existing.contains(&(*i as i32)))
+            .collect();
+        assert_eq!(t.db.filter_unsynced(&candidates).await.unwrap(), expected);
+        assert_eq!(expected, vec![1, 5, 9]);
+        assert!(t.db.filter_unsynced(&[]).await.unwrap().is_empty());
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn duplicate_insert_is_ignored() {
+        let t = TestDb::new().await;
+        assert_eq!(t.db.insert_referendum(&record(1)).await.unwrap(), 1);
+        let mut dup = record(1);
+        dup.title = "changed".into();
+        assert_eq!(t.db.insert_referendum(&dup).await.unwrap(), 0);
+        let stored = t.db.get_referendum(1).await.unwrap().unwrap();
+        assert_eq!(stored.title, Some(record(1).title));
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn publish_failures_accumulate_and_clear() {
+        let t = TestDb::new().await;
+        t.db.record_publish_failure(5, "HTTP 500").await.unwrap();
+        t.db.record_publish_failure(5, "HTTP 502").await.unwrap();
+        let failed = t.db.list_failed_publishes().await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].attempt_count, 2);
+        assert_eq!(failed[0].last_error, "HTTP 502");
+
+        t.db.clear_publish_failure(5).await.unwrap();
+        assert!(t.db.list_failed_publishes().await.unwrap().is_empty());
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn space_publishes_are_tracked_per_space() {
+        let t = TestDb::new().await;
+        t.db.record_space_publish(9, "main", None).await.unwrap();
+        t.db.record_space_publish(9, "main", None).await.unwrap();
+        t.db.record_space_publish(9, "archive", None).await.unwrap();
+        let mut spaces = t.db.published_spaces(9).await.unwrap();
+        spaces.sort();
+        assert_eq!(spaces, vec!["archive", "main"]);
+        assert!(t.db.published_spaces(10).await.unwrap().is_empty());
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn cids_are_kept_and_replaced_per_space() {
+        let t = TestDb::new().await;
+        t.seed(&[9]).await;
+        t.db.record_space_publish(9, "main", Some("cid-1")).await.unwrap();
+        t.db.record_space_publish(9, "archive", None).await.unwrap();
+        t.db.record_space_publish(9, "main", None).await.unwrap();
+        assert_eq!(t.db.published_cids(9).await.unwrap(), vec![("main".into(), "cid-1".into())]);
+
+        t.db.record_space_publish(9, "main", Some("cid-2")).await.unwrap();
+        assert_eq!(t.db.published_cids(9).await.unwrap(), vec![("main".into(), "cid-2".into())]);
+
+        t.db.update_content(9, "new title", "abc").await.unwrap();
+        let stored = t.db.get_referendum(9).await.unwrap().unwrap();
+        assert_eq!((stored.title.as_deref(), stored.content_hash.as_deref()), (Some("new title"), Some("abc")));
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn import_preserves_records_and_skips_existing() {
+        let t = TestDb::new().await;
+        t.seed(&[1]).await;
+        t.db.record_space_publish(1, "main", Some("cid-1")).await.unwrap();
+        let records = t.db.list_all_referenda().await.unwrap();
+        let publishes = t.db.list_space_publishes().await.unwrap();
+        assert_eq!(publishes.len(), 1);
+
+        let mut imported = records[0].clone();
+        imported.referendum_index = 2;
+        assert_eq!(t.db.import_referendum(&imported).await.unwrap(), 1);
+        assert_eq!(t.db.import_referendum(&imported).await.unwrap(), 0);
+        assert_eq!(t.db.import_referendum(&records[0]).await.unwrap(), 0);
+        assert_eq!(t.db.get_referendum(2).await.unwrap().unwrap().synced_at, records[0].synced_at);
+
+        assert_eq!(t.db.import_space_publish(&publishes[0]).await.unwrap(), 0);
+        let moved = SpacePublish { referendum_index: 2, ..publishes[0].clone() };
+        assert_eq!(t.db.import_space_publish(&moved).await.unwrap(), 1);
+        assert_eq!(t.db.published_cids(2).await.unwrap(), vec![("main".into(), "cid-1".into())]);
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn cursor_only_moves_forward() {
+        let t = TestDb::new().await;
+        assert_eq!(t.db.get_cursor().await.unwrap(), None);
+        t.db.set_cursor(120).await.unwrap();
+        assert_eq!(t.db.get_cursor().await.unwrap(), Some(120));
+        t.db.set_cursor(90).await.unwrap();
+        assert_eq!(t.db.get_cursor().await.unwrap(), Some(120));
+        t.db.set_cursor(121).await.unwrap();
+        assert_eq!(t.db.get_cursor().await.unwrap(), Some(121));
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn delete_referendum_removes_related_rows() {
+        let t = TestDb::new().await;
+        t.seed(&[3, 4]).await;
+        t.db.record_space_publish(3, "main", None).await.unwrap();
+        t.db.record_publish_failure(3, "HTTP 500").await.unwrap();
+
+        assert!(t.db.delete_referendum(3).await.unwrap());
+        assert!(!t.db.delete_referendum(3).await.unwrap());
+        assert_eq!(t.db.get_existing_indices().await.unwrap(), vec![4]);
+        assert!(t.db.published_spaces(3).await.unwrap().is_empty());
+        assert!(t.db.list_failed_publishes().await.unwrap().is_empty());
+        t.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn gaps_below_high_water_mark() {
+        let t = TestDb::new().await;
+        assert_eq!(t.db.max_synced_index().await.unwrap(), None);
+        assert!(t.db.find_gaps().await.unwrap().is_empty());
+
+        t.seed(&[10, 11, 14, 16]).await;
+        assert_eq!(t.db.min_synced_index().await.unwrap(), Some(10));
+        assert_eq!(t.db.max_synced_index().await.unwrap(), Some(16));
+        assert_eq!(t.db.find_gaps().await.unwrap(), vec![12, 13, 15]);
+        t.cleanup().await;
     }
 }
@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 被 OpenSquare 拒绝的已签名请求体的落盘目录
+///
+/// 每次拒绝写一个 `<index>-<space>-<时间>.json`，内容与当时发送的请求体完全一致，
+/// 可直接重新 POST 到 OpenSquare 复现；文件数超过上限时删除最旧的
+pub struct DeadLetters {
+    dir: PathBuf,
+    max_files: usize,
+}
+
+impl DeadLetters {
+    pub fn new(dir: impl Into<PathBuf>, max_files: usize) -> Self {
+        DeadLetters { dir: dir.into(), max_files: max_files.max(1) }
+    }
+
+    /// 写入一次被拒绝的请求体，返回文件路径
+    pub fn write(&self, index: u32, space: &str, at: DateTime<Utc>, body: &Value) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("无法创建 DEAD_LETTER_DIR {}", self.dir.display()))?;
+        let name = format!("{}-{}-{}.json", index, sanitize_file_part(space), at.format("%Y%m%dT%H%M%S%.3fZ"));
+        let path = self.dir.join(name);
+        fs::write(&path, serde_json::to_vec_pretty(body)?)
+            .with_context(|| format!("无法写入 {}", path.display()))?;
+        self.prune()?;
+        Ok(path)
+    }
+
+    /// 只保留最近的 `max_files` 个文件，按修改时间删除更早的
+    fn prune(&self) -> Result<()> {
+        let mut files: Vec<(std::time::SystemTime, PathBuf)> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_dead_letter(path))
+            .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+            .collect();
+        if files.len() <= self.max_files {
+            return Ok(());
+        }
+        // 修改时间相同（如秒级精度的文件系统）时按文件名排序，保证删除顺序确定
+        files.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        let excess = files.len() - self.max_files;
+        for (_, path) in files.into_iter().take(excess) {
+            fs::remove_file(&path).with_context(|| format!("无法删除 {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// 只清理本模块写出的 `.json` 文件，目录中的其他文件不受影响
+fn is_dead_letter(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+/// 空间名用于文件名，替换掉路径分隔符等不安全字符
+fn sanitize_file_part(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tdao-dead-letter-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn writes_request_body_named_by_index_and_time() {
+        let dir = temp_dir("write");
+        let letters = DeadLetters::new(&dir, 10);
+        let at = Utc.with_ymd_and_hms(2024, 5, 6, 7, 8, 9).unwrap();
+        let body = json!({ "address": "1abc", "signature": "0xdead", "data": { "space": "twodao" } });
+
+        let path = letters.write(42, "two/dao", at, &body).unwrap();
+        assert_eq!(path.file_name().unwrap(), "42-two_dao-20240506T070809.000Z.json");
+        let written: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written, body);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_only_newest_files() {
+        let dir = temp_dir("prune");
+        let letters = DeadLetters::new(&dir, 2);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("README.txt"), "keep me").unwrap();
+        for (i, minute) in [1, 2, 3].into_iter().enumerate() {
+            let at = Utc.with_ymd_and_hms(2024, 5, 6, 7, minute, 0).unwrap();
+            letters.write(i as u32, "twodao", at, &json!({})).unwrap();
+        }
+
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "1-twodao-20240506T070200.000Z.json",
+                "2-twodao-20240506T070300.000Z.json",
+                "README.txt",
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+/// 同步流程中的错误，按来源分类，便于调用方区分处理
+#[derive(Debug, Error)]
+pub enum SyncError {
+    /// 配置缺失或取值无效
+    #[error("配置错误：{0}")]
+    Config(String),
+    /// 网络层错误：连接失败、超时、响应读取失败等
+    #[error("HTTP 请求失败：{0}")]
+    Http(#[from] reqwest::Error),
+    /// SubSquare 返回了异常或无法解析的响应
+    #[error("SubSquare 错误：{0:#}")]
+    SubSquare(anyhow::Error),
+    /// 快照区块高度来源（Subscan 或节点 RPC）出错
+    #[error("获取快照高度失败：{0:#}")]
+    Subscan(anyhow::Error),
+    /// OpenSquare 查询接口返回了异常响应
+    #[error("OpenSquare 错误：{0:#}")]
+    OpenSquare(anyhow::Error),
+    /// OpenSquare 拒绝了提交的提案
+    #[error("OpenSquare 拒绝了提案（HTTP {status}）：{body}")]
+    OpenSquareRejected { status: StatusCode, body: String },
+    /// 数据库不可用或读写失败
+    #[error("数据库错误：{0:#}")]
+    Db(anyhow::Error),
+    /// 密钥派生、载荷规范化或签名失败
+    #[error("签名失败：{0:#}")]
+    Signing(anyhow::Error),
+}
+
+pub type SyncResult<T> = Result<T, SyncError>;
+
+impl SyncError {
+    /// 归类上游调用返回的 anyhow 错误：底层是 reqwest 错误时归为 Http，否则交给 `wrap`
+    pub fn upstream(e: anyhow::Error, wrap: fn(anyhow::Error) -> SyncError) -> SyncError {
+        match e.downcast::<reqwest::Error>() {
+            Ok(e) => SyncError::Http(e),
+            Err(e) => wrap(e),
+        }
+    }
+
+    /// OpenSquare 以 401/403 拒绝，通常意味着签名密钥有问题，而不是单条提案的数据有误
+    pub fn is_auth_rejection(&self) -> bool {
+        matches!(
+            self,
+            SyncError::OpenSquareRejected { status, .. }
+                if *status == StatusCode::UNAUTHORIZED || *status == StatusCode::FORBIDDEN
+        )
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{Db, ReferendumRecord, SpacePublish};
+
+/// 导出文件的格式版本，字段含义变化时递增，导入时拒绝更新版本的文件
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// `--export` 写出、`--import` 读入的同步状态，用于迁移到新数据库时避免重新发布
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportFile {
+    pub schema_version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// 拉取游标，尚未记录时为空
+    #[serde(default)]
+    pub cursor: Option<u32>,
+    /// referenda 表的全部记录
+    pub referenda: Vec<ReferendumRecord>,
+    /// 各空间的发布记录及提案 CID
+    #[serde(default)]
+    pub space_publishes: Vec<SpacePublish>,
+}
+
+/// 导入结果：各类记录实际插入的条数，已存在而跳过的不计
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub referenda: u64,
+    pub skipped_referenda: u64,
+    pub space_publishes: u64,
+}
+
+/// 读取当前数据库的同步状态
+pub async fn export(db: &Db) -> Result<ExportFile> {
+    Ok(ExportFile {
+        schema_version: SCHEMA_VERSION,
+        exported_at: Utc::now(),
+        cursor: db.get_cursor().await?,
+        referenda: db.list_all_referenda().await?,
+        space_publishes: db.list_space_publishes().await?,
+    })
+}
+
+/// 解析导出文件，版本高于当前程序支持的版本时报错
+pub fn parse(raw: &[u8]) -> Result<ExportFile> {
+    #[derive(Deserialize)]
+    struct Version {
+        schema_version: u32,
+    }
+    let Version { schema_version } = serde_json::from_slice(raw)
+        .map_err(|e| anyhow::anyhow!("导出文件缺少 schema_version 或不是 JSON 对象：{}", e))?;
+    if schema_version > SCHEMA_VERSION {
+        anyhow::bail!(
+            "导出文件的 schema_version 为 {}，本程序只支持到 {}，请先升级",
+            schema_version,
+            SCHEMA_VERSION
+        );
+    }
+    Ok(serde_json::from_slice(raw)?)
+}
+
+/// 把导出的状态写入数据库，已存在的记录保持不变，可重复执行
+pub async fn import(db: &Db, file: &ExportFile) -> Result<ImportSummary> {
+    db.init_schema().await?;
+    let mut summary = ImportSummary::default();
+    for record in &file.referenda {
+        match db.import_referendum(record).await? {
+            0 => summary.skipped_referenda += 1,
+            n => summary.referenda += n,
+        }
+    }
+    for publish in &file.space_publishes {
+        summary.space_publishes += db.import_space_publish(publish).await?;
+    }
+    // 游标只会前进，目标库已有更大的游标时保持不变
+    if let Some(cursor) = file.cursor {
+        db.set_cursor(cursor).await?;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn sample() -> ExportFile {
+        let at = Utc.with_ymd_and_hms(2024, 5, 6, 7, 8, 9).unwrap();
+        ExportFile {
+            schema_version: SCHEMA_VERSION,
+            exported_at: at,
+            cursor: Some(42),
+            referenda: vec![ReferendumRecord {
+                referendum_index: 42,
+                track_id: Some(34),
+                title: Some("[BS] #42 - Fund tooling".into()),
+                content_summary: None,
+                snapshot_height: Some(1_000),
+                nonce: None,
+                synced_at: Some(at),
+                content_hash: Some("abc".into()),
+                state: Some("Deciding".into()),
+            }],
+            space_publishes: vec![SpacePublish {
+                referendum_index: 42,
+                space: "twodao".into(),
+                cid: Some("cid-42".into()),
+                published_at: at,
+            }],
+        }
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let file = sample();
+        let raw = serde_json::to_vec_pretty(&file).unwrap();
+        assert_eq!(parse(&raw).unwrap(), file);
+    }
+
+    #[test]
+    fn rejects_newer_or_missing_schema_version() {
+        let mut newer = serde_json::to_value(sample()).unwrap();
+        newer["schema_version"] = json!(SCHEMA_VERSION + 1);
+        let err = parse(newer.to_string().as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("schema_version"));
+
+        assert!(parse(br#"[{"referendum_index": 1}]"#).is_err());
+        assert!(parse(br#"{"referenda": []}"#).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+
+/// 定时同步的随机延迟，错开多个实例同时访问上游的时间点
+pub struct Jitter {
+    rng: StdRng,
+}
+
+impl Jitter {
+    /// 指定种子时延迟序列可复现，否则使用系统熵
+    pub fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Jitter { rng }
+    }
+
+    /// 在 `[0, max]` 内均匀取一个延迟（毫秒精度），`max` 为 0 时不延迟
+    pub fn delay(&mut self, max: Duration) -> Duration {
+        if max.is_zero() {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(self.rng.gen_range(0..=max.as_millis() as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_jitter_is_deterministic() {
+        let max = Duration::from_secs(300);
+        let mut a = Jitter::new(Some(42));
+        let mut b = Jitter::new(Some(42));
+        for _ in 0..10 {
+            assert_eq!(a.delay(max), b.delay(max));
+        }
+    }
+
+    #[test]
+    fn delay_stays_within_bound() {
+        let max = Duration::from_secs(5);
+        let mut jitter = Jitter::new(Some(7));
+        for _ in 0..100 {
+            assert!(jitter.delay(max) <= max);
+        }
+        assert_eq!(jitter.delay(Duration::ZERO), Duration::ZERO);
+    }
+}
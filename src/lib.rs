@@ -0,0 +1,46 @@
+//! SubSquare 公投同步到 OpenSquare 的核心逻辑
+//!
+//! 二进制只负责命令行和定时调度；需要自定义调度或嵌入其他服务时，
+//! 直接依赖本库并调用 [`run_sync`]：
+//!
+//! ```no_run
+//! use tdao_referenda_sync::{run_sync, Config, Db, HeightCache, RateLimiter};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let cfg = Config::from_env()?;
+//! let http = cfg.http_client()?;
+//! let db = Db::connect(&cfg.postgres_url, cfg.db_pool_max_size, cfg.postgres_tls, cfg.postgres_ca_cert.as_deref()).await?;
+//! let limiter = RateLimiter::new(cfg.rate_limit_rps);
+//! let heights = HeightCache::new(cfg.snapshot_cache_ttl);
+//! let report = run_sync(&http, &limiter, &heights, &db, &cfg).await?;
+//! println!("发布 {} 条", report.published);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod block_source;
+pub mod config;
+pub mod dead_letter;
+pub mod db;
+pub mod error;
+pub mod export;
+pub mod jitter;
+pub mod models;
+pub mod network;
+pub mod notify;
+pub mod ratelimit;
+pub mod run_id;
+pub mod sanitize;
+pub mod service;
+pub mod signing;
+pub mod transform;
+
+pub use block_source::HeightCache;
+pub use config::Config;
+pub use db::Db;
+pub use error::{SyncError, SyncResult};
+pub use ratelimit::RateLimiter;
+pub use service::{
+    fetch_referenda, fetch_referenda_incremental, fetch_referenda_since, fetch_single_referendum, retry_failed, run_sync,
+    SyncReport,
+};
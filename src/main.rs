@@ -1,44 +1,169 @@
 
 
-mod config;
-mod db;
-mod models;
-mod service;
+mod cli;
 
-use tokio::time::{interval, MissedTickBehavior};
+use tokio::time::{interval, sleep, MissedTickBehavior};
 use anyhow::Result;
-use dotenv::dotenv;
 use env_logger::Env;
-use log::{info, error};
-use reqwest::Client;
+use log::{info, warn, error};
+use std::io::Write;
 use std::time::Duration;
-use config::Config;
-use db::Db;
-use service::run_sync;
+use cli::Command;
+use tdao_referenda_sync::config::{self, BlockSourceKind};
+use tdao_referenda_sync::jitter::Jitter;
+use tdao_referenda_sync::run_id;
+use tdao_referenda_sync::service;
+use tdao_referenda_sync::{run_sync, Config, Db, HeightCache, RateLimiter, SyncError};
 use chrono::{Local, Duration as ChronoDuration};
+use futures::FutureExt;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+
+/// 取出 panic 携带的信息，非字符串负载时给出占位说明
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("（非字符串的 panic 负载）")
+}
 
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // 先加载 .env，再加载环境变量
-    dotenv().ok();
-
-    // 初始化日志：从环境变量 RUST_LOG 读取过滤级别，默认为 info
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    // 加载 .env（及 PROFILE 对应的 .env.<profile>），已设置的环境变量优先
+    let profile = config::load_dotenv()?;
+
+    // 初始化日志：从环境变量 RUST_LOG 读取过滤级别，默认为 info；同步轮次内的日志带上 run id 前缀
+    env_logger::Builder::from_env(Env::default().default_filter_or("info"))
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {:<5} {}] {}{}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                run_id::log_prefix(),
+                record.args()
+            )
+        })
+        .init();
+
+    if let Some(profile) = &profile {
+        info!("🗂 配置 PROFILE：{}（.env.{} 覆盖 .env）", profile, profile);
+    }
+
+    // 解析命令行模式
+    let command = Command::parse(std::env::args().skip(1))?;
+
+    // 配置检查自行加载配置，以便把加载失败也作为一项检查结果输出
+    if command == Command::ConfigCheck {
+        let ok = cli::config_check().await;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
 
     // 加载程序配置
     let cfg = Config::from_env()?;
-    info!("🔧 使用的 OpenSquare 空间：{}", cfg.open_square_space);
-
-    // 构建 HTTP 客户端
-    let http = Client::builder()
-        .timeout(cfg.http_timeout)
-        .build()?;
+    info!("🔧 使用的 OpenSquare 空间：{}", cfg.open_square_spaces.join(", "));
+    match (cfg.block_source, &cfg.subscan_api_key) {
+        (BlockSourceKind::Subscan, Some(_)) => info!("⛏ 区块高度来源：Subscan（使用 API Key）"),
+        (BlockSourceKind::Subscan, None) => warn!(
+            "⛏ 区块高度来源：Subscan（未设置 SUBSCAN_API_KEY，匿名访问，限流更严格；\
+             生产环境请配置 API Key 或改用 BLOCK_SOURCE=rpc）"
+        ),
+        (BlockSourceKind::Rpc, _) => info!("⛏ 区块高度来源：节点 RPC（RPC_URL）"),
+    }
+
+    // 签名自检不需要网络和数据库
+    if command == Command::SelfTest {
+        return cli::selftest(&cfg);
+    }
+
+    let http = cfg.http_client()?;
+
+    // 重放保存的请求体不需要数据库
+    if let Command::Replay { path, resign } = &command {
+        let limiter = RateLimiter::new(cfg.rate_limit_rps);
+        return cli::replay(&http, &limiter, &cfg, path, *resign).await;
+    }
 
     // 连接数据库
-    let db = Db::connect(&cfg.postgres_url).await?;
-
-  
+    // 容器编排中数据库可能比应用晚就绪，启动时按 DB_CONNECT_RETRIES 重试
+    let db = Db::connect_with_retry(
+        &cfg.postgres_url,
+        cfg.db_pool_max_size,
+        cfg.postgres_tls,
+        cfg.postgres_ca_cert.as_deref(),
+        cfg.db_connect_retries,
+        cfg.db_connect_backoff,
+    )
+    .await?;
+    // 已发布但写库失败的公投落盘保存，重启后补写而不是重复发布
+    let db = match &cfg.pending_inserts_file {
+        Some(path) => db.with_pending_file(path)?,
+        None => db,
+    };
+
+    // 运维命令：执行后直接退出，不进入定时循环
+    if let Command::List { json } = command {
+        db.init_schema().await?;
+        return cli::list(&db, json).await;
+    }
+    if let Command::DbStatus { json } = command {
+        db.init_schema().await?;
+        return cli::db_status(&db, json).await;
+    }
+    if let Command::Export { path } = &command {
+        db.init_schema().await?;
+        return cli::export(&db, path).await;
+    }
+    if let Command::Import { path } = &command {
+        return cli::import(&db, path).await;
+    }
+    if let Command::Unsync { index, confirmed } = command {
+        db.init_schema().await?;
+        return cli::unsync(&db, index, confirmed).await;
+    }
+
+    db.init_schema().await?;
+    info!("📚 本地已同步 {} 条公投（完整列表见 --list）", db.count_synced().await?);
+
+    // 出站请求限流器，跨轮次共享
+    let limiter = RateLimiter::new(cfg.rate_limit_rps);
+
+    // 各网络区块高度缓存，跨轮次共享
+    let heights = HeightCache::new(cfg.snapshot_cache_ttl);
+
+    if cfg.verify_space {
+        for space in &cfg.open_square_spaces {
+            service::verify_space(&http, &limiter, &cfg, space).await?;
+            info!("✅ 已确认 OpenSquare 空间 {} 存在", space);
+        }
+    }
+
+    service::check_clock_skew(&http, &limiter, &cfg).await?;
+
+    if command == Command::RetryFailed {
+        let report = service::retry_failed(&http, &limiter, &heights, &db, &cfg).await?;
+        info!(
+            "✅ 失败重试完成（run {}）：重试 {}，成功 {}，仍失败 {}，跳过 {}",
+            report.run_id,
+            report.published + report.failed,
+            report.published,
+            report.failed,
+            report.skipped_existing + report.skipped_filtered
+        );
+        info!("⏱ 各阶段耗时：{}", report.timings);
+        return Ok(());
+    }
+
+    // 启动前随机等待一段时间，避免多个实例在同一时刻访问上游
+    let mut jitter = Jitter::new(cfg.jitter_seed);
+    let startup_delay = jitter.delay(cfg.startup_jitter);
+    if !startup_delay.is_zero() {
+        info!("🎲 启动抖动：{:?} 后开始首次同步", startup_delay);
+        sleep(startup_delay).await;
+    }
 
     // 创建一个 Interval
     let mut ticker = interval(Duration::from_secs(60 * 30));
@@ -50,16 +175,42 @@ async fn main() -> Result<()> {
     loop {
         // 2. 等待下一个 tick
         ticker.tick().await;
+        sleep(jitter.delay(cfg.tick_jitter)).await;
 
         // 3. 执行前日志
         let now = Local::now();
         info!("🔄 [{}] 开始定时同步...", now.format("%Y-%m-%d %H:%M:%S"));
 
-        // 4. 真正的同步逻辑
-        if let Err(err) = run_sync(&http, &db, &cfg).await {
-            error!("❌ 定时同步失败: {:?}", err);
-        } else {
-            info!("✅ 定时同步完成");
+        // 4. 真正的同步逻辑；单轮 panic 只记录日志，不终止定时循环
+        let outcome = AssertUnwindSafe(run_sync(&http, &limiter, &heights, &db, &cfg))
+            .catch_unwind()
+            .await;
+        let result = match outcome {
+            Ok(result) => result,
+            Err(panic) => {
+                error!("💥 本轮同步发生 panic，已跳过，下一轮照常执行：{}", panic_message(panic.as_ref()));
+                continue;
+            }
+        };
+        match result {
+            Ok(report) => info!(
+                "✅ 定时同步完成（run {}）：拉取 {}，已存在 {}，过滤 {}，无标题 {}，推迟 {}，发布 {}，失败 {}",
+                report.run_id,
+                report.fetched,
+                report.skipped_existing,
+                report.skipped_filtered,
+                report.untitled,
+                report.deferred,
+                report.published,
+                report.failed
+            ),
+            // 上游暂时不可用时下一轮自然重试，配置和签名问题需要人工介入
+            Err(err @ (SyncError::Http(_)
+            | SyncError::SubSquare(_)
+            | SyncError::Subscan(_)
+            | SyncError::OpenSquare(_))) => warn!("⚠️ 上游服务暂不可用，下一轮重试：{}", err),
+            Err(err @ SyncError::Db(_)) => error!("❌ 数据库不可用，本轮同步中止：{}", err),
+            Err(err) => error!("❌ 定时同步失败，需要检查配置或签名密钥：{}", err),
         }
 
         // 5. 计算并打印下一次执行时间
@@ -1,12 +1,14 @@
 // src/models.rs
 
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::str::FromStr;
 
 
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
 pub enum ReferendumStatus {
     Confirming,
     Deciding,
@@ -21,6 +23,51 @@ pub enum ReferendumStatus {
     Executed,
 }
 
+impl ReferendumStatus {
+    const ALL: [ReferendumStatus; 11] = [
+        ReferendumStatus::Confirming,
+        ReferendumStatus::Deciding,
+        ReferendumStatus::Queueing,
+        ReferendumStatus::Preparing,
+        ReferendumStatus::Submitted,
+        ReferendumStatus::Approved,
+        ReferendumStatus::Cancelled,
+        ReferendumStatus::Killed,
+        ReferendumStatus::TimedOut,
+        ReferendumStatus::Rejected,
+        ReferendumStatus::Executed,
+    ];
+
+    /// 与 SubSquare 一致的状态名，写入数据库的 state 列
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReferendumStatus::Confirming => "Confirming",
+            ReferendumStatus::Deciding => "Deciding",
+            ReferendumStatus::Queueing => "Queueing",
+            ReferendumStatus::Preparing => "Preparing",
+            ReferendumStatus::Submitted => "Submitted",
+            ReferendumStatus::Approved => "Approved",
+            ReferendumStatus::Cancelled => "Cancelled",
+            ReferendumStatus::Killed => "Killed",
+            ReferendumStatus::TimedOut => "TimedOut",
+            ReferendumStatus::Rejected => "Rejected",
+            ReferendumStatus::Executed => "Executed",
+        }
+    }
+}
+
+/// 按状态名解析，不区分大小写，如 `deciding`、`TimedOut`
+impl FromStr for ReferendumStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReferendumStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow::anyhow!("未知的公投状态：{}", s.trim()))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SubSquareReferendumState {
@@ -36,11 +83,139 @@ pub struct SubSquareReferendum {
     pub referendum_index: u32,
     pub title: Option<String>,
     pub content: Option<String>,
-    #[serde(rename = "track")]
+    #[serde(rename = "track", deserialize_with = "deserialize_track_id")]
     pub track_id: u16,
-    #[serde(rename = "contentSummary")]
+    #[serde(rename = "contentSummary", default)]
     pub content_summary: Option<ContentSummary>,
     pub state: SubSquareReferendumState,
+    /// 部分接口在顶层直接给出提交者地址
+    #[serde(default)]
+    pub proposer: Option<Value>,
+    #[serde(rename = "onchainData", default)]
+    pub onchain_data: Option<OnchainData>,
+    /// 提交公投的链上事件位置
+    #[serde(default)]
+    pub indexer: Option<Indexer>,
+}
+
+/// SubSquare 的 indexer 字段，只取提交时的区块高度和出块时间
+#[derive(Debug, Deserialize)]
+pub struct Indexer {
+    #[serde(rename = "blockHeight")]
+    pub block_height: u64,
+    /// 出块时间，Unix 毫秒时间戳
+    #[serde(rename = "blockTime", default)]
+    pub block_time: Option<i64>,
+}
+
+/// SubSquare 的 track 字段：旧格式为整数编号，新格式为带 `id` 的对象
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TrackRepr {
+    Id(u16),
+    Object { id: u16 },
+}
+
+/// 兼容两种 track 格式，只取编号
+fn deserialize_track_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    match TrackRepr::deserialize(deserializer)? {
+        TrackRepr::Id(id) | TrackRepr::Object { id } => Ok(id),
+    }
+}
+
+/// SubSquare 公投的链上数据，只取需要的字段
+#[derive(Debug, Default, Deserialize)]
+pub struct OnchainData {
+    /// 不同 track 下格式不一：可能是地址字符串，也可能是带 address/id 的对象
+    #[serde(default)]
+    pub proposer: Option<Value>,
+    /// 提交公投的链上事件位置
+    #[serde(default)]
+    pub indexer: Option<Indexer>,
+    /// 国库支出公投申请的原生代币金额和受益人，其他公投没有该字段
+    #[serde(rename = "treasuryInfo", default)]
+    pub treasury_info: Option<TreasuryInfo>,
+    /// 链上 ReferendumInfo 的 Ongoing 部分，只取决策押金
+    #[serde(default)]
+    pub info: Option<OngoingInfo>,
+}
+
+/// SubSquare onchainData.info：尚未缴纳决策押金时 decisionDeposit 为 null 或缺失
+#[derive(Debug, Default, Deserialize)]
+pub struct OngoingInfo {
+    #[serde(rename = "decisionDeposit", default)]
+    pub decision_deposit: Option<Value>,
+}
+
+/// SubSquare onchainData.treasuryInfo：金额以 planck 为单位，可能是字符串或数字
+#[derive(Debug, Default, Deserialize)]
+pub struct TreasuryInfo {
+    #[serde(default)]
+    pub amount: Option<Value>,
+    #[serde(default)]
+    pub beneficiary: Option<Value>,
+}
+
+/// 国库支出公投申请的金额（planck）和受益人地址
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spend {
+    pub amount: u128,
+    pub beneficiary: Option<String>,
+}
+
+/// 从字符串或 `{ "address": .. }` / `{ "id": .. }` 形式中提取地址
+fn address_from_value(v: &Value) -> Option<String> {
+    let addr = match v {
+        Value::String(s) => s.as_str(),
+        Value::Object(map) => map
+            .get("address")
+            .or_else(|| map.get("id"))
+            .and_then(Value::as_str)?,
+        _ => return None,
+    };
+    let addr = addr.trim();
+    (!addr.is_empty()).then(|| addr.to_string())
+}
+
+impl SubSquareReferendum {
+    /// 链上提交者地址，优先取 onchainData.proposer，缺失时回退到顶层 proposer
+    pub fn proposer_address(&self) -> Option<String> {
+        self.onchain_data
+            .as_ref()
+            .and_then(|d| d.proposer.as_ref())
+            .and_then(address_from_value)
+            .or_else(|| self.proposer.as_ref().and_then(address_from_value))
+    }
+
+    /// 链上提交时间，优先取 onchainData.indexer，缺失时回退到顶层 indexer
+    pub fn submitted_at(&self) -> Option<DateTime<Utc>> {
+        self.onchain_data
+            .as_ref()
+            .and_then(|d| d.indexer.as_ref())
+            .and_then(|i| i.block_time)
+            .or_else(|| self.indexer.as_ref().and_then(|i| i.block_time))
+            .and_then(DateTime::from_timestamp_millis)
+    }
+
+    /// 是否已缴纳决策押金；onchainData.info.decisionDeposit 缺失或为 null 都视为未缴纳
+    pub fn has_decision_deposit(&self) -> bool {
+        self.onchain_data
+            .as_ref()
+            .and_then(|d| d.info.as_ref())
+            .and_then(|i| i.decision_deposit.as_ref())
+            .is_some_and(|v| !v.is_null())
+    }
+
+    /// 国库支出的金额和受益人；没有 treasuryInfo 或金额无法解析时返回 None
+    pub fn spend(&self) -> Option<Spend> {
+        let info = self.onchain_data.as_ref()?.treasury_info.as_ref()?;
+        let amount = match info.amount.as_ref()? {
+            Value::String(s) => s.trim().parse().ok()?,
+            Value::Number(n) => n.as_u64()? as u128,
+            _ => return None,
+        };
+        Some(Spend { amount, beneficiary: info.beneficiary.as_ref().and_then(address_from_value) })
+    }
 }
 
 
@@ -48,7 +223,11 @@ pub struct SubSquareReferendum {
 
 #[derive(Debug, Deserialize)]
 pub struct ContentSummary {
+    /// AI 生成的摘要
     pub summary: Option<String>,
+    /// 作者提供的摘要（部分公投才有）
+    #[serde(rename = "authorSummary", default)]
+    pub author_summary: Option<String>,
 }
 
 /// networksConfig 里的单个资产配置
@@ -56,9 +235,12 @@ pub struct ContentSummary {
 pub struct AssetConfig {
     pub symbol: String,
     pub decimals: u8,
-    // #[serde(rename = "votingThreshold")]
-    // pub voting_threshold: String,
-    // pub multiplier: u32,
+    /// 投票门槛，单位为最小精度（planck），以十进制字符串表示；未配置时不序列化
+    #[serde(rename = "votingThreshold", default, skip_serializing_if = "Option::is_none")]
+    pub voting_threshold: Option<String>,
+    /// 投票权重倍数；未配置时不序列化
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiplier: Option<u32>,
 }
 
 /// networksConfig 里的单个网络详情
@@ -67,7 +249,7 @@ pub struct AssetConfig {
 pub struct NetworkDetail {
     pub network: String,
     #[serde(rename = "ss58Format")]
-    pub ss58_format: u8,
+    pub ss58_format: u16,
     pub assets: Vec<AssetConfig>,
 }
 
@@ -81,8 +263,9 @@ pub struct NetworksConfig {
     pub strategies: Vec<String>,
     pub version: String,
     pub accessibility: String,
-    pub whitelist: Vec<String>,
-   
+    /// 仅 whitelist 模式下下发，public 模式不序列化该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub whitelist: Option<Vec<String>>,
 }
 
 /// data 字段里的完整提案结构
@@ -116,7 +299,8 @@ pub struct ProposalData {
     #[serde(rename = "snapshotHeights")]
     pub snapshot_heights: HashMap<String, u64>,
 
-    #[serde(rename = "realProposer")]
+    /// 链上真实提交者，取不到时不序列化该字段（OpenSquare 以缺省而非 null 表示"无"）
+    #[serde(rename = "realProposer", default, skip_serializing_if = "Option::is_none")]
     pub real_proposer: Option<Value>,
 
     #[serde(rename = "proposerNetwork")]
@@ -131,7 +315,13 @@ pub struct ProposalData {
     #[serde(rename = "networksConfig")]
     pub networks_config: NetworksConfig,
 
+    /// 讨论链接，未设置时不序列化该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub discussion: Option<String>,
+
+    /// 每个提案唯一的 nonce，参与签名，仅在 INCLUDE_NONCE 开启时序列化
+    #[serde(rename = "nonce", default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
 }
 
 /// 最终发送的请求体
@@ -142,6 +332,27 @@ pub struct OpenSquareNewProposalRequest {
     pub signature: String,
 }
 
+/// 更新已发布提案时签名的载荷，`proposalCid` 指向原提案
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposalUpdateData {
+    pub space: String,
+    pub proposal_cid: String,
+    pub title: String,
+    pub content: String,
+    pub content_type: String,
+    pub version: String,
+    pub timestamp: u64,
+}
+
+/// 更新提案的请求体
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenSquareUpdateProposalRequest {
+    pub data: ProposalUpdateData,
+    pub address: String,
+    pub signature: String,
+}
+
 /// Track 枚举及格式化，保持不变
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Track {
@@ -185,6 +396,35 @@ impl Track {
         }
     }
 
+    /// 可读的 track 全名，如 `Big Spender`
+    pub fn full_name(&self) -> &str {
+        match self {
+            Track::Root => "Root",
+            Track::WhitelistedCaller => "Whitelisted Caller",
+            Track::WishForChange => "Wish For Change",
+            Track::StakingAdmin => "Staking Admin",
+            Track::Treasurer => "Treasurer",
+            Track::LeaseAdmin => "Lease Admin",
+            Track::FellowshipAdmin => "Fellowship Admin",
+            Track::GeneralAdmin => "General Admin",
+            Track::AuctionAdmin => "Auction Admin",
+            Track::ReferendumCanceller => "Referendum Canceller",
+            Track::ReferendumKiller => "Referendum Killer",
+            Track::SmallTipper => "Small Tipper",
+            Track::BigTipper => "Big Tipper",
+            Track::SmallSpender => "Small Spender",
+            Track::MediumSpender => "Medium Spender",
+            Track::BigSpender => "Big Spender",
+        }
+    }
+
+    /// 按编号取 track 全名，未知 track 回退为 `Track {track_id}`
+    pub fn full_name_for(track_id: u16) -> String {
+        Track::from_id(track_id)
+            .map(|t| t.full_name().to_string())
+            .unwrap_or_else(|| format!("Track {}", track_id))
+    }
+
     pub fn from_id(id: u16) -> Option<Track> {
         match id {
             0 => Some(Track::Root),
@@ -208,10 +448,309 @@ impl Track {
     }
 
 
+    /// 格式化提案标题，未知 track 回退为 `T{track_id}`，保留真实编号
+    ///
+    /// 只做字符串拼接，任意 track / 编号 / 标题（包括空串、控制字符、超长文本）都不会 panic，
+    /// 结果总是包含 `#{referendum_index}`；已知 track 的前缀只取决于 `short_name`
     pub fn format_title(track_id: u16, referendum_index: u32, title_text: &str) -> String {
         let short = Track::from_id(track_id)
             .map(|t| t.short_name().to_string())
-            .unwrap_or_else(|| "OT".into());
+            .unwrap_or_else(|| format!("T{}", track_id));
         format!("[{}] #{} - {}", short, referendum_index, title_text)
     }
+
+    /// 从 `format_title` 生成的标题中解析出公投编号，格式不符时返回 None
+    ///
+    /// 标题必须以 `[track] #index - ` 开头，track 须是已知简称或未知 track 的 `T{track_id}`；
+    /// TITLE_PREFIX 等前后缀由调用方先去掉
+    pub fn parse_index(title: &str) -> Option<u32> {
+        let (short, rest) = title.strip_prefix('[')?.split_once("] #")?;
+        let known = (0..=u16::from(u8::MAX))
+            .filter_map(Track::from_id)
+            .any(|t| t.short_name() == short);
+        let unknown = short
+            .strip_prefix('T')
+            .and_then(|id| id.parse::<u16>().ok())
+            .is_some_and(|id| Track::from_id(id).is_none() && short == format!("T{}", id));
+        if !known && !unknown {
+            return None;
+        }
+        let (digits, _) = rest.split_once(" - ")?;
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn networks_config(accessibility: &str, whitelist: Option<Vec<String>>) -> NetworksConfig {
+        NetworksConfig {
+            symbol: "DOT".into(),
+            decimals: 10,
+            networks: vec![],
+            strategies: vec!["one-person-one-vote".into()],
+            version: "4".into(),
+            accessibility: accessibility.into(),
+            whitelist,
+        }
+    }
+
+    fn referendum(track: Value, content_summary: Option<Value>) -> SubSquareReferendum {
+        let mut v = json!({
+            "referendumIndex": 7,
+            "title": "t",
+            "track": track,
+            "state": { "name": "Deciding" },
+        });
+        if let Some(cs) = content_summary {
+            v["contentSummary"] = cs;
+        }
+        serde_json::from_value(v).unwrap()
+    }
+
+    #[test]
+    fn full_name_for_every_track() {
+        let expected = [
+            (0, "Root"),
+            (1, "Whitelisted Caller"),
+            (2, "Wish For Change"),
+            (10, "Staking Admin"),
+            (11, "Treasurer"),
+            (12, "Lease Admin"),
+            (13, "Fellowship Admin"),
+            (14, "General Admin"),
+            (15, "Auction Admin"),
+            (20, "Referendum Canceller"),
+            (21, "Referendum Killer"),
+            (30, "Small Tipper"),
+            (31, "Big Tipper"),
+            (32, "Small Spender"),
+            (33, "Medium Spender"),
+            (34, "Big Spender"),
+        ];
+        for (id, name) in expected {
+            assert_eq!(Track::from_id(id).unwrap().full_name(), name);
+            assert_eq!(Track::full_name_for(id), name);
+        }
+        assert_eq!(Track::full_name_for(99), "Track 99");
+        assert_eq!(Track::full_name_for(3), "Track 3");
+    }
+
+    /// 随机标题：混合 ASCII、中文、emoji、控制字符和方括号，长度 0 到 2000
+    fn random_title(rng: &mut impl rand::Rng) -> String {
+        const POOL: &[char] = &['a', 'Z', '0', ' ', '#', '[', ']', '-', '\0', '\n', '\t', '\u{7f}', '\u{200b}', '公', '投', '🚀', 'é'];
+        let len = match rng.gen_range(0..4) {
+            0 => 0,
+            1 => rng.gen_range(1..8),
+            2 => rng.gen_range(8..200),
+            _ => rng.gen_range(200..2000),
+        };
+        (0..len).map(|_| POOL[rng.gen_range(0..POOL.len())]).collect()
+    }
+
+    #[test]
+    fn format_title_holds_for_arbitrary_input() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x7da0);
+        for _ in 0..2000 {
+            let track_id: u16 = rng.gen();
+            let index: u32 = rng.gen();
+            let text = random_title(&mut rng);
+            let title = Track::format_title(track_id, index, &text);
+
+            let prefix = match Track::from_id(track_id) {
+                Some(track) => format!("[{}] #{} - ", track.short_name(), index),
+                None => format!("[T{}] #{} - ", track_id, index),
+            };
+            assert!(title.starts_with(&prefix), "{:?}", title);
+            assert!(title.ends_with(&text));
+            assert_eq!(Track::parse_index(&title), Some(index), "{:?}", title);
+        }
+    }
+
+    #[test]
+    fn format_title_is_stable_for_known_tracks() {
+        for id in 0..=u16::from(u8::MAX) {
+            let Some(track) = Track::from_id(id) else { continue };
+            let title = Track::format_title(id, 7, "t");
+            assert_eq!(title, format!("[{}] #7 - t", track.short_name()));
+            assert_eq!(Track::format_title(id, 7, "t"), title);
+        }
+    }
+
+    #[test]
+    fn unknown_track_keeps_its_id_in_title() {
+        assert!(Track::from_id(37).is_none());
+        assert_eq!(Track::format_title(37, 1234, "New track"), "[T37] #1234 - New track");
+        assert_eq!(Track::format_title(34, 1234, "Known"), "[BS] #1234 - Known");
+        assert_eq!(Track::parse_index(&Track::format_title(37, 1234, "x")), Some(1234));
+        // 只认 format_title 的格式：前面有别的文字、未知简称、已知 track 写成 T{id} 都不算
+        for title in ["TDAO [BS] #1234 - x", "[XX] #1234 - x", "[T34] #1234 - x", "[T037] #1234 - x", "[BS] #1234x - x", "[BS] #1234"] {
+            assert_eq!(Track::parse_index(title), None, "{:?}", title);
+        }
+    }
+
+    #[test]
+    fn track_accepts_integer_id() {
+        assert_eq!(referendum(json!(33), None).track_id, 33);
+    }
+
+    #[test]
+    fn track_accepts_object_with_id() {
+        let r = referendum(json!({ "id": 34, "name": "big_spender" }), None);
+        assert_eq!(r.track_id, 34);
+    }
+
+    #[test]
+    fn content_summary_may_be_absent_null_or_object() {
+        assert!(referendum(json!(0), None).content_summary.is_none());
+        assert!(referendum(json!(0), Some(Value::Null)).content_summary.is_none());
+        let r = referendum(json!(0), Some(json!({ "summary": "s" })));
+        assert_eq!(r.content_summary.unwrap().summary.as_deref(), Some("s"));
+    }
+
+    #[test]
+    fn submitted_at_prefers_onchain_indexer() {
+        let mut v = json!({
+            "referendumIndex": 7,
+            "track": 0,
+            "state": { "name": "Deciding" },
+            "indexer": { "blockHeight": 100, "blockTime": 1_700_000_000_000i64 },
+        });
+        let r: SubSquareReferendum = serde_json::from_value(v.clone()).unwrap();
+        assert_eq!(r.submitted_at().unwrap().timestamp(), 1_700_000_000);
+
+        v["onchainData"] = json!({ "indexer": { "blockHeight": 99, "blockTime": 1_600_000_000_000i64 } });
+        let r: SubSquareReferendum = serde_json::from_value(v).unwrap();
+        assert_eq!(r.submitted_at().unwrap().timestamp(), 1_600_000_000);
+
+        assert!(referendum(json!(0), None).submitted_at().is_none());
+    }
+
+    #[test]
+    fn spend_reads_treasury_info() {
+        let with_onchain = |onchain: Value| -> SubSquareReferendum {
+            serde_json::from_value(json!({
+                "referendumIndex": 7,
+                "track": 33,
+                "state": { "name": "Deciding" },
+                "onchainData": onchain,
+            }))
+            .unwrap()
+        };
+        let r = with_onchain(json!({
+            "treasuryInfo": { "amount": "100000000000000", "beneficiary": { "id": "16ap6fdqS2rqFsyYah35hX1FH6rPNWtLqqXZDQC9x6GW141C" } }
+        }));
+        assert_eq!(
+            r.spend(),
+            Some(Spend {
+                amount: 100_000_000_000_000,
+                beneficiary: Some("16ap6fdqS2rqFsyYah35hX1FH6rPNWtLqqXZDQC9x6GW141C".into()),
+            })
+        );
+
+        let r = with_onchain(json!({ "treasuryInfo": { "amount": 5 } }));
+        assert_eq!(r.spend(), Some(Spend { amount: 5, beneficiary: None }));
+
+        assert!(with_onchain(json!({ "treasuryInfo": { "amount": "lots" } })).spend().is_none());
+        assert!(with_onchain(json!({})).spend().is_none());
+        assert!(referendum(json!(0), None).spend().is_none());
+    }
+
+    #[test]
+    fn decision_deposit_absent_or_null_counts_as_missing() {
+        let with_onchain = |onchain: Value| -> SubSquareReferendum {
+            serde_json::from_value(json!({
+                "referendumIndex": 7,
+                "track": 0,
+                "state": { "name": "Preparing" },
+                "onchainData": onchain,
+            }))
+            .unwrap()
+        };
+        let placed = json!({ "info": { "decisionDeposit": { "who": "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5", "amount": "1000000000000" } } });
+        assert!(with_onchain(placed).has_decision_deposit());
+        assert!(!with_onchain(json!({ "info": { "decisionDeposit": null } })).has_decision_deposit());
+        assert!(!with_onchain(json!({ "info": {} })).has_decision_deposit());
+        assert!(!with_onchain(json!({})).has_decision_deposit());
+        assert!(!referendum(json!(0), None).has_decision_deposit());
+    }
+
+    #[test]
+    fn status_parses_case_insensitively() {
+        assert_eq!("deciding".parse::<ReferendumStatus>().unwrap(), ReferendumStatus::Deciding);
+        assert_eq!(" TIMEDOUT ".parse::<ReferendumStatus>().unwrap(), ReferendumStatus::TimedOut);
+        assert!("voting".parse::<ReferendumStatus>().is_err());
+        for status in ReferendumStatus::ALL {
+            assert_eq!(status.as_str().parse::<ReferendumStatus>().unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn whitelist_mode_serializes_whitelist() {
+        let cfg = networks_config("whitelist", Some(vec!["16ap6fdqS2rqFsyYah35hX1FH6rPNWtLqqXZDQC9x6GW141C".into()]));
+        let v = serde_json::to_value(&cfg).unwrap();
+        assert_eq!(v["accessibility"], "whitelist");
+        assert_eq!(v["whitelist"], json!(["16ap6fdqS2rqFsyYah35hX1FH6rPNWtLqqXZDQC9x6GW141C"]));
+    }
+
+    #[test]
+    fn public_mode_omits_whitelist() {
+        let v = serde_json::to_value(networks_config("public", None)).unwrap();
+        assert_eq!(v["accessibility"], "public");
+        assert!(v.get("whitelist").is_none());
+    }
+
+    fn proposal(real_proposer: Option<Value>, discussion: Option<&str>, nonce: Option<&str>) -> ProposalData {
+        let mut networks_config = networks_config("public", None);
+        networks_config.networks.push(NetworkDetail {
+            network: "polkadot".into(),
+            ss58_format: 0,
+            assets: vec![AssetConfig { symbol: "DOT".into(), decimals: 10, voting_threshold: None, multiplier: None }],
+        });
+        ProposalData {
+            space: "twodao".into(),
+            title: "t".into(),
+            content: "c".into(),
+            content_type: "markdown".into(),
+            choice_type: "single".into(),
+            choices: vec!["Aye".into(), "Nay".into()],
+            start_date: 1,
+            end_date: 2,
+            snapshot_heights: HashMap::from([("polkadot".to_string(), 100)]),
+            real_proposer,
+            proposer_network: "polkadot".into(),
+            version: "5".into(),
+            timestamp: 3,
+            networks_config,
+            discussion: discussion.map(Into::into),
+            nonce: nonce.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn absent_optional_fields_are_omitted_not_null() {
+        let v = serde_json::to_value(proposal(None, None, None)).unwrap();
+        for key in ["realProposer", "discussion", "nonce"] {
+            assert!(v.get(key).is_none(), "{key} 不应出现：{v}");
+        }
+        let asset = &v["networksConfig"]["networks"][0]["assets"][0];
+        assert!(asset.get("votingThreshold").is_none() && asset.get("multiplier").is_none(), "{asset}");
+        assert!(!v.to_string().contains("null"), "{v}");
+
+        let v = serde_json::to_value(proposal(
+            Some(json!({ "address": "1abc", "network": "polkadot" })),
+            Some("https://polkadot.subsquare.io/referenda/7"),
+            Some("n1"),
+        ))
+        .unwrap();
+        assert_eq!(v["realProposer"], json!({ "address": "1abc", "network": "polkadot" }));
+        assert_eq!(v["discussion"], "https://polkadot.subsquare.io/referenda/7");
+        assert_eq!(v["nonce"], "n1");
+    }
 }
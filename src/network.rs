@@ -0,0 +1,87 @@
+use std::str::FromStr;
+
+/// 同步的中继链网络，决定地址前缀、代币和各上游服务的默认地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Network {
+    #[default]
+    Polkadot,
+    Kusama,
+}
+
+impl Network {
+    /// OpenSquare / snapshotHeights 中使用的网络名
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Polkadot => "polkadot",
+            Network::Kusama => "kusama",
+        }
+    }
+
+    /// SS58 地址前缀
+    pub fn ss58_format(&self) -> u16 {
+        match self {
+            Network::Polkadot => 0,
+            Network::Kusama => 2,
+        }
+    }
+
+    /// 原生代币符号
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Network::Polkadot => "DOT",
+            Network::Kusama => "KSM",
+        }
+    }
+
+    /// 原生代币精度
+    pub fn decimals(&self) -> u8 {
+        match self {
+            Network::Polkadot => 10,
+            Network::Kusama => 12,
+        }
+    }
+
+    /// SubSquare API 默认地址
+    pub fn subsquare_api_url(&self) -> &'static str {
+        match self {
+            Network::Polkadot => "https://polkadot-api.subsquare.io",
+            Network::Kusama => "https://kusama-api.subsquare.io",
+        }
+    }
+
+    /// SubSquare 前端地址，用于拼接公投详情页链接
+    pub fn subsquare_site_url(&self) -> &'static str {
+        match self {
+            Network::Polkadot => "https://polkadot.subsquare.io",
+            Network::Kusama => "https://kusama.subsquare.io",
+        }
+    }
+
+    /// Subscan API 默认地址
+    pub fn subscan_api_url(&self) -> &'static str {
+        match self {
+            Network::Polkadot => "https://polkadot.api.subscan.io",
+            Network::Kusama => "https://kusama.api.subscan.io",
+        }
+    }
+
+    /// 节点 JSON-RPC 默认地址
+    pub fn rpc_url(&self) -> &'static str {
+        match self {
+            Network::Polkadot => "https://rpc.polkadot.io",
+            Network::Kusama => "https://kusama-rpc.polkadot.io",
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "polkadot" => Ok(Network::Polkadot),
+            "kusama" => Ok(Network::Kusama),
+            other => anyhow::bail!("NETWORK 取值无效：{}（可选 polkadot / kusama）", other),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use log::warn;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+use crate::config::Config;
+
+/// 哪些发布结果需要发送通知
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NotifyOn {
+    /// 成功和失败都通知
+    #[default]
+    All,
+    /// 只通知失败
+    Failures,
+    /// 不发送通知
+    None,
+}
+
+impl FromStr for NotifyOn {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "all" => Ok(NotifyOn::All),
+            "failures" => Ok(NotifyOn::Failures),
+            "none" => Ok(NotifyOn::None),
+            other => anyhow::bail!("NOTIFY_ON 取值无效：{}（可选 all / failures / none）", other),
+        }
+    }
+}
+
+/// webhook 载荷格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NotifyKind {
+    /// 直接发送 `NotifyEvent` 的 JSON
+    #[default]
+    Raw,
+    /// Slack incoming webhook（blocks）
+    Slack,
+    /// Discord webhook（embeds）
+    Discord,
+}
+
+impl FromStr for NotifyKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "raw" => Ok(NotifyKind::Raw),
+            "slack" => Ok(NotifyKind::Slack),
+            "discord" => Ok(NotifyKind::Discord),
+            other => anyhow::bail!("NOTIFY_KIND 取值无效：{}（可选 slack / discord / raw）", other),
+        }
+    }
+}
+
+/// 发布结果事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Published,
+    PublishFailed,
+    /// OpenSquare 连续以 401/403 拒绝，本轮已中止，需要人工检查签名密钥
+    SignerRejected,
+}
+
+/// 发送到 webhook 的通知内容
+#[derive(Debug, Serialize)]
+pub struct NotifyEvent<'a> {
+    pub event: EventKind,
+    pub index: u32,
+    pub title: &'a str,
+    /// HTTP 状态码或错误描述
+    pub status: String,
+    pub subsquare_url: String,
+    /// 发布成功且 OpenSquare 返回了提案 CID 时才有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opensquare_url: Option<String>,
+}
+
+impl NotifyEvent<'_> {
+    /// 聊天消息中的一行摘要
+    fn headline(&self) -> String {
+        match self.event {
+            EventKind::Published => format!("✅ 已发布公投 #{}：{}", self.index, self.title),
+            EventKind::PublishFailed => format!("❌ 公投 #{} 发布失败（{}）：{}", self.index, self.status, self.title),
+            EventKind::SignerRejected => format!(
+                "🚨 OpenSquare 拒绝了签名（{}），同步已中止，请检查签名密钥；最后一次被拒的是公投 #{}",
+                self.status, self.index
+            ),
+        }
+    }
+
+    /// 链接行，Slack 使用 `<url|text>` 语法，Discord 使用 markdown 链接
+    fn links(&self, slack: bool) -> String {
+        let link = |url: &str, text: &str| {
+            if slack { format!("<{}|{}>", url, text) } else { format!("[{}]({})", text, url) }
+        };
+        let mut links = vec![link(&self.subsquare_url, "SubSquare")];
+        if let Some(url) = &self.opensquare_url {
+            links.insert(0, link(url, "OpenSquare"));
+        }
+        links.join(" · ")
+    }
+}
+
+/// 按 NOTIFY_KIND 构造 webhook 载荷
+fn build_payload(kind: NotifyKind, event: &NotifyEvent<'_>) -> Value {
+    match kind {
+        NotifyKind::Raw => json!(event),
+        NotifyKind::Slack => json!({
+            "text": event.headline(),
+            "blocks": [{
+                "type": "section",
+                "text": {
+                    "type": "mrkdwn",
+                    "text": format!("{}\n{}", event.headline(), event.links(true)),
+                },
+            }],
+        }),
+        NotifyKind::Discord => json!({
+            "embeds": [{
+                "title": event.headline(),
+                "url": event.opensquare_url.as_deref().unwrap_or(&event.subsquare_url),
+                "description": event.links(false),
+                "color": match event.event {
+                    EventKind::Published => 0x2ECC71,
+                    EventKind::PublishFailed => 0xE74C3C,
+                    EventKind::SignerRejected => 0x992D22,
+                },
+            }],
+        }),
+    }
+}
+
+/// 按配置发送发布结果通知
+///
+/// 通知是尽力而为的：未配置、被 NOTIFY_ON 过滤或发送失败都不会影响同步流程，失败只记录日志
+pub async fn notify(client: &Client, cfg: &Config, event: NotifyEvent<'_>) {
+    let Some(url) = &cfg.notify_webhook_url else {
+        return;
+    };
+    let wanted = match cfg.notify_on {
+        NotifyOn::All => true,
+        NotifyOn::Failures => event.event != EventKind::Published,
+        NotifyOn::None => false,
+    };
+    if !wanted {
+        return;
+    }
+
+    let payload = build_payload(cfg.notify_kind, &event);
+    match client.post(url).json(&payload).send().await {
+        Ok(resp) if !resp.status().is_success() => {
+            warn!("📣 公投 #{} 的通知发送失败：HTTP {}", event.index, resp.status());
+        }
+        Ok(_) => {}
+        Err(e) => warn!("📣 公投 #{} 的通知发送失败：{}", event.index, e),
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Result};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter as Governor};
+use log::{debug, log_enabled, warn, Level};
+use reqwest::header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Client, Request, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use std::num::NonZeroU32;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// 收到 429 后最多重试的次数
+const MAX_429_RETRIES: u32 = 3;
+
+/// 没有 Retry-After 头时的初始等待时间
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// 出站请求限流器，按目标主机分别计数
+///
+/// 未配置 RATE_LIMIT_RPS 时不做限流，只保留 429 的退避重试
+pub struct RateLimiter {
+    limiter: Option<DefaultKeyedRateLimiter<String>>,
+}
+
+impl RateLimiter {
+    /// 按每秒请求数构建限流器，`None` 或 0 表示不限流
+    pub fn new(rps: Option<u32>) -> Self {
+        let limiter = rps
+            .and_then(NonZeroU32::new)
+            .map(|rps| Governor::keyed(Quota::per_second(rps)));
+        RateLimiter { limiter }
+    }
+
+    /// 等待令牌后发送请求；遇到 429 时按 Retry-After 等待并重试
+    pub async fn send(&self, client: &Client, req: RequestBuilder) -> Result<Response> {
+        let request = req.build()?;
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        let mut attempt = 0;
+        loop {
+            let req = request
+                .try_clone()
+                .ok_or_else(|| anyhow!("请求体无法复制，不能重试"))?;
+            if let Some(limiter) = &self.limiter {
+                limiter.until_key_ready(&host).await;
+            }
+
+            if log_enabled!(Level::Debug) {
+                log_request(&req);
+            }
+            let mut resp = client.execute(req).await?;
+            if log_enabled!(Level::Debug) {
+                resp = log_response(resp).await?;
+            }
+            if resp.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= MAX_429_RETRIES {
+                return Ok(resp);
+            }
+
+            let wait = retry_after(&resp).unwrap_or(DEFAULT_RETRY_AFTER * 2u32.pow(attempt));
+            attempt += 1;
+            warn!("🐢 {} 返回 429，{:?} 后第 {} 次重试", host, wait, attempt);
+            sleep(wait).await;
+        }
+    }
+}
+
+/// 非 JSON 错误信息中保留的响应体最大字符数
+const NON_JSON_PREVIEW_CHARS: usize = 200;
+
+/// 读取应为 JSON 的响应体文本
+///
+/// 上游过载时可能以 200 状态返回 HTML 维护页，直接交给 serde 只会得到难以辨认的解析错误；
+/// 这里先检查 Content-Type，非 JSON 时返回带状态码和响应体片段的明确错误
+pub async fn read_json_text(resp: Response, upstream: &str) -> Result<(StatusCode, String)> {
+    let status = resp.status();
+    let content_type = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let text = resp.text().await?;
+    if let Some(e) = non_json_error(upstream, status, content_type.as_deref(), &text) {
+        return Err(e);
+    }
+    Ok((status, text))
+}
+
+/// 响应声明了非 JSON 的 Content-Type，或未声明但内容是 HTML 时返回错误
+fn non_json_error(upstream: &str, status: StatusCode, content_type: Option<&str>, body: &str) -> Option<anyhow::Error> {
+    let is_json = match content_type {
+        Some(ct) => ct.to_ascii_lowercase().contains("json"),
+        None => !body.trim_start().starts_with('<'),
+    };
+    if is_json {
+        return None;
+    }
+    let snippet: String = body.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(NON_JSON_PREVIEW_CHARS).collect();
+    Some(anyhow!(
+        "{} 返回了非 JSON 响应（HTTP {}，content-type: {}），可能正在维护或故障；响应体片段：{}",
+        upstream,
+        status,
+        content_type.unwrap_or("未声明"),
+        snippet
+    ))
+}
+
+/// 解析 Retry-After 头（秒数形式）
+fn retry_after(resp: &Response) -> Option<Duration> {
+    resp.headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// 调试日志中需要隐去取值的请求头（小写）
+const SECRET_HEADERS: &[&str] = &["x-api-key", "authorization", "cookie", "set-cookie"];
+
+/// 调试日志中需要隐去取值的 JSON 字段
+const SECRET_FIELDS: &[&str] = &["signature"];
+
+/// 隐去敏感取值时使用的占位符
+const REDACTED: &str = "<redacted>";
+
+/// 格式化请求头，敏感头只保留名称
+fn redact_headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if SECRET_HEADERS.contains(&name.as_str()) {
+                REDACTED
+            } else {
+                value.to_str().unwrap_or("<binary>")
+            };
+            format!("{}: {}", name, value)
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// 格式化请求/响应体：JSON 中的敏感字段替换为占位符，非 JSON 按文本原样输出
+fn redact_body(bytes: &[u8]) -> String {
+    fn redact(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, v) in map.iter_mut() {
+                    if SECRET_FIELDS.contains(&key.as_str()) {
+                        *v = Value::String(REDACTED.into());
+                    } else {
+                        redact(v);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(redact),
+            _ => {}
+        }
+    }
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(mut json) => {
+            redact(&mut json);
+            json.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// debug 级别记录完整的出站请求
+fn log_request(req: &Request) {
+    let body = req.body().and_then(|b| b.as_bytes()).map(redact_body).unwrap_or_default();
+    debug!(
+        "➡️ {} {} [{}] {}",
+        req.method(),
+        req.url(),
+        redact_headers(req.headers()),
+        body
+    );
+}
+
+/// debug 级别记录完整响应：读出响应体后重新组装成 Response 交还调用方
+async fn log_response(resp: Response) -> Result<Response> {
+    let status = resp.status();
+    let url = resp.url().clone();
+    let headers = resp.headers().clone();
+    let bytes = resp.bytes().await?;
+    debug!("⬅️ {} {} [{}] {}", status, url, redact_headers(&headers), redact_body(&bytes));
+
+    let mut rebuilt = http::Response::builder().status(status);
+    if let Some(h) = rebuilt.headers_mut() {
+        *h = headers;
+    }
+    Ok(Response::from(rebuilt.body(bytes)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn api_key_header_is_redacted() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-API-Key", HeaderValue::from_static("secret-key"));
+        headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+        let out = redact_headers(&headers);
+        assert!(!out.contains("secret-key"));
+        assert!(out.contains("x-api-key: <redacted>"));
+        assert!(out.contains("content-type: application/json"));
+    }
+
+    #[test]
+    fn nested_signature_is_redacted() {
+        let body = br#"{"data":{"title":"t"},"address":"1abc","signature":"0xdeadbeef"}"#;
+        let out = redact_body(body);
+        assert!(!out.contains("0xdeadbeef"));
+        assert!(out.contains(r#""signature":"<redacted>""#));
+        assert!(out.contains(r#""address":"1abc""#));
+    }
+
+    #[test]
+    fn html_maintenance_page_is_reported_clearly() {
+        let page = "<!DOCTYPE html>\n<html>\n  <body>Under   maintenance</body></html>";
+        let err = non_json_error("SubSquare", StatusCode::OK, Some("text/html; charset=utf-8"), page).unwrap();
+        let msg = err.to_string();
+        assert!(msg.contains("SubSquare 返回了非 JSON 响应"), "{}", msg);
+        assert!(msg.contains("content-type: text/html; charset=utf-8"), "{}", msg);
+        assert!(msg.contains("<html> <body>Under maintenance</body></html>"), "{}", msg);
+
+        assert!(non_json_error("SubSquare", StatusCode::OK, None, page).is_some());
+        assert!(non_json_error("SubSquare", StatusCode::OK, Some("application/json"), "{}").is_none());
+        assert!(non_json_error("RPC", StatusCode::OK, Some("application/json; charset=utf-8"), "{}").is_none());
+        assert!(non_json_error("RPC", StatusCode::OK, None, "{\"result\":1}").is_none());
+    }
+
+    #[test]
+    fn non_json_body_is_logged_as_text() {
+        assert_eq!(redact_body(b"Too Many Requests"), "Too Many Requests");
+    }
+}
@@ -0,0 +1,35 @@
+use std::future::Future;
+use uuid::Uuid;
+
+tokio::task_local! {
+    static RUN_ID: Uuid;
+}
+
+/// 在 `run_id` 的上下文中执行一轮同步，期间（同一任务内）的日志都能取到该 run id
+pub async fn scope<F: Future>(run_id: Uuid, f: F) -> F::Output {
+    RUN_ID.scope(run_id, f).await
+}
+
+/// 当前任务所属的同步轮次，不在 `scope` 内时为 None
+pub fn current() -> Option<Uuid> {
+    RUN_ID.try_with(|id| *id).ok()
+}
+
+/// 日志前缀 `[run <uuid>] `，不在同步轮次内时为空串；供日志格式化函数使用，便于按 run id grep 一轮的全部日志
+pub fn log_prefix() -> String {
+    current().map_or_else(String::new, |id| format!("[run {}] ", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn prefix_only_inside_scope() {
+        assert_eq!(log_prefix(), "");
+        let id = Uuid::new_v4();
+        let inside = scope(id, async { (current(), log_prefix()) }).await;
+        assert_eq!(inside, (Some(id), format!("[run {}] ", id)));
+        assert_eq!(current(), None);
+    }
+}
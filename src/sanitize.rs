@@ -0,0 +1,320 @@
+/// 连同内容整块删除的 HTML 元素：脚本、样式和嵌入内容对提案正文没有意义
+const DROPPED_ELEMENTS: &[&str] = &["script", "style", "iframe", "object", "embed"];
+
+/// 正文中最多保留的连续空行数
+const MAX_BLANK_LINES: usize = 1;
+
+/// 清理 SubSquare 正文中会破坏 OpenSquare 渲染的内容，返回清理后的文本及是否有改动
+///
+/// 依次删除 script / style 等元素及其内容、HTML 注释、其余 HTML 标签（保留标签内的文字）、
+/// data: URI（连同引用它的 markdown 图片或链接），最后把多个连续空行压缩为一个；
+/// markdown 自动链接 `<https://...>` 和 `a < b` 这类比较不视为标签
+pub fn sanitize_content(input: &str) -> (String, bool) {
+    let mut out = input.to_string();
+    for name in DROPPED_ELEMENTS {
+        out = drop_element(&out, name);
+    }
+    out = strip_comments(&out);
+    out = strip_tags(&out);
+    out = strip_data_uris(&out);
+    out = collapse_blank_lines(&out);
+    let changed = out != input;
+    (out, changed)
+}
+
+/// 删除 `<name ...>...</name>` 整块；没有闭合标签时只删除开始标签
+fn drop_element(s: &str, name: &str) -> String {
+    // 只做 ASCII 小写转换，字节偏移与原文一致
+    let lower = s.to_ascii_lowercase();
+    let open = format!("<{}", name);
+    let close = format!("</{}", name);
+    let mut out = String::with_capacity(s.len());
+    let mut pos = 0;
+    while let Some(found) = lower[pos..].find(&open) {
+        let start = pos + found;
+        let after = start + open.len();
+        // `<scripts>` 之类标签名更长的不算
+        if lower[after..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '-') {
+            out.push_str(&s[pos..after]);
+            pos = after;
+            continue;
+        }
+        out.push_str(&s[pos..start]);
+        let end_of_tag = |from: usize| lower[from..].find('>').map_or(s.len(), |gt| from + gt + 1);
+        pos = match lower[after..].find(&close) {
+            Some(offset) => end_of_tag(after + offset),
+            None => end_of_tag(after),
+        };
+    }
+    out.push_str(&s[pos..]);
+    out
+}
+
+/// 删除 `<!-- ... -->` 注释，未闭合时删除到末尾
+fn strip_comments(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("<!--") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start + 4..].find("-->") {
+            Some(end) => &rest[start + 4 + end + 3..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 删除 HTML 标签本身，保留标签之间的文字
+fn strip_tags(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(lt) = rest.find('<') {
+        out.push_str(&rest[..lt]);
+        let tail = &rest[lt..];
+        match tag_len(tail) {
+            Some(len) => rest = &tail[len..],
+            None => {
+                out.push('<');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `s` 以 `<` 开头，是 HTML 标签时返回标签的字节长度
+///
+/// 标签名须以字母开头，其后紧跟空白、`>` 或 `/`；`<!DOCTYPE ...>` 等声明也算标签
+fn tag_len(s: &str) -> Option<usize> {
+    let body = &s[1..];
+    if !body.starts_with('!') {
+        let name = body.strip_prefix('/').unwrap_or(body);
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        let name_len = name
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .unwrap_or(name.len());
+        if !name[name_len..].starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            return None;
+        }
+    }
+    s.find('>').map(|gt| gt + 1)
+}
+
+/// 删除 `data:<type>/<subtype>...` 形式的 URI；作为 markdown 图片或链接目标时整个图片/链接一并删除
+fn strip_data_uris(s: &str) -> String {
+    let lower = s.to_ascii_lowercase();
+    let mut out = String::with_capacity(s.len());
+    let mut pos = 0;
+    let mut search = 0;
+    while let Some(found) = lower[search..].find("data:") {
+        let start = search + found;
+        search = start + 5;
+        // 须位于 URI 可能出现的位置，避免误删 "metadata:" 之类的正文
+        let prev = s[..start].chars().next_back();
+        let at_boundary = prev.is_none_or(|c| c.is_whitespace() || matches!(c, '(' | '"' | '\'' | '=' | '<'));
+        let has_mime = lower[search..]
+            .split_once('/')
+            .is_some_and(|(ty, _)| !ty.is_empty() && ty.chars().all(|c| c.is_ascii_alphabetic()));
+        if !at_boundary || !has_mime {
+            continue;
+        }
+
+        let end = s[start..]
+            .find(|c: char| c.is_whitespace() || matches!(c, ')' | '"' | '\'' | '>'))
+            .map_or(s.len(), |offset| start + offset);
+        let (mut cut_start, mut cut_end) = (start, end);
+        if s[..start].ends_with("](") {
+            if let Some(open) = s[..start - 2].rfind('[') {
+                cut_start = if s[..open].ends_with('!') { open - 1 } else { open };
+                if s[end..].starts_with(')') {
+                    cut_end = end + 1;
+                }
+            }
+        }
+        let cut_start = cut_start.max(pos);
+        out.push_str(&s[pos..cut_start]);
+        pos = cut_end;
+        search = cut_end;
+    }
+    out.push_str(&s[pos..]);
+    out
+}
+
+/// 把超过 MAX_BLANK_LINES 的连续空行（含只有空白的行）压缩掉，其余行原样保留
+fn collapse_blank_lines(s: &str) -> String {
+    let mut blank_run = 0;
+    s.split('\n')
+        .filter(|line| {
+            if line.trim().is_empty() {
+                blank_run += 1;
+                blank_run <= MAX_BLANK_LINES
+            } else {
+                blank_run = 0;
+                true
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 去掉 markdown 语法，供 CONTENT_TYPE=plain 使用
+///
+/// 删除标题和引用标记、代码块围栏与分隔线，图片只留替代文字，链接写成 `文字 (url)`，
+/// 自动链接去掉尖括号，再删除 `**` `__` `~~` 和行内代码的反引号；列表项统一为 `- `。
+/// 单个 `*` / `_` 的强调不处理，以免误伤 snake_case 之类的正文
+pub fn strip_markdown(input: &str) -> String {
+    input
+        .split('\n')
+        .filter_map(|line| {
+            let trimmed = line.trim();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") || is_thematic_break(trimmed) {
+                return None;
+            }
+            // 保留缩进，嵌套列表的层次在纯文本中仍然可读
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let mut line = line.trim_start().to_string();
+            while let Some(rest) = line.strip_prefix('>') {
+                line = rest.trim_start().to_string();
+            }
+            let hashes = line.chars().take_while(|&c| c == '#').count();
+            if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+                line = line[hashes..].trim_start().to_string();
+            }
+            if let Some(item) = line.strip_prefix("* ").or_else(|| line.strip_prefix("+ ")) {
+                line = format!("- {}", item);
+            }
+            let mut line = strip_autolinks(&strip_links(&line));
+            for marker in ["**", "__", "~~", "`"] {
+                line = line.replace(marker, "");
+            }
+            Some(format!("{}{}", indent, line))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `---`、`***`、`___` 这类只由同一符号（可夹空格）组成、至少三个的分隔线
+fn is_thematic_break(line: &str) -> bool {
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && matches!(marks[0], '-' | '*' | '_') && marks.iter().all(|&c| c == marks[0])
+}
+
+/// `![alt](url)` 换成 alt，`[text](url)` 换成 `text (url)`，文字为空或与 url 相同时只留 url
+fn strip_links(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find("](").map(|c| open + c) else { break };
+        let Some(end) = rest[close + 2..].find(')').map(|e| close + 2 + e) else { break };
+        let text = &rest[open + 1..close];
+        // 文字中还有 `[` 时从里面那个开始匹配
+        if let Some(inner) = text.rfind('[') {
+            out.push_str(&rest[..open + 1 + inner]);
+            rest = &rest[open + 1 + inner..];
+            continue;
+        }
+        let url = rest[close + 2..end].trim();
+        let is_image = rest[..open].ends_with('!');
+        out.push_str(&rest[..if is_image { open - 1 } else { open }]);
+        if is_image {
+            out.push_str(text);
+        } else if text.trim().is_empty() || text.trim() == url {
+            out.push_str(url);
+        } else {
+            out.push_str(&format!("{} ({})", text, url));
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `<https://...>` / `<mailto:...>` 去掉尖括号
+fn strip_autolinks(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(lt) = rest.find('<') {
+        out.push_str(&rest[..lt]);
+        let tail = &rest[lt + 1..];
+        let link = tail.find('>').map(|gt| &tail[..gt]).filter(|inner| {
+            ["http://", "https://", "mailto:"].iter().any(|p| inner.starts_with(p)) && !inner.contains(char::is_whitespace)
+        });
+        match link {
+            Some(inner) => {
+                out.push_str(inner);
+                rest = &tail[inner.len() + 1..];
+            }
+            None => {
+                out.push('<');
+                rest = tail;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(s: &str) -> String {
+        sanitize_content(s).0
+    }
+
+    #[test]
+    fn drops_script_and_style_with_contents() {
+        assert_eq!(clean("Hello<script>alert('x')</script> world"), "Hello world");
+        assert_eq!(clean("a<STYLE type=\"text/css\">body{}</STYLE>b"), "ab");
+        assert_eq!(clean("x<iframe src=\"https://evil\"></iframe>y"), "xy");
+    }
+
+    #[test]
+    fn strips_tags_but_keeps_text() {
+        assert_eq!(clean("<p>Fund <b>this</b><br/>now</p>"), "Fund thisnow");
+        assert_eq!(clean("a<!-- hidden -->b"), "ab");
+        assert_eq!(clean("<div\n  class=\"x\">multi-line</div>"), "multi-line");
+    }
+
+    #[test]
+    fn removes_data_uris() {
+        assert_eq!(
+            clean("Intro\n\n![chart](data:image/png;base64,iVBORw0KGgo=)\n\nEnd"),
+            "Intro\n\nEnd"
+        );
+        assert_eq!(clean("<img src=\"data:image/png;base64,AAAA\">"), "");
+        assert_eq!(clean("raw data:text/html;base64,PHNjcmlwdD4= end"), "raw  end");
+        assert_eq!(clean("[file](data:application/pdf;base64,JVBER) ok"), " ok");
+    }
+
+    #[test]
+    fn collapses_blank_lines() {
+        assert_eq!(clean("a\n\n\n  \n\nb"), "a\n\nb");
+        assert_eq!(clean("a\r\n\r\n\r\n\r\nb"), "a\r\n\r\nb");
+    }
+
+    #[test]
+    fn strips_markdown_to_plain_text() {
+        let md = "## Proposal\n\n> **Fund** the `tool` ~~now~~\n\n* one\n+ two\n\n---\n\n```\ncode\n```\n\n\
+                  See [SubSquare](https://polkadot.subsquare.io/referenda/1) or <https://x.io>.\n\n\
+                  ![logo](https://x.io/logo.png) [https://a.io](https://a.io) snake_case a < b";
+        assert_eq!(
+            strip_markdown(md),
+            "Proposal\n\nFund the tool now\n\n- one\n- two\n\n\ncode\n\n\
+             See SubSquare (https://polkadot.subsquare.io/referenda/1) or https://x.io.\n\n\
+             logo https://a.io snake_case a < b"
+        );
+        assert_eq!(strip_markdown("[not a link] and [x]"), "[not a link] and [x]");
+    }
+
+    #[test]
+    fn leaves_ordinary_markdown_untouched() {
+        let md = "## Proposal\n\nSee <https://polkadot.subsquare.io> if a < b and c > d.\n\n\
+                  metadata: v2, email <me@example.com>\n\n![logo](https://x.io/logo.png)";
+        assert_eq!(sanitize_content(md), (md.to_string(), false));
+    }
+}
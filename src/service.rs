@@ -1,22 +1,34 @@
 
-use anyhow::Result;
-use log::{info, error};
+use log::{debug, info, warn, error};
 use reqwest::Client;
-use std::collections::{HashMap};
-use chrono::{Utc, Duration as ChronoDuration};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use futures::future::join_all;
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+use chrono::{DateTime, NaiveTime, TimeZone, Utc, Duration as ChronoDuration};
+use chrono_tz::Tz;
 
-use sp_core::Pair;
-use sp_core::sr25519;
-use sp_core::crypto::{Ss58AddressFormat, Ss58AddressFormatRegistry, Ss58Codec};
-use hex;
+use uuid::Uuid;
 
-use crate::config::Config;
-use crate::db::Db;
+use crate::block_source::{self, BlockSource, HeightCache, RpcBlockSource};
+use crate::config::{Accessibility, Config, ContentSource, ContentType, FetchMode, PublishOrder, SnapshotFailureMode, SummaryPreference};
+use crate::db::{Db, NewReferendum, SyncEventKind};
+use crate::dead_letter::DeadLetters;
+use crate::error::{SyncError, SyncResult};
+use crate::network::Network;
+use crate::notify::{notify, EventKind, NotifyEvent};
+use crate::ratelimit::{read_json_text, RateLimiter};
+use crate::run_id;
+use crate::sanitize::{sanitize_content, strip_markdown};
+use crate::signing::{canonical_value, sign_request, sign_update, signer_address, Signer};
+use crate::transform;
 use crate::models::{
     SubSquareReferendum,
-    ReferendumStatus,
     ProposalData,
-    OpenSquareNewProposalRequest,
+    ProposalUpdateData,
     NetworksConfig,
     NetworkDetail,
     AssetConfig,
@@ -25,184 +37,2580 @@ use crate::models::{
 
 
 
-/// 拉取 SubSquare 公投列表，数量由配置决定
-pub async fn fetch_referenda(client: &Client, page_size: usize) -> Result<Vec<SubSquareReferendum>> {
+/// 错误信息中保留的响应体最大字符数
+const ERROR_BODY_PREVIEW_CHARS: usize = 500;
+
+/// 截断响应体用于错误信息，按字符截断避免切断多字节字符
+fn truncate_body(body: &str) -> String {
+    if body.chars().count() <= ERROR_BODY_PREVIEW_CHARS {
+        return body.to_string();
+    }
+    let head: String = body.chars().take(ERROR_BODY_PREVIEW_CHARS).collect();
+    format!("{}…", head)
+}
+
+/// 拉取 SubSquare 最新一页公投列表，数量由配置决定
+pub async fn fetch_referenda(
+    client: &Client,
+    limiter: &RateLimiter,
+    base_url: &str,
+    page_size: usize,
+) -> SyncResult<Vec<SubSquareReferendum>> {
+    fetch_referenda_page(client, limiter, base_url, 1, page_size).await
+}
+
+/// 按游标回看窗口翻页时的最大页数，防止接口异常时无限翻页
+const SUBSQUARE_MAX_PAGES: usize = 20;
+
+/// 游标回看窗口的下界：编号不高于它的公投不再拉取，尚无游标时为 None
+fn lookback_floor(cursor: Option<u32>, lookback: u32) -> Option<u32> {
+    cursor.map(|c| c.saturating_sub(lookback))
+}
+
+/// 按编号倒序翻页时，本页之后是否无需继续翻页：没有下界时只看第 1 页；
+/// 本页不足一页或已出现不高于下界的编号时停止
+fn is_last_page(indices: &[u32], page_size: usize, floor: Option<u32>) -> bool {
+    match floor {
+        None => true,
+        Some(floor) => indices.len() < page_size || indices.iter().any(|&i| i <= floor),
+    }
+}
+
+/// 本轮结束后的新游标：只有整轮无失败、无推迟时才推进到本轮拉取到的最大编号，
+/// 否则保留原游标，下一轮仍会回看这些公投
+fn advance_cursor(cursor: Option<u32>, fetched: &[u32], clean_round: bool) -> Option<u32> {
+    if !clean_round {
+        return cursor;
+    }
+    cursor.into_iter().chain(fetched.iter().copied()).max()
+}
+
+/// 从第 1 页起按编号倒序拉取编号高于 `floor` 的公投，直到越过下界
+///
+/// `floor` 为 None（尚无游标）时与 `fetch_referenda` 相同，只拉取第 1 页
+pub async fn fetch_referenda_since(
+    client: &Client,
+    limiter: &RateLimiter,
+    base_url: &str,
+    page_size: usize,
+    floor: Option<u32>,
+) -> SyncResult<Vec<SubSquareReferendum>> {
+    let mut referenda = Vec::new();
+    for page in 1..=SUBSQUARE_MAX_PAGES {
+        let items = fetch_referenda_page(client, limiter, base_url, page, page_size).await?;
+        let indices: Vec<u32> = items.iter().map(|r| r.referendum_index).collect();
+        referenda.extend(items.into_iter().filter(|r| floor.is_none_or(|f| r.referendum_index > f)));
+        if is_last_page(&indices, page_size, floor) {
+            break;
+        }
+    }
+    Ok(referenda)
+}
+
+/// 增量拉取：从第 1 页起按编号倒序翻页，某页出现已同步的编号（`known` 或数据库中已有）即停止
+///
+/// 假定同步是连续的，已同步编号以下的都已处理过；本地为空时一直翻到最后一页（最多 SUBSQUARE_MAX_PAGES 页）
+pub async fn fetch_referenda_incremental(
+    client: &Client,
+    limiter: &RateLimiter,
+    db: &Db,
+    base_url: &str,
+    page_size: usize,
+    known: &HashSet<u32>,
+) -> SyncResult<Vec<SubSquareReferendum>> {
+    collect_until_synced(
+        |page| fetch_referenda_page(client, limiter, base_url, page, page_size),
+        |indices| async move {
+            if indices.iter().any(|i| known.contains(i)) {
+                return Ok(true);
+            }
+            let unsynced = db.filter_unsynced(&indices).await.map_err(SyncError::Db)?;
+            Ok(unsynced.len() < indices.len())
+        },
+        page_size,
+    )
+    .await
+}
+
+/// `fetch_referenda_incremental` 的翻页逻辑：`fetch_page` 取指定页，`page_has_synced` 判断该页是否已有同步过的编号
+async fn collect_until_synced<F, FFut, S, SFut>(
+    mut fetch_page: F,
+    mut page_has_synced: S,
+    page_size: usize,
+) -> SyncResult<Vec<SubSquareReferendum>>
+where
+    F: FnMut(usize) -> FFut,
+    FFut: std::future::Future<Output = SyncResult<Vec<SubSquareReferendum>>>,
+    S: FnMut(Vec<u32>) -> SFut,
+    SFut: std::future::Future<Output = SyncResult<bool>>,
+{
+    let mut referenda = Vec::new();
+    for page in 1..=SUBSQUARE_MAX_PAGES {
+        let items = fetch_page(page).await?;
+        let indices: Vec<u32> = items.iter().map(|r| r.referendum_index).collect();
+        let short_page = items.len() < page_size;
+        referenda.extend(items);
+        if short_page || page_has_synced(indices).await? {
+            debug!("📄 增量拉取在第 {} 页停止", page);
+            return Ok(referenda);
+        }
+    }
+    warn!("📄 增量拉取达到 {} 页上限仍未遇到已同步的公投，更早的留待下一轮", SUBSQUARE_MAX_PAGES);
+    Ok(referenda)
+}
+
+/// 拉取 SubSquare 公投列表的指定页（从 1 开始，按编号倒序）
+async fn fetch_referenda_page(
+    client: &Client,
+    limiter: &RateLimiter,
+    base_url: &str,
+    page: usize,
+    page_size: usize,
+) -> SyncResult<Vec<SubSquareReferendum>> {
     let url = format!(
-        "https://polkadot-api.subsquare.io/gov2/referendums?page=1&page_size={}&simple=false",
-        page_size
+        "{}/gov2/referendums?page={}&page_size={}&simple=false",
+        base_url, page, page_size
     );
-    let resp = client.get(&url)
-        .send().await?
-        .json::<serde_json::Value>().await?;
-    let items = serde_json::from_value::<Vec<SubSquareReferendum>>(resp["items"].clone())?;
+    let resp = limiter
+        .send(client, client.get(&url))
+        .await
+        .map_err(|e| SyncError::upstream(e, SyncError::SubSquare))?;
+    let (status, text) = read_json_text(resp, "SubSquare")
+        .await
+        .map_err(|e| SyncError::upstream(e, SyncError::SubSquare))?;
+
+    // 先保留原始文本，解析失败时便于排查（如限流返回的纯文本）
+    let json: Value = serde_json::from_str(&text).map_err(|e| {
+        SyncError::SubSquare(anyhow::anyhow!(
+            "SubSquare 返回了无法解析的 JSON（HTTP {}）：{}；响应体：{}",
+            status, e, truncate_body(&text)
+        ))
+    })?;
+
+    let items = match json.get("items") {
+        Some(items @ Value::Array(_)) => items.clone(),
+        Some(_) => return Err(SyncError::SubSquare(anyhow::anyhow!(
+            "SubSquare 响应中的 items 不是数组（HTTP {}）；响应体：{}",
+            status, truncate_body(&text)
+        ))),
+        None => return Err(SyncError::SubSquare(anyhow::anyhow!(
+            "SubSquare 响应缺少 items 字段（HTTP {}）；响应体：{}",
+            status, truncate_body(&text)
+        ))),
+    };
+    let items = serde_json::from_value::<Vec<SubSquareReferendum>>(items)
+        .map_err(|e| SyncError::SubSquare(e.into()))?;
     Ok(items)
 }
 
-/// 获取最新区块高度并应用偏移
-pub async fn get_latest_block_height(client: &Client, offset: u64) -> Result<u64> {
-    let resp = client
-        .post("https://polkadot.api.subscan.io/api/scan/metadata")
-        .header("Content-Type", "application/json")
-        .header("X-API-Key", &Config::from_env()?.subscan_api_key)
-        .body("{}")
-        .send().await?
-        .json::<serde_json::Value>().await?;
+/// 拉取 SubSquare 单条公投详情，公投不存在时视为错误
+pub async fn fetch_referendum(
+    client: &Client,
+    limiter: &RateLimiter,
+    base_url: &str,
+    referendum_index: u32,
+) -> SyncResult<SubSquareReferendum> {
+    fetch_single_referendum(client, limiter, base_url, referendum_index)
+        .await?
+        .ok_or_else(|| SyncError::SubSquare(anyhow::anyhow!("SubSquare 上不存在公投 #{}", referendum_index)))
+}
+
+/// 按编号从 `/gov2/referendums/{index}` 拉取单条公投，不必翻页；SubSquare 返回 404 时为 None
+pub async fn fetch_single_referendum(
+    client: &Client,
+    limiter: &RateLimiter,
+    base_url: &str,
+    referendum_index: u32,
+) -> SyncResult<Option<SubSquareReferendum>> {
+    let url = format!("{}/gov2/referendums/{}", base_url, referendum_index);
+    let resp = limiter
+        .send(client, client.get(&url))
+        .await
+        .map_err(|e| SyncError::upstream(e, SyncError::SubSquare))?;
+    // 404 的响应体可能是 HTML 页面，先于 Content-Type 检查处理
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let (status, text) = read_json_text(resp, "SubSquare")
+        .await
+        .map_err(|e| SyncError::upstream(e, SyncError::SubSquare))?;
+    if !status.is_success() {
+        return Err(SyncError::SubSquare(anyhow::anyhow!(
+            "拉取公投 #{} 失败（HTTP {}）：{}",
+            referendum_index, status, truncate_body(&text)
+        )));
+    }
+    serde_json::from_str(&text).map(Some).map_err(|e| {
+        SyncError::SubSquare(anyhow::anyhow!(
+            "SubSquare 返回的公投 #{} 无法解析：{}；响应体：{}",
+            referendum_index, e, truncate_body(&text)
+        ))
+    })
+}
+
+/// 确认 OpenSquare 上存在配置的空间，不存在时返回错误
+pub async fn verify_space(client: &Client, limiter: &RateLimiter, cfg: &Config, space: &str) -> SyncResult<()> {
+    let url = format!("{}/api/spaces/{}", cfg.opensquare_base_url, space);
+    let resp = limiter
+        .send(client, client.get(&url))
+        .await
+        .map_err(|e| SyncError::upstream(e, SyncError::OpenSquare))?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(SyncError::Config(format!("OpenSquare 上不存在空间 {}", space)));
+    }
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(SyncError::OpenSquare(anyhow::anyhow!(
+            "查询 OpenSquare 空间 {} 失败（HTTP {}）：{}",
+            space, status, truncate_body(&body)
+        )));
+    }
+    Ok(())
+}
+
+/// 本机时钟与链上时间偏差的判定结果
+#[derive(Debug, PartialEq, Eq)]
+enum ClockSkew {
+    Within,
+    Warn,
+    Abort,
+}
+
+fn classify_skew(skew: Duration, cfg: &Config) -> ClockSkew {
+    if cfg.clock_skew_abort.is_some_and(|limit| skew > limit) {
+        ClockSkew::Abort
+    } else if cfg.max_clock_skew.is_some_and(|limit| skew > limit) {
+        ClockSkew::Warn
+    } else {
+        ClockSkew::Within
+    }
+}
+
+/// 启动检查：比较本机时间与链上最新区块时间
+///
+/// 提案的 timestamp / startDate / endDate 都取自本机时钟，偏差超过 MAX_CLOCK_SKEW_SECS 时告警，
+/// 超过 CLOCK_SKEW_ABORT_SECS 时返回配置错误；取不到区块时间时只告警，不影响启动
+pub async fn check_clock_skew(client: &Client, limiter: &RateLimiter, cfg: &Config) -> SyncResult<()> {
+    if cfg.max_clock_skew.is_none() && cfg.clock_skew_abort.is_none() {
+        return Ok(());
+    }
+    let source = block_source::from_config(cfg);
+    let block_time = match source.latest_block_time(client, limiter).await {
+        Ok(t) => t,
+        Err(e) => {
+            warn!("⚠️ 无法从 {} 获取最新区块时间，跳过时钟偏差检查：{:#}", source.name(), e);
+            return Ok(());
+        }
+    };
+    let local = Utc::now();
+    let skew = (local - block_time).abs().to_std().unwrap_or_default();
+    let detail = format!(
+        "本机时间 {} 与最新区块时间 {} 相差 {} 秒",
+        local.format("%Y-%m-%d %H:%M:%S"),
+        block_time.format("%Y-%m-%d %H:%M:%S"),
+        skew.as_secs()
+    );
+    match classify_skew(skew, cfg) {
+        ClockSkew::Within => debug!("🕰 {}", detail),
+        ClockSkew::Warn => warn!("🕰 {}，提案的开始/结束时间可能不正确，请检查系统时钟（NTP）", detail),
+        ClockSkew::Abort => {
+            return Err(SyncError::Config(format!("{}，超过 CLOCK_SKEW_ABORT_SECS，拒绝启动", detail)));
+        }
+    }
+    Ok(())
+}
+
+/// 一轮同步中各网络的快照高度
+#[derive(Clone)]
+pub struct Snapshots {
+    /// 网络名 -> 快照高度，直接作为 snapshotHeights 下发
+    pub heights: HashMap<String, u64>,
+    /// 主网络（配置中第一个成功取到高度的网络）的快照高度，写入本地记录
+    pub primary: u64,
+    /// 主网络名
+    pub primary_network: String,
+}
+
+impl Snapshots {
+    /// 按 SNAPSHOT_MODE 得到某条公投实际使用的快照高度
+    ///
+    /// 只调整 NETWORK 所在链的高度，SNAPSHOT_NETWORKS 中的其他网络沿用本轮的最新高度减偏移；
+    /// NETWORK 即主网络时本地记录的快照高度一并更新
+    pub fn for_referendum(&self, r: &SubSquareReferendum, cfg: &Config) -> SyncResult<Snapshots> {
+        let network = cfg.network.name();
+        let Some(&latest) = self.heights.get(network) else {
+            return Ok(self.clone());
+        };
+        let height = cfg
+            .snapshot_mode
+            .resolve(latest, r.indexer.as_ref().map(|i| i.block_height))
+            .map_err(|e| SyncError::SubSquare(e.context(format!("公投 #{} 的快照高度", r.referendum_index))))?;
+        let mut snapshots = self.clone();
+        snapshots.heights.insert(network.to_string(), height);
+        if self.primary_network == network {
+            snapshots.primary = height;
+        }
+        Ok(snapshots)
+    }
+}
+
+/// 并发获取所有配置网络的快照高度，部分失败时按 SNAPSHOT_PARTIAL_FAILURE 处理
+pub async fn fetch_snapshot_heights(
+    client: &Client,
+    limiter: &RateLimiter,
+    cache: &HeightCache,
+    cfg: &Config,
+) -> SyncResult<Snapshots> {
+    let lookups = cfg.snapshot_networks.iter().map(|network| async move {
+        let source: Box<dyn BlockSource> = match &network.rpc_url {
+            Some(url) => Box::new(RpcBlockSource { rpc_url: url.clone() }),
+            None => block_source::from_config(cfg),
+        };
+        let height = get_latest_block_height(
+            client, limiter, source.as_ref(), cache, &network.name, cfg.snapshot_offset, cfg.min_snapshot_height,
+        )
+        .await;
+        (network, source.name(), height)
+    });
 
-    let block_num_str = resp["data"]["blockNum"]
-        .as_str()
-        .ok_or_else(|| anyhow::anyhow!("blockNum not found"))?;
-    let height = block_num_str.parse::<u64>()?;
-    Ok(height.saturating_sub(offset))
+    // 按配置顺序处理结果，保证主网络仍是第一个成功取到高度的网络
+    let mut heights = HashMap::new();
+    let mut primary = None;
+    for (network, source_name, height) in join_all(lookups).await {
+        match height {
+            Ok(height) => {
+                info!("⛏ {} 快照块高度：{}（来源：{}）", network.name, height, source_name);
+                primary.get_or_insert((height, network.name.clone()));
+                heights.insert(network.name.clone(), height);
+            }
+            Err(e) if cfg.snapshot_partial_failure == SnapshotFailureMode::Skip => {
+                warn!("⚠️ 获取 {} 快照高度失败，跳过该网络：{:?}", network.name, e);
+            }
+            Err(e) => {
+                let e = e.context(format!("获取 {} 快照高度失败", network.name));
+                return Err(SyncError::upstream(e, SyncError::Subscan));
+            }
+        }
+    }
+    let (primary, primary_network) = primary
+        .ok_or_else(|| SyncError::Subscan(anyhow::anyhow!("所有网络的快照高度都获取失败")))?;
+    Ok(Snapshots { heights, primary, primary_network })
 }
 
+/// 拉取 OpenSquare 提案列表时的分页大小
+const OPENSQUARE_PAGE_SIZE: usize = 100;
+
+/// 拉取 OpenSquare 提案列表的最大页数，防止接口异常时无限翻页
+const OPENSQUARE_MAX_PAGES: usize = 100;
+
+/// 若标题正是本服务按当前配置发布的格式，返回其中的公投编号
+///
+/// 依次去掉 PUBLISHED_TITLE_PREFIX、TITLE_PREFIX 和 TITLE_SUFFIX（都必须存在），剩下的部分须符合 `Track::format_title`
+fn published_index(title: &str, cfg: &Config) -> Option<u32> {
+    let mut rest = title;
+    for prefix in [cfg.published_title_prefix.as_deref(), cfg.title_prefix.as_deref()].into_iter().flatten() {
+        rest = rest.strip_prefix(prefix)?.strip_prefix(' ')?;
+    }
+    if let Some(suffix) = cfg.title_suffix.as_deref() {
+        rest = rest.strip_suffix(suffix)?.strip_suffix(' ')?;
+    }
+    Track::parse_index(rest)
+}
+
+/// 拉取 OpenSquare 空间中由 `address` 发布的提案，从标题解析出已发布的公投编号
+///
+/// 别人发布的提案即使标题格式相同也不算，否则一条同名提案就会让真正的公投永远不再发布
+pub async fn fetch_opensquare_indices(
+    client: &Client,
+    limiter: &RateLimiter,
+    cfg: &Config,
+    space: &str,
+    address: &str,
+) -> SyncResult<Vec<u32>> {
+    let mut indices = Vec::new();
+    for page in 1..=OPENSQUARE_MAX_PAGES {
+        let url = format!(
+            "{}/api/{}/proposals?page={}&pageSize={}",
+            cfg.opensquare_base_url, space, page, OPENSQUARE_PAGE_SIZE
+        );
+        let resp = limiter
+            .send(client, client.get(&url))
+            .await
+            .map_err(|e| SyncError::upstream(e, SyncError::OpenSquare))?;
+        let (status, text) = read_json_text(resp, "OpenSquare")
+            .await
+            .map_err(|e| SyncError::upstream(e, SyncError::OpenSquare))?;
+        if !status.is_success() {
+            return Err(SyncError::OpenSquare(anyhow::anyhow!(
+                "拉取 OpenSquare 提案列表失败（HTTP {}）：{}",
+                status, truncate_body(&text)
+            )));
+        }
+        let json: Value = serde_json::from_str(&text).map_err(|e| SyncError::OpenSquare(e.into()))?;
+        let items = json["items"].as_array().ok_or_else(|| {
+            SyncError::OpenSquare(anyhow::anyhow!("OpenSquare 提案列表缺少 items 数组：{}", truncate_body(&text)))
+        })?;
+
+        indices.extend(
+            items
+                .iter()
+                .filter(|p| p.get("address").or_else(|| p.get("proposer")).and_then(Value::as_str) == Some(address))
+                .filter_map(|p| p["title"].as_str())
+                .filter_map(|title| published_index(title, cfg)),
+        );
+        if items.len() < OPENSQUARE_PAGE_SIZE {
+            break;
+        }
+    }
+    Ok(indices)
+}
+
+/// 获取最新区块高度并应用偏移，TTL 内优先使用缓存的原始高度
+///
+/// 上游偶发返回过小的高度（如 0）时，快照会让投票权失去意义：原始高度明显低于此前见过的高度，
+/// 或扣除偏移后低于 `min_height`（MIN_SNAPSHOT_HEIGHT）时返回错误，宁可本轮失败也不用错误的快照发布
+pub async fn get_latest_block_height(
+    client: &Client,
+    limiter: &RateLimiter,
+    source: &dyn BlockSource,
+    cache: &HeightCache,
+    network: &str,
+    offset: u64,
+    min_height: u64,
+) -> anyhow::Result<u64> {
+    let height = match cache.get(network) {
+        Some(height) => height,
+        None => {
+            let height = source.latest_height(client, limiter).await?;
+            if let Err(previous) = cache.observe(network, height) {
+                error!("🚨 异常快照高度：{} 返回 {} 的最新高度 {}，低于此前见过的 {}", source.name(), network, height, previous);
+                anyhow::bail!("{} 的最新高度 {} 低于此前见过的 {}，疑似上游异常", network, height, previous);
+            }
+            cache.insert(network, height);
+            height
+        }
+    };
+    let snapshot = height.saturating_sub(offset);
+    if snapshot < min_height {
+        error!("🚨 异常快照高度：{} 的快照高度 {}（最新 {} 减偏移 {}）低于 MIN_SNAPSHOT_HEIGHT {}", network, snapshot, height, offset, min_height);
+        anyhow::bail!("{} 的快照高度 {} 低于 MIN_SNAPSHOT_HEIGHT {}，疑似上游异常", network, snapshot, min_height);
+    }
+    Ok(snapshot)
+}
+
+
+/// 截断时追加在摘要末尾的标记
+const TRUNCATED_MARKER: &str = "…(truncated)";
+
+/// 将摘要截断到 `max_chars` 个字符以内（按字符而非字节，保证不切断多字节字符），
+/// 截断时追加标记和原文链接；返回截断后的摘要及是否发生截断
+fn truncate_summary(summary: &str, max_chars: usize, url: &str) -> (String, bool) {
+    if summary.chars().count() <= max_chars {
+        return (summary.to_string(), false);
+    }
+    let suffix = format!("\n\n{} {}", TRUNCATED_MARKER, url);
+    let keep = max_chars.saturating_sub(suffix.chars().count());
+    let head: String = summary.chars().take(keep).collect();
+    (format!("{}{}", head, suffix), true)
+}
+
+/// 按偏好从 AI 摘要、作者摘要、原始内容中挑选正文摘要，空字符串视为缺失；
+/// 同时返回所用来源的名称，便于调试
+///
+/// SUMMARY_PREFERENCE 未设置时由 CONTENT_SOURCE 决定摘要与原文的先后
+fn select_summary(
+    r: &SubSquareReferendum,
+    preference: SummaryPreference,
+    source: ContentSource,
+) -> (String, &'static str) {
+    let non_empty = |s: Option<&String>| s.filter(|s| !s.trim().is_empty()).cloned();
+    let ai = non_empty(r.content_summary.as_ref().and_then(|c| c.summary.as_ref())).map(|s| (s, "summary"));
+    let author = non_empty(r.content_summary.as_ref().and_then(|c| c.author_summary.as_ref()))
+        .map(|s| (s, "author_summary"));
+    let content = non_empty(r.content.as_ref()).map(|s| (s, "content"));
+
+    let picked = match preference {
+        SummaryPreference::Default => match source {
+            // 与历史行为保持一致：只要有 summary 字段就使用
+            ContentSource::SummaryThenContent => r.content_summary
+                .as_ref().and_then(|c| c.summary.clone()).map(|s| (s, "summary"))
+                .or_else(|| r.content.clone().map(|s| (s, "content"))),
+            ContentSource::ContentThenSummary => content.or(ai),
+            ContentSource::ContentOnly => content,
+            ContentSource::SummaryOnly => ai,
+        },
+        SummaryPreference::Ai => ai.or(content),
+        SummaryPreference::Author => author.or(content).or(ai),
+        SummaryPreference::Longest => [ai, author, content]
+            .into_iter()
+            .flatten()
+            .max_by_key(|(s, _)| s.chars().count()),
+    };
+    picked.unwrap_or((String::new(), "none"))
+}
+
+/// SubSquare 公投详情页链接
+fn subsquare_referendum_url(network: Network, referendum_index: u32) -> String {
+    format!("{}/referenda/{}", network.subsquare_site_url(), referendum_index)
+}
+
+/// 从 OpenSquare 发布接口的响应体中取出提案 CID
+fn proposal_cid(body: &str) -> Option<String> {
+    let json: Value = serde_json::from_str(body).ok()?;
+    json.get("cid").and_then(Value::as_str).map(String::from)
+}
+
+/// OpenSquare 提案页链接
+fn opensquare_proposal_url(cfg: &Config, space: &str, cid: &str) -> String {
+    format!("{}/space/{}/proposal/{}", cfg.opensquare_base_url, space, cid)
+}
+
+/// 提案的 discussion 链接：按模板替换 `{index}`，未配置模板时使用 SubSquare 公投页
+fn discussion_link(referendum_index: u32, cfg: &Config) -> Option<String> {
+    if !cfg.set_discussion_link {
+        return None;
+    }
+    Some(match &cfg.discussion_link_pattern {
+        Some(pattern) => pattern.replace("{index}", &referendum_index.to_string()),
+        None => subsquare_referendum_url(cfg.network, referendum_index),
+    })
+}
+
+/// 提案正文模板中可用的占位符取值
+struct ContentFields<'a> {
+    url: &'a str,
+    summary: &'a str,
+    index: u32,
+    track: &'a str,
+    title: &'a str,
+}
+
+/// 按 CONTENT_TEMPLATE 渲染正文，未配置模板时为 `链接 + 空行 + 摘要`
+///
+/// `{summary}` 最后替换，摘要中恰好出现的占位符文本不会被再次展开
+fn render_content(template: Option<&str>, f: &ContentFields) -> String {
+    match template {
+        None => format!("{}\n\n{}", f.url, f.summary),
+        Some(t) => t
+            .replace("{url}", f.url)
+            .replace("{index}", &f.index.to_string())
+            .replace("{track}", f.track)
+            .replace("{title}", f.title)
+            .replace("{summary}", f.summary),
+    }
+}
+
+/// 拼接提案正文：默认为 SubSquare 链接 + 摘要，可由 CONTENT_TEMPLATE 自定义
+fn build_content(r: &SubSquareReferendum, cfg: &Config) -> String {
+    let url = subsquare_referendum_url(cfg.network, r.referendum_index);
+    let (summary, source) = select_summary(r, cfg.summary_preference, cfg.content_source);
+    debug!("📝 公投 #{} 正文来源：{}", r.referendum_index, source);
+    let summary = if cfg.sanitize_content {
+        let (clean, changed) = sanitize_content(&summary);
+        if changed {
+            info!("🧹 公投 #{} 正文含 HTML、data: URI 或多余空行，已清理", r.referendum_index);
+        }
+        clean
+    } else {
+        summary
+    };
+    let track = Track::from_id(r.track_id)
+        .map(|t| t.short_name().to_string())
+        .unwrap_or_else(|| format!("T{}", r.track_id));
+    let title = r.title.clone().unwrap_or_default();
+    let mut fields = ContentFields {
+        url: &url,
+        summary: "",
+        index: r.referendum_index,
+        track: &track,
+        title: &title,
+    };
+
+    // track 全名和国库支出摘要行放在正文最前面，各占一段
+    let track_line = cfg
+        .include_track_in_content
+        .then(|| format!("Track: {}", Track::full_name_for(r.track_id)));
+    let header: String = [track_line, spend_line(r, cfg)]
+        .into_iter()
+        .flatten()
+        .map(|line| format!("{}\n\n", line))
+        .collect();
+
+    // 正文总长度受限，扣除模板其余部分后剩余的才是摘要的预算
+    let overhead = render_content(cfg.content_template.as_deref(), &fields).chars().count()
+        + header.chars().count();
+    let budget = cfg.max_content_chars.saturating_sub(overhead);
+    let (summary, truncated) = truncate_summary(&summary, budget, &url);
+    if truncated {
+        info!(
+            "✂️ 公投 #{} 摘要过长，已截断至 {} 字符以内",
+            r.referendum_index, cfg.max_content_chars
+        );
+    }
+    fields.summary = &summary;
+    let content = header + &render_content(cfg.content_template.as_deref(), &fields);
+    match cfg.content_type {
+        ContentType::Plain => strip_markdown(&content),
+        ContentType::Markdown => content,
+    }
+}
+
+/// 国库支出摘要行，如 `Requested: 10,000 DOT → 1abc...`；未开启 INCLUDE_SPEND_DETAILS 或没有支出数据时返回 None
+fn spend_line(r: &SubSquareReferendum, cfg: &Config) -> Option<String> {
+    if !cfg.include_spend_details {
+        return None;
+    }
+    let spend = r.spend()?;
+    let amount = format_amount(spend.amount, cfg.token_decimals);
+    Some(match spend.beneficiary {
+        Some(beneficiary) => format!("Requested: {} {} → {}", amount, cfg.token_symbol, beneficiary),
+        None => format!("Requested: {} {}", amount, cfg.token_symbol),
+    })
+}
+
+/// 把 planck 金额按精度格式化为带千分位的十进制数，去掉小数部分末尾的 0
+fn format_amount(planck: u128, decimals: u8) -> String {
+    let unit = 10u128.pow(decimals.into());
+    let whole = (planck / unit).to_string();
+    let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+    for (i, c) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    let fraction = planck % unit;
+    if fraction == 0 {
+        return grouped;
+    }
+    let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+    format!("{}.{}", grouped, fraction.trim_end_matches('0'))
+}
+
+/// 拼接提案标题：`[track] #index - title`，按配置在前后加上 TITLE_PREFIX / TITLE_SUFFIX
+fn build_title(r: &SubSquareReferendum, cfg: &Config) -> String {
+    let title_text = r.title.clone().unwrap_or_default();
+    let title = Track::format_title(r.track_id, r.referendum_index, &title_text);
+    [cfg.title_prefix.as_deref(), Some(title.as_str()), cfg.title_suffix.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 标题与正文的 blake2-256 哈希，用于判断上游内容是否变化
+fn content_hash(title: &str, content: &str) -> String {
+    let digest = sp_core::hashing::blake2_256(format!("{}\n{}", title, content).as_bytes());
+    hex::encode(digest)
+}
+
+/// 发布请求的幂等键：公投编号、空间与快照高度的 blake2-256 哈希
+///
+/// 不含时间戳和 nonce，同一轮内因网络错误重发同一提案时保持不变
+fn idempotency_key(referendum_index: u32, space: &str, snapshot_heights: &HashMap<String, u64>) -> String {
+    let mut heights: Vec<_> = snapshot_heights.iter().collect();
+    heights.sort();
+    let heights: Vec<String> = heights.iter().map(|(network, height)| format!("{}={}", network, height)).collect();
+    let digest = sp_core::hashing::blake2_256(
+        format!("{}\n{}\n{}", referendum_index, space, heights.join(",")).as_bytes(),
+    );
+    hex::encode(digest)
+}
+
+/// 对比已同步公投的存储哈希和上游最新内容，变化时提示需要更新提案；开启 ALLOW_UPDATES 时直接推送更新
+async fn check_for_update(
+    client: &Client,
+    limiter: &RateLimiter,
+    audit: Audit<'_>,
+    cfg: &Config,
+    keypair: &Signer,
+    r: &SubSquareReferendum,
+) -> SyncResult<()> {
+    let Some(stored) = audit.db.get_referendum(r.referendum_index).await.map_err(SyncError::Db)? else {
+        return Ok(());
+    };
+    // 该列上线前同步的记录没有哈希，无从比较
+    let Some(stored_hash) = stored.content_hash else {
+        return Ok(());
+    };
+    let current = content_hash(&build_title(r, cfg), &build_content(r, cfg));
+    if current == stored_hash {
+        return Ok(());
+    }
+    if !cfg.allow_updates {
+        warn!(
+            "📝 公投 #{} 的上游标题/内容已变化（已发布标题：{}），需要更新 OpenSquare 提案",
+            r.referendum_index,
+            stored.title.unwrap_or_default()
+        );
+        return Ok(());
+    }
+    info!("📝 公投 #{} 的上游标题/内容已变化，推送提案更新", r.referendum_index);
+    for (space, cid) in update_proposal(client, limiter, audit.db, cfg, keypair, r).await? {
+        audit.log(SyncEventKind::Updated, Some(r.referendum_index), format!("空间 {}，CID {}", space, cid)).await;
+    }
+    Ok(())
+}
+
+/// 按上游最新标题和内容更新公投在各空间已发布的提案，返回（空间, 新 CID）
+///
+/// 依次签名并推送到每个保存了 CID 的空间，成功后保存新 CID，全部空间成功后才刷新本地的标题和内容哈希，
+/// 因此部分空间失败时下一轮会再次检测到变化并重试；没有保存 CID 的公投（该功能上线前发布）只提示
+pub async fn update_proposal(
+    client: &Client,
+    limiter: &RateLimiter,
+    db: &Db,
+    cfg: &Config,
+    keypair: &Signer,
+    r: &SubSquareReferendum,
+) -> SyncResult<Vec<(String, String)>> {
+    let cids = db.published_cids(r.referendum_index).await.map_err(SyncError::Db)?;
+    if cids.is_empty() {
+        warn!("📝 公投 #{} 没有保存提案 CID，无法自动更新，需要手动更新 OpenSquare 提案", r.referendum_index);
+        return Ok(Vec::new());
+    }
+    let mut updated = Vec::new();
+    for (space, cid) in cids {
+        let data = build_update(r, cfg, &space, &cid);
+        let new_cid = send_update(client, limiter, cfg, keypair, data).await?;
+        db.record_space_publish(r.referendum_index, &space, Some(&new_cid))
+            .await
+            .map_err(SyncError::Db)?;
+        info!("✅ 已更新公投 #{} 在空间 {} 的提案：{} → {}", r.referendum_index, space, cid, new_cid);
+        updated.push((space, new_cid));
+    }
+    let title = build_title(r, cfg);
+    let hash = content_hash(&title, &build_content(r, cfg));
+    db.update_content(r.referendum_index, &title, &hash).await.map_err(SyncError::Db)?;
+    Ok(updated)
+}
+
+/// 由公投最新的标题和内容构造对 `cid` 提案的更新载荷
+fn build_update(r: &SubSquareReferendum, cfg: &Config, space: &str, cid: &str) -> ProposalUpdateData {
+    ProposalUpdateData {
+        space: space.to_string(),
+        proposal_cid: cid.to_string(),
+        title: build_title(r, cfg),
+        content: build_content(r, cfg),
+        content_type: cfg.content_type.as_str().into(),
+        version: cfg.proposal_version.clone(),
+        timestamp: Utc::now().timestamp() as u64,
+    }
+}
+
+/// 签名并推送提案更新，返回 OpenSquare 响应中的新 CID，响应中没有时沿用原 CID
+async fn send_update(
+    client: &Client,
+    limiter: &RateLimiter,
+    cfg: &Config,
+    keypair: &Signer,
+    data: ProposalUpdateData,
+) -> SyncResult<String> {
+    let url = format!("{}/api/{}/proposals/{}/update", cfg.opensquare_base_url, data.space, data.proposal_cid);
+    let original = data.proposal_cid.clone();
+    let request = sign_update(keypair, data, cfg.network.ss58_format()).map_err(SyncError::Signing)?;
+    let request_body = canonical_value(&request).map_err(SyncError::Signing)?;
+    let res = limiter
+        .send(client, client.post(&url).json(&request_body))
+        .await
+        .map_err(|e| SyncError::upstream(e, SyncError::OpenSquare))?;
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+    if !status.is_success() {
+        error!("❌ 更新提案 {} 失败（空间 {}）：{} - {}", original, request.data.space, status, body);
+        return Err(SyncError::OpenSquareRejected { status, body: truncate_body(&body) });
+    }
+    Ok(proposal_cid(&body).unwrap_or(original))
+}
+
+/// 单轮同步的统计结果
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncReport {
+    /// 从 SubSquare 拉取的公投数
+    pub fetched: usize,
+    /// 本地已同步而跳过的数量
+    pub skipped_existing: usize,
+    /// 因状态等过滤条件跳过的数量
+    pub skipped_filtered: usize,
+    /// 发布成功的数量
+    pub published: usize,
+    /// 发布失败的数量
+    pub failed: usize,
+    /// 待发布但 SubSquare 尚无标题的数量（含因 DEFER_UNTITLED 推迟的）
+    pub untitled: usize,
+    /// 因不在 PUBLISH_WINDOW 时段内或熔断而推迟到下一轮发布的数量
+    pub deferred: usize,
+    /// 本轮的 run id，与日志前缀及 sync_events 中的 run_id 一致
+    pub run_id: Uuid,
+    /// 各阶段的累计耗时
+    pub timings: PhaseTimings,
+}
+
+/// 发布熔断器：连续失败达到阈值后打开，本轮不再发布；任一成功清零，阈值为 0 时永不打开
+struct CircuitBreaker {
+    threshold: usize,
+    consecutive_failures: AtomicUsize,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: usize) -> Self {
+        CircuitBreaker { threshold, consecutive_failures: AtomicUsize::new(0) }
+    }
+
+    fn is_open(&self) -> bool {
+        self.threshold > 0 && self.consecutive_failures.load(Ordering::SeqCst) >= self.threshold
+    }
+
+    /// 记录一次发布结果，恰好在这次失败后打开时返回 true，便于只打印一次熔断日志
+    fn record(&self, success: bool) -> bool {
+        if success {
+            self.consecutive_failures.store(0, Ordering::SeqCst);
+            return false;
+        }
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        self.threshold > 0 && failures == self.threshold
+    }
+}
+
+/// 一轮同步中计时的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// 从 SubSquare / OpenSquare 拉取公投和已有提案
+    Fetch,
+    /// 获取快照区块高度
+    Snapshot,
+    /// 构造提案并签名
+    Sign,
+    /// 向 OpenSquare 发送提案
+    Post,
+    /// 数据库读写
+    Db,
+}
+
+/// 一轮同步各阶段的累计耗时；并发发布时为各条公投耗时之和，可能超过整轮的实际耗时
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PhaseTimings {
+    pub fetch: Duration,
+    pub snapshot: Duration,
+    pub sign: Duration,
+    pub post: Duration,
+    pub db: Duration,
+}
+
+impl fmt::Display for PhaseTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "拉取 {:?}，快照 {:?}，签名 {:?}，发布 {:?}，写库 {:?}",
+            self.fetch, self.snapshot, self.sign, self.post, self.db
+        )
+    }
+}
+
+/// 累加各阶段耗时（微秒），可在并发发布的任务间共享
+#[derive(Default)]
+struct PhaseTimer([AtomicU64; 5]);
+
+impl PhaseTimer {
+    /// 把从 `started` 到现在的耗时计入 `phase`
+    fn record(&self, phase: Phase, started: Instant) {
+        self.0[phase as usize].fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn timings(&self) -> PhaseTimings {
+        let get = |phase: Phase| Duration::from_micros(self.0[phase as usize].load(Ordering::Relaxed));
+        PhaseTimings {
+            fetch: get(Phase::Fetch),
+            snapshot: get(Phase::Snapshot),
+            sign: get(Phase::Sign),
+            post: get(Phase::Post),
+            db: get(Phase::Db),
+        }
+    }
+}
+
+/// 一轮同步的审计日志，写入 sync_events 并带上本轮的 run id
+///
+/// 写入失败只打日志，不影响同步本身
+#[derive(Clone, Copy)]
+struct Audit<'a> {
+    db: &'a Db,
+    run_id: Uuid,
+}
+
+impl Audit<'_> {
+    async fn log(&self, kind: SyncEventKind, index: Option<u32>, detail: impl AsRef<str>) {
+        if let Err(e) = self.db.log_event(self.run_id, kind, index, detail.as_ref()).await {
+            warn!("⚠️ 写入审计事件 {}（公投 {:?}）失败：{:?}", kind.as_str(), index, e);
+        }
+    }
+}
+
+/// 汇总单条公投的发布结果，并同步更新 failed_publishes 中的记录
+///
+/// 失败记录写库出错只打日志，不影响本轮其余公投
+async fn tally_outcome(db: &Db, audit: Audit<'_>, report: &mut SyncReport, index: u32, outcome: SyncResult<()>) {
+    let e = match outcome {
+        Ok(()) => {
+            report.published += 1;
+            if let Err(e) = db.clear_publish_failure(index).await {
+                warn!("⚠️ 清除公投 #{} 的失败记录出错：{:?}", index, e);
+            }
+            return;
+        }
+        Err(e) => e,
+    };
+    // OpenSquare 拒绝的情况在发布时已记录日志
+    if !matches!(e, SyncError::OpenSquareRejected { .. }) {
+        error!("❌ 处理公投 #{} 出错，跳过：{}", index, e);
+    }
+    report.failed += 1;
+    audit.log(SyncEventKind::Failed, Some(index), e.to_string()).await;
+    if let Err(e) = db.record_publish_failure(index, &e.to_string()).await {
+        warn!("⚠️ 记录公投 #{} 的发布失败出错：{:?}", index, e);
+    }
+}
+
+/// 公投提交时间早于 `max_age` 时返回其已提交时长；未设置上限或取不到提交时间时返回 None
+fn too_old(
+    r: &SubSquareReferendum,
+    max_age: Option<std::time::Duration>,
+    now: DateTime<Utc>,
+) -> Option<ChronoDuration> {
+    let max_age = ChronoDuration::from_std(max_age?).ok()?;
+    let age = now - r.submitted_at()?;
+    (age > max_age).then_some(age)
+}
 
 /// 核心同步流程：拉取、去重、签名并推送提案
-pub async fn run_sync(client: &Client, db: &Db, cfg: &Config) -> Result<()> {
+///
+/// 每轮生成一个 run id，本轮的日志（经 `run_id::log_prefix`）、sync_events 和返回的报告都带上它
+pub async fn run_sync(
+    client: &Client,
+    limiter: &RateLimiter,
+    heights: &HeightCache,
+    db: &Db,
+    cfg: &Config,
+) -> SyncResult<SyncReport> {
+    let run_id = Uuid::new_v4();
+    run_id::scope(run_id, sync_round(client, limiter, heights, db, cfg, run_id)).await
+}
+
+async fn sync_round(
+    client: &Client,
+    limiter: &RateLimiter,
+    heights: &HeightCache,
+    db: &Db,
+    cfg: &Config,
+    run_id: Uuid,
+) -> SyncResult<SyncReport> {
+    let mut report = SyncReport { run_id, ..Default::default() };
+    let audit = Audit { db, run_id };
+    let timer = &PhaseTimer::default();
+    let round_started = Instant::now();
+    info!("🆔 本轮同步 run id：{}", run_id);
+
     // 1. 初始化 DB
-    db.init_schema().await?;
+    let started = Instant::now();
+    db.init_schema().await.map_err(SyncError::Db)?;
+
+    // 2. 补写上一轮发布成功但写库失败的公投
+    let reconciled = db
+        .reconcile_pending_inserts(cfg.db_insert_retries, cfg.db_insert_backoff)
+        .await;
+    timer.record(Phase::Db, started);
+    if reconciled > 0 {
+        info!("🗄 已补写 {} 条待插入公投", reconciled);
+    }
 
-    // 2. 打印已同步列表
-    let existing = db.get_existing_indices().await?;
-    info!("📚 当前已同步公投编号（{} 条）：{:?}", existing.len(), existing);
+    // 仍在待补写队列中的也视为已同步，避免重复发布
+    let mut known: HashSet<u32> = db.pending_inserts().into_iter().collect();
+    if cfg.dedup_against_opensquare {
+        // 只有所有目标空间都已有的编号才算已发布，只发布到部分空间的留给本轮补发
+        let started = Instant::now();
+        let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type).map_err(SyncError::Signing)?;
+        let address = signer_address(&keypair, cfg.network.ss58_format()).map_err(SyncError::Signing)?;
+        let mut remote: Option<HashSet<u32>> = None;
+        for space in &cfg.open_square_spaces {
+            let indices: HashSet<u32> = fetch_opensquare_indices(client, limiter, cfg, space, &address)
+                .await?
+                .into_iter()
+                .collect();
+            info!("🌐 OpenSquare 空间 {} 中已有 {} 条本服务发布的提案", space, indices.len());
+            remote = Some(match remote {
+                Some(prev) => prev.intersection(&indices).copied().collect(),
+                None => indices,
+            });
+        }
+        known.extend(remote.unwrap_or_default());
+        timer.record(Phase::Fetch, started);
+    }
+    let started = Instant::now();
+    info!("📚 本地已同步 {} 条公投，另有 {} 条待补写或已在 OpenSquare 上的编号参与去重", db.count_synced().await.map_err(SyncError::Db)?, known.len());
+    debug!("📚 待补写或已在 OpenSquare 上的公投编号：{:?}", known);
+    if let Some(high) = db.max_synced_index().await.map_err(SyncError::Db)? {
+        info!("📈 已同步高水位：#{}", high);
+        // 待补写队列与 OpenSquare 上已有的编号都已计入 known，不算缺口
+        let gaps: Vec<u32> = db
+            .find_gaps()
+            .await
+            .map_err(SyncError::Db)?
+            .into_iter()
+            .filter(|i| !cfg.is_ignored(*i) && !known.contains(i))
+            .collect();
+        if !gaps.is_empty() {
+            warn!("🕳 高水位以下有 {} 个未同步的编号，可能是发布失败，需要补发：{:?}", gaps.len(), gaps);
+        }
+    }
+    match db.last_sync_time().await.map_err(SyncError::Db)? {
+        Some(t) => info!("🕒 最近一次同步写入时间：{}", t.format("%Y-%m-%d %H:%M:%S UTC")),
+        None => info!("🕒 暂无同步时间记录"),
+    }
 
 
-     // 3. 拉取并筛选 Deciding 状态的公投
-     let referenda: Vec<SubSquareReferendum> = fetch_referenda(client, cfg.page_size).await?;
+     // 3. 拉取并筛选 SYNC_STATES 中状态的公投
+     //    有游标时只拉取回看窗口内的公投，窗口内较早的公投（如后补标题的）仍会被重新检查
+     let cursor = db.get_cursor().await.map_err(SyncError::Db)?;
+     timer.record(Phase::Db, started);
+     let started = Instant::now();
+     let referenda: Vec<SubSquareReferendum> = match cfg.fetch_mode {
+         FetchMode::FixedPage => {
+             let floor = lookback_floor(cursor, cfg.cursor_lookback);
+             if let (Some(cursor), Some(floor)) = (cursor, floor) {
+                 info!("🧭 拉取游标 #{}，回看至 #{} 之后", cursor, floor);
+             }
+             fetch_referenda_since(client, limiter, &cfg.subsquare_base_url, cfg.page_size, floor).await?
+         }
+         FetchMode::Incremental => {
+             fetch_referenda_incremental(client, limiter, db, &cfg.subsquare_base_url, cfg.page_size, &known).await?
+         }
+     };
+     timer.record(Phase::Fetch, started);
      info!("🔍 拉取 {} 条公投数据", referenda.len());
+     report.fetched = referenda.len();
+     let fetched_indices: Vec<u32> = referenda.iter().map(|r| r.referendum_index).collect();
+     audit.log(SyncEventKind::Fetched, None, format!("拉取 {} 条公投", referenda.len())).await;
  
-     let deciding_only: Vec<SubSquareReferendum> = referenda
-         .into_iter()
-         .filter(|r| r.state.status == ReferendumStatus::Deciding)
-         .collect();
-         info!("🔍 一共有 {} 条 Deciding 公投数据", deciding_only.len());
+     let mut in_states = Vec::new();
+     for r in referenda {
+         if cfg.sync_states.contains(&r.state.status) {
+             in_states.push(r);
+         } else {
+             let reason = format!("状态 {} 不在 SYNC_STATES 中", r.state.status.as_str());
+             audit.log(SyncEventKind::Skipped, Some(r.referendum_index), reason).await;
+         }
+     }
+         info!("🔍 一共有 {} 条处于 SYNC_STATES 状态的公投数据", in_states.len());
+     report.skipped_filtered = report.fetched - in_states.len();
     
   
     // 4. 签名密钥对
-    let keypair = sr25519::Pair::from_string(&cfg.mnemonic, None)?;
+    let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type).map_err(SyncError::Signing)?;
 
     // 5. 获取快照高度
-    let snapshot = get_latest_block_height(client, cfg.snapshot_offset).await?;
-    info!("⛏ 快照块高度：{}", snapshot);
+    let started = Instant::now();
+    let snapshots = fetch_snapshot_heights(client, limiter, heights, cfg).await?;
+    timer.record(Phase::Snapshot, started);
 
-    // 6. 逐条处理
-    for r in deciding_only {
+    // 6. 去重，筛出待发布的公投：只按本轮候选编号查库，不载入全部已同步编号
+    let candidates: Vec<u32> = in_states.iter().map(|r| r.referendum_index).collect();
+    let started = Instant::now();
+    let unsynced: HashSet<u32> = db
+        .filter_unsynced(&candidates)
+        .await
+        .map_err(SyncError::Db)?
+        .into_iter()
+        .collect();
+    timer.record(Phase::Db, started);
+    let mut to_publish = Vec::new();
+    let now = Utc::now();
+    for r in in_states {
         info!("➡️ 开始处理公投 #{}", r.referendum_index);
-        if existing.contains(&(r.referendum_index as i32)) {
+        if cfg.is_ignored(r.referendum_index) {
+            info!("🚫 公投 #{} 在 IGNORE_INDICES 中，跳过", r.referendum_index);
+            audit.log(SyncEventKind::Skipped, Some(r.referendum_index), "在 IGNORE_INDICES 中").await;
+            report.skipped_filtered += 1;
+            continue;
+        }
+        if let Some(age) = too_old(&r, cfg.max_referendum_age, now) {
+            info!(
+                "📅 公投 #{} 已提交 {} 天，超过 MAX_REFERENDUM_AGE_DAYS，跳过",
+                r.referendum_index, age.num_days()
+            );
+            let reason = format!("已提交 {} 天，超过 MAX_REFERENDUM_AGE_DAYS", age.num_days());
+            audit.log(SyncEventKind::Skipped, Some(r.referendum_index), reason).await;
+            report.skipped_filtered += 1;
+            continue;
+        }
+        if cfg.require_decision_deposit && !r.has_decision_deposit() {
+            info!("🪙 公投 #{} 尚未缴纳决策押金，REQUIRE_DECISION_DEPOSIT 已开启，跳过", r.referendum_index);
+            audit.log(SyncEventKind::Skipped, Some(r.referendum_index), "未缴纳决策押金").await;
+            report.skipped_filtered += 1;
+            continue;
+        }
+        if !unsynced.contains(&r.referendum_index) || known.contains(&r.referendum_index) {
+            if cfg.update_on_title_change || cfg.allow_updates {
+                if let Err(e) = check_for_update(client, limiter, audit, cfg, &keypair, &r).await {
+                    warn!("⚠️ 检查公投 #{} 的上游变化失败：{:?}", r.referendum_index, e);
+                }
+            }
             info!("↩️ 公投 #{} 已存在，跳过", r.referendum_index);
+            audit.log(SyncEventKind::Skipped, Some(r.referendum_index), "已同步").await;
+            report.skipped_existing += 1;
             continue;
         }
+        if r.title.as_deref().is_none_or(|t| t.trim().is_empty()) {
+            report.untitled += 1;
+            if cfg.defer_untitled {
+                info!("⏳ 公投 #{} 暂无标题，推迟到之后的同步再发布", r.referendum_index);
+                audit.log(SyncEventKind::Deferred, Some(r.referendum_index), "暂无标题（DEFER_UNTITLED）").await;
+                continue;
+            }
+            warn!("⚠️ 公投 #{} 暂无标题，将以空标题发布", r.referendum_index);
+        }
+        to_publish.push(r);
+    }
 
-         // 6.1 拼时间戳 ——— 时间字段：全部用真实当前时间 ———
+    // 不在发布时段内时只完成拉取和去重，新公投留到下一次时段内的同步再发布
+    if let Some(window) = &cfg.publish_window {
         let now = Utc::now();
-        let start_date = now.timestamp_millis() as u64;             // 毫秒
-        let end_date   = (now + ChronoDuration::days(30))
-                                  .timestamp_millis() as u64;    // 毫秒，30 天后
+        let local_time = match cfg.timezone {
+            Some(tz) => now.with_timezone(&tz).time(),
+            None => now.time(),
+        };
+        if !window.contains(local_time) {
+            for r in &to_publish {
+                info!("🌙 当前不在发布时段 {}，推迟发布公投 #{}", window, r.referendum_index);
+                let reason = format!("不在发布时段 {} 内", window);
+                audit.log(SyncEventKind::Deferred, Some(r.referendum_index), reason).await;
+            }
+            report.deferred = to_publish.len();
+            report.timings = timer.timings();
+            log_timings(&report.timings, round_started);
+            return Ok(report);
+        }
+    }
 
-        // 6.2 拼标题和内容
-        let title_text = r.title.clone().unwrap_or_default();
-        let display_title = Track::format_title(r.track_id, r.referendum_index, &title_text);
- 
-        let content = format!(
-            "https://polkadot.subsquare.io/referenda/{}\n\n{}",
-            r.referendum_index,
-            r.content_summary
-                .as_ref().and_then(|c| c.summary.clone())
-                .or_else(|| r.content.clone())
-                .unwrap_or_default()
+    // SubSquare 按最新优先返回，发布前按 PUBLISH_ORDER 排序，默认编号小的先发布
+    sort_for_publish(&mut to_publish, cfg.publish_order);
+
+    // 7. 按并发上限发布，默认并发为 1 即逐条串行；
+    //    单条出错只记为失败并继续处理其余公投，不中止整轮
+    let keypair = &keypair;
+    let snapshots = &snapshots;
+    //    签名被拒（401/403）累计达到阈值后不再发布剩余公投，整轮中止
+    let auth_rejections = &AtomicUsize::new(0);
+    //    连续失败达到 CIRCUIT_BREAKER_THRESHOLD 后熔断，剩余公投留到下一轮，避免持续请求已宕机的上游
+    let breaker = &CircuitBreaker::new(cfg.circuit_breaker_threshold);
+    let outcomes = stream::iter(to_publish)
+        .map(|r| async move {
+            let index = r.referendum_index;
+            if auth_rejections.load(Ordering::SeqCst) >= cfg.auth_rejection_threshold || breaker.is_open() {
+                return (index, None);
+            }
+            let outcome = publish_referendum(client, limiter, db, audit, timer, cfg, keypair, snapshots, r).await;
+            if matches!(&outcome, Err(e) if e.is_auth_rejection()) {
+                auth_rejections.fetch_add(1, Ordering::SeqCst);
+            }
+            if breaker.record(outcome.is_ok()) {
+                error!(
+                    "🔌 熔断：连续 {} 次发布失败，本轮不再发布剩余公投，留到下一轮同步",
+                    cfg.circuit_breaker_threshold
+                );
+            }
+            (index, Some(outcome))
+        })
+        .buffer_unordered(cfg.publish_concurrency)
+        .collect::<Vec<_>>()
+        .await;
+    let mut last_rejection = None;
+    for (index, outcome) in outcomes {
+        let Some(outcome) = outcome else {
+            if auth_rejections.load(Ordering::SeqCst) >= cfg.auth_rejection_threshold {
+                info!("⏭ 签名已被拒绝，本轮不再发布公投 #{}", index);
+                audit.log(SyncEventKind::Skipped, Some(index), "签名已被拒绝，本轮中止发布").await;
+            } else {
+                debug!("⏭ 已熔断，公投 #{} 留到下一轮发布", index);
+                report.deferred += 1;
+                audit.log(SyncEventKind::Deferred, Some(index), "连续发布失败熔断，留到下一轮").await;
+            }
+            continue;
+        };
+        if let Err(e @ SyncError::OpenSquareRejected { status, body }) = &outcome {
+            if e.is_auth_rejection() {
+                last_rejection = Some((index, *status, body.clone()));
+            }
+        }
+        tally_outcome(db, audit, &mut report, index, outcome).await;
+    }
+
+    let rejections = auth_rejections.load(Ordering::SeqCst);
+    if let Some((index, status, body)) = last_rejection.filter(|_| rejections >= cfg.auth_rejection_threshold) {
+        error!("🚨 OpenSquare 本轮 {} 次以 {} 拒绝签名，中止同步，请检查签名密钥", rejections, status);
+        notify(client, cfg, NotifyEvent {
+            event: EventKind::SignerRejected,
+            index,
+            title: "",
+            status: status.to_string(),
+            subsquare_url: subsquare_referendum_url(cfg.network, index),
+            opensquare_url: None,
+        }).await;
+        return Err(SyncError::OpenSquareRejected { status, body });
+    }
+
+    let clean_round = report.failed == 0 && report.deferred == 0;
+    if let Some(next) = advance_cursor(cursor, &fetched_indices, clean_round).filter(|&n| Some(n) != cursor) {
+        let started = Instant::now();
+        db.set_cursor(next).await.map_err(SyncError::Db)?;
+        timer.record(Phase::Db, started);
+        info!("🧭 拉取游标推进至 #{}", next);
+    }
+
+    report.timings = timer.timings();
+    log_timings(&report.timings, round_started);
+    Ok(report)
+}
+
+/// 按公投编号排列待发布的公投
+fn sort_for_publish(referenda: &mut [SubSquareReferendum], order: PublishOrder) {
+    match order {
+        PublishOrder::Asc => referenda.sort_by_key(|r| r.referendum_index),
+        PublishOrder::Desc => referenda.sort_by_key(|r| std::cmp::Reverse(r.referendum_index)),
+    }
+}
+
+/// 打印本轮各阶段耗时，便于判断瓶颈在上游、签名还是数据库
+fn log_timings(timings: &PhaseTimings, round_started: Instant) {
+    info!("⏱ 本轮耗时 {:?}：{}", round_started.elapsed(), timings);
+}
+
+/// `--retry-failed`：只重试 failed_publishes 中记录的公投
+///
+/// 尝试次数达到 RETRY_MAX_ATTEMPTS 的不再重试；每条记录在上次尝试后需等待
+/// RETRY_BACKOFF_SECS × 2^(次数-1) 才会再次尝试
+pub async fn retry_failed(
+    client: &Client,
+    limiter: &RateLimiter,
+    heights: &HeightCache,
+    db: &Db,
+    cfg: &Config,
+) -> SyncResult<SyncReport> {
+    let run_id = Uuid::new_v4();
+    run_id::scope(run_id, retry_round(client, limiter, heights, db, cfg, run_id)).await
+}
+
+async fn retry_round(
+    client: &Client,
+    limiter: &RateLimiter,
+    heights: &HeightCache,
+    db: &Db,
+    cfg: &Config,
+    run_id: Uuid,
+) -> SyncResult<SyncReport> {
+    let mut report = SyncReport { run_id, ..Default::default() };
+    let audit = Audit { db, run_id };
+    let timer = &PhaseTimer::default();
+    info!("🆔 本次重试 run id：{}", run_id);
+    db.init_schema().await.map_err(SyncError::Db)?;
+
+    let now = Utc::now();
+    let mut due = Vec::new();
+    for f in db.list_failed_publishes().await.map_err(SyncError::Db)? {
+        if f.attempt_count >= cfg.retry_max_attempts {
+            warn!(
+                "🛑 公投 #{} 已失败 {} 次，达到重试上限，需人工处理：{}",
+                f.referendum_index, f.attempt_count, f.last_error
+            );
+            report.skipped_filtered += 1;
+            continue;
+        }
+        let wait = cfg.retry_backoff * 2u32.saturating_pow(f.attempt_count.saturating_sub(1));
+        let next_attempt = f.last_attempt_at
+            + ChronoDuration::from_std(wait).map_err(|e| SyncError::Config(format!("RETRY_BACKOFF_SECS 过大：{}", e)))?;
+        if now < next_attempt {
+            info!(
+                "⏳ 公投 #{} 仍在退避期内，{} 之后再重试",
+                f.referendum_index, next_attempt.format("%Y-%m-%d %H:%M:%S UTC")
+            );
+            report.skipped_filtered += 1;
+            continue;
+        }
+        due.push(f.referendum_index);
+    }
+    info!("🔁 共有 {} 条发布失败的公投待重试", due.len());
+    if due.is_empty() {
+        return Ok(report);
+    }
+
+    let unsynced = db.filter_unsynced(&due).await.map_err(SyncError::Db)?;
+    let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type).map_err(SyncError::Signing)?;
+    let started = Instant::now();
+    let snapshots = fetch_snapshot_heights(client, limiter, heights, cfg).await?;
+    timer.record(Phase::Snapshot, started);
+
+    for index in due {
+        // 已由其他途径同步、被忽略或状态已不在 SYNC_STATES 中的公投不再重试，直接清除记录
+        if !unsynced.contains(&index) || cfg.is_ignored(index) {
+            info!("↩️ 公投 #{} 已同步或在 IGNORE_INDICES 中，清除失败记录", index);
+            db.clear_publish_failure(index).await.map_err(SyncError::Db)?;
+            report.skipped_existing += 1;
+            continue;
+        }
+        let started = Instant::now();
+        let fetched = fetch_referendum(client, limiter, &cfg.subsquare_base_url, index).await;
+        timer.record(Phase::Fetch, started);
+        let r = match fetched {
+            Ok(r) => r,
+            Err(e) => {
+                tally_outcome(db, audit, &mut report, index, Err(e)).await;
+                continue;
+            }
+        };
+        report.fetched += 1;
+        if !cfg.sync_states.contains(&r.state.status) {
+            info!("↩️ 公投 #{} 已是 {} 状态，不在 SYNC_STATES 中，清除失败记录", index, r.state.status.as_str());
+            db.clear_publish_failure(index).await.map_err(SyncError::Db)?;
+            report.skipped_filtered += 1;
+            continue;
+        }
+        let outcome = publish_referendum(client, limiter, db, audit, timer, cfg, &keypair, &snapshots, r).await;
+        tally_outcome(db, audit, &mut report, index, outcome).await;
+    }
+
+    report.timings = timer.timings();
+    Ok(report)
+}
+
+/// 未在 TRACK_CONFIG 中覆盖时的投票天数
+const DEFAULT_VOTE_DURATION_DAYS: u32 = 30;
+
+/// 提案开始时间（毫秒时间戳）：未配置时区时为当前时间，否则为该时区当天零点
+///
+/// 零点落在夏令时跳变中不存在时回退为当前时间
+fn start_date_millis(now: DateTime<Utc>, timezone: Option<Tz>) -> u64 {
+    let Some(tz) = timezone else {
+        return now.timestamp_millis() as u64;
+    };
+    let midnight = now.with_timezone(&tz).date_naive().and_time(NaiveTime::MIN);
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .map_or(now.timestamp_millis(), |t| t.timestamp_millis()) as u64
+}
+
+/// 由公投构造待签名的 OpenSquare 提案，时间字段取当前时间
+pub fn build_proposal(
+    r: &SubSquareReferendum,
+    cfg: &Config,
+    space: &str,
+    snapshot_heights: HashMap<String, u64>,
+) -> ProposalData {
+    // TRACK_CONFIG 中该 track 的覆盖，未覆盖的项沿用全局设置
+    let track = cfg.track_overrides.get(&r.track_id);
+
+    // 6.1 拼时间戳 ——— 开始时间取当前时间，配置 TIMEZONE 时对齐到当地零点 ———
+    let now = Utc::now();
+    let vote_days = track.and_then(|t| t.vote_duration_days).unwrap_or(DEFAULT_VOTE_DURATION_DAYS);
+    let start_date = start_date_millis(now, cfg.timezone);      // 毫秒
+    let end_date   = start_date
+                     + ChronoDuration::days(vote_days.into()).num_milliseconds() as u64;  // 毫秒，默认 30 天后
+
+    // 6.2 拼标题和内容
+    let display_title = build_title(r, cfg);
+    let content = build_content(r, cfg);
+
+    // 6.3 构造 networksConfig
+    let networks_config = NetworksConfig {
+        symbol: cfg.token_symbol.clone(),
+        decimals: cfg.token_decimals,
+        networks: vec![
+            NetworkDetail {
+                network: cfg.network.name().into(),
+                ss58_format: cfg.network.ss58_format(),
+                assets: vec![
+                    AssetConfig {
+                        symbol: cfg.token_symbol.clone(),
+                        decimals: cfg.token_decimals,
+                        voting_threshold: track
+                            .and_then(|t| t.threshold.clone())
+                            .or_else(|| cfg.voting_threshold.clone()),
+                        multiplier: cfg.vote_multiplier,
+                    }
+                ],
+            },
+        ],
+        accessibility: cfg.accessibility.as_str().into(),
+        whitelist: (cfg.accessibility == Accessibility::Whitelist).then(|| vec![
+            "12mP4sjCfKbDyMRAEyLpkeHeoYtS5USY4x34n9NMwQrcEyoh".to_string(),
+            "167rjWHghVwBJ52mz8sNkqr5bKu5vpchbc9CBoieBhVX714h".to_string(),
+            "16ap6fdqS2rqFsyYah35hX1FH6rPNWtLqqXZDQC9x6GW141C".to_string(),
+            "14pa3BAYZLPvZfRDjWEfZXZWBVU45E67HUQEUxNCrdXGoata".to_string(),
+            "14qwyVVvW4Tuhq4Fvt2AHZqhbCtGfVb8HUY2xM2PKrzKsmZT".to_string(),
+        ]),
+        strategies: cfg.strategies.clone(),
+        version: cfg.networks_config_version.clone(),
+    };
+
+    // 6.4 构造 ProposalData
+    ProposalData {
+        space:            space.to_string(),
+        title:            display_title,
+        content,
+        content_type:     cfg.content_type.as_str().into(),
+        choice_type:      "single".into(),
+        choices:          track.and_then(|t| t.choices.clone())
+                              .unwrap_or_else(|| vec!["Aye".into(), "Nay".into(), "Abstain".into()]),
+        start_date,
+        end_date,
+        snapshot_heights,
+        real_proposer:    r.proposer_address().map(Value::String),
+        proposer_network: cfg.network.name().into(),
+        version:          cfg.proposal_version.clone(),
+        timestamp:        now.timestamp() as u64,
+        networks_config,
+        discussion:       discussion_link(r.referendum_index, cfg),
+        nonce:            cfg.include_nonce.then(|| uuid::Uuid::new_v4().to_string()),
+    }
+}
+
+/// 重新签名前刷新提案：开始时间取当前时间（按 TIMEZONE 对齐），投票时长保持不变，
+/// 同时更新时间戳、快照高度，原有 nonce 时换一个新的
+pub fn refresh_proposal(data: &mut ProposalData, cfg: &Config, snapshot_heights: HashMap<String, u64>) {
+    let now = Utc::now();
+    let duration = data.end_date.saturating_sub(data.start_date);
+    data.start_date = start_date_millis(now, cfg.timezone);
+    data.end_date = data.start_date + duration;
+    data.timestamp = now.timestamp() as u64;
+    data.snapshot_heights = snapshot_heights;
+    if data.nonce.is_some() {
+        data.nonce = Some(uuid::Uuid::new_v4().to_string());
+    }
+}
+
+/// 处理单条公投：依次发布到每个配置的空间，全部成功后才写库
+///
+/// 已在之前的轮次中发布成功的空间会跳过，部分空间失败时下一轮只重试失败的空间
+#[allow(clippy::too_many_arguments)]
+async fn publish_referendum(
+    client: &Client,
+    limiter: &RateLimiter,
+    db: &Db,
+    audit: Audit<'_>,
+    timer: &PhaseTimer,
+    cfg: &Config,
+    keypair: &Signer,
+    snapshots: &Snapshots,
+    r: SubSquareReferendum,
+) -> SyncResult<()> {
+    let snapshots = &snapshots.for_referendum(&r, cfg)?;
+    let started = Instant::now();
+    let done = db.published_spaces(r.referendum_index).await.map_err(SyncError::Db)?;
+    timer.record(Phase::Db, started);
+    let mut nonce = None;
+    for space in &cfg.open_square_spaces {
+        if done.contains(space) {
+            info!("↩️ 公投 #{} 已发布到空间 {}，跳过", r.referendum_index, space);
+            continue;
+        }
+        let (space_nonce, cid) = publish_to_space(client, limiter, timer, cfg, keypair, snapshots, &r, space).await?;
+        let detail = format!("空间 {}，CID {}", space, cid.as_deref().unwrap_or("未知"));
+        audit.log(SyncEventKind::Published, Some(r.referendum_index), detail).await;
+        let started = Instant::now();
+        if let Err(e) = db.record_space_publish(r.referendum_index, space, cid.as_deref()).await {
+            warn!("⚠️ 记录公投 #{} 已发布到空间 {} 失败：{:?}", r.referendum_index, space, e);
+        }
+        timer.record(Phase::Db, started);
+        nonce = nonce.or(space_nonce);
+    }
+
+    // 6.5 插入 DB，失败则加入待补写队列，下一轮再补
+    let display_title = build_title(&r, cfg);
+    let record = NewReferendum {
+        referendum_index: r.referendum_index,
+        track_id: r.track_id,
+        content_hash: content_hash(&display_title, &build_content(&r, cfg)),
+        title: display_title,
+        content_summary: r.content_summary.as_ref().and_then(|c| c.summary.clone()),
+        snapshot_height: snapshots.primary,
+        nonce,
+        state: r.state.status.as_str().into(),
+    };
+    let started = Instant::now();
+    let inserted = db
+        .insert_referendum_with_retry(&record, cfg.db_insert_retries, cfg.db_insert_backoff)
+        .await;
+    timer.record(Phase::Db, started);
+    match inserted {
+        Ok(0) => info!("🗄 公投 #{} 已在本地数据库中，无需重复写入", r.referendum_index),
+        Ok(_) => info!("🗄 已插入本地数据库 #{}", r.referendum_index),
+        Err(e) => {
+            error!("❌ 公投 #{} 已发布但写库失败，加入待补写队列：{:?}", r.referendum_index, e);
+            db.queue_pending_insert(record);
+        }
+    }
+
+    Ok(())
+}
+
+/// 构造、签名并发布到单个空间，返回签名载荷中的 nonce 与 OpenSquare 返回的提案 CID
+///
+/// 签名载荷包含空间名，每个空间都需要单独签名
+#[allow(clippy::too_many_arguments)]
+async fn publish_to_space(
+    client: &Client,
+    limiter: &RateLimiter,
+    timer: &PhaseTimer,
+    cfg: &Config,
+    keypair: &Signer,
+    snapshots: &Snapshots,
+    r: &SubSquareReferendum,
+    space: &str,
+) -> SyncResult<(Option<String>, Option<String>)> {
+    // 6.1 构造提案
+    let started = Instant::now();
+    let mut data = build_proposal(r, cfg, space, snapshots.heights.clone());
+    transform::apply(&cfg.transformers, r, &mut data);
+    let key = idempotency_key(r.referendum_index, space, &snapshots.heights);
+    let display_title = data.title.clone();
+    let nonce = data.nonce.clone();
+
+    // 6.2 签名 & 拼装请求：签名与发送都使用键有序的规范化 JSON
+    let request = sign_request(keypair, data, cfg.network.ss58_format()).map_err(SyncError::Signing)?;
+    let request_body = canonical_value(&request).map_err(SyncError::Signing)?;
+    timer.record(Phase::Sign, started);
+
+    // 6.3 日志打印
+    let proposals_url = format!("{}/api/{}/proposals", cfg.opensquare_base_url, space);
+    info!("🔗 请求 URL: {}", proposals_url);
+    info!("🔑 公投 #{}（空间 {}）的幂等键：{}", r.referendum_index, space, key);
+
+    // 6.4 发送
+    let mut req = client.post(&proposals_url).json(&request_body);
+    if let Some(header) = &cfg.idempotency_header {
+        req = req.header(header.as_str(), &key);
+    }
+    let started = Instant::now();
+    let sent = limiter.send(client, req).await;
+    timer.record(Phase::Post, started);
+    let res = match sent
+    {
+        Ok(res) => res,
+        Err(e) => {
+            notify(client, cfg, NotifyEvent {
+                event: EventKind::PublishFailed,
+                index: r.referendum_index,
+                title: &display_title,
+                status: e.to_string(),
+                subsquare_url: subsquare_referendum_url(cfg.network, r.referendum_index),
+                opensquare_url: None,
+            }).await;
+            return Err(SyncError::upstream(e, SyncError::OpenSquare));
+        }
+    };
+    let status = res.status();
+    let body   = res.text().await.unwrap_or_default();
+    if !status.is_success() {
+        error!("❌ 发布失败 #{}（空间 {}）：{} - {}", r.referendum_index, space, status, body);
+        if let Some(dir) = &cfg.dead_letter_dir {
+            match DeadLetters::new(dir, cfg.dead_letter_max_files).write(r.referendum_index, space, Utc::now(), &request_body) {
+                Ok(path) => info!("📮 已保存被拒绝的请求体：{}", path.display()),
+                Err(e) => warn!("⚠️ 保存公投 #{} 被拒绝的请求体失败：{:?}", r.referendum_index, e),
+            }
+        }
+        notify(client, cfg, NotifyEvent {
+            event: EventKind::PublishFailed,
+            index: r.referendum_index,
+            title: &display_title,
+            status: status.to_string(),
+            subsquare_url: subsquare_referendum_url(cfg.network, r.referendum_index),
+            opensquare_url: None,
+        }).await;
+        return Err(SyncError::OpenSquareRejected { status, body: truncate_body(&body) });
+    }
+    info!("✅ 发布成功 #{}（空间 {}）：{}", r.referendum_index, space, status);
+    notify(client, cfg, NotifyEvent {
+        event: EventKind::Published,
+        index: r.referendum_index,
+        title: &display_title,
+        status: status.to_string(),
+        subsquare_url: subsquare_referendum_url(cfg.network, r.referendum_index),
+        opensquare_url: proposal_cid(&body).map(|cid| opensquare_proposal_url(cfg, space, &cid)),
+    }).await;
+
+    Ok((nonce, proposal_cid(&body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SnapshotMode, TrackOverride};
+    use std::time::Duration;
+
+    fn referendum(summary: Option<&str>, content: Option<&str>) -> SubSquareReferendum {
+        serde_json::from_value(serde_json::json!({
+            "referendumIndex": 1,
+            "title": "t",
+            "content": content,
+            "track": 0,
+            "contentSummary": { "summary": summary },
+            "state": { "name": "Deciding" },
+        }))
+        .unwrap()
+    }
+
+    fn pick(summary: Option<&str>, content: Option<&str>, source: ContentSource) -> (String, &'static str) {
+        select_summary(&referendum(summary, content), SummaryPreference::Default, source)
+    }
+
+    #[test]
+    fn summary_then_content_keeps_legacy_order() {
+        let source = ContentSource::SummaryThenContent;
+        assert_eq!(pick(Some("S"), Some("C"), source), ("S".into(), "summary"));
+        assert_eq!(pick(None, Some("C"), source), ("C".into(), "content"));
+        assert_eq!(pick(None, None, source), ("".into(), "none"));
+    }
+
+    #[test]
+    fn content_then_summary_prefers_content() {
+        let source = ContentSource::ContentThenSummary;
+        assert_eq!(pick(Some("S"), Some("C"), source), ("C".into(), "content"));
+        assert_eq!(pick(Some("S"), Some(" "), source), ("S".into(), "summary"));
+        assert_eq!(pick(None, None, source), ("".into(), "none"));
+    }
+
+    #[test]
+    fn content_only_ignores_summary() {
+        let source = ContentSource::ContentOnly;
+        assert_eq!(pick(Some("S"), Some("C"), source), ("C".into(), "content"));
+        assert_eq!(pick(Some("S"), None, source), ("".into(), "none"));
+    }
+
+    #[test]
+    fn summary_only_ignores_content() {
+        let source = ContentSource::SummaryOnly;
+        assert_eq!(pick(Some("S"), Some("C"), source), ("S".into(), "summary"));
+        assert_eq!(pick(None, Some("C"), source), ("".into(), "none"));
+    }
+
+    /// 同时带 AI 摘要、作者摘要和原文的公投，按 SUMMARY_PREFERENCE 选取
+    fn prefer(ai: &str, author: &str, content: &str, preference: SummaryPreference) -> (String, &'static str) {
+        let r: SubSquareReferendum = serde_json::from_value(serde_json::json!({
+            "referendumIndex": 1,
+            "title": "t",
+            "content": content,
+            "track": 0,
+            "contentSummary": { "summary": ai, "authorSummary": author },
+            "state": { "name": "Deciding" },
+        }))
+        .unwrap();
+        select_summary(&r, preference, ContentSource::default())
+    }
+
+    #[test]
+    fn author_preference_falls_back_to_content_then_ai() {
+        let author = SummaryPreference::Author;
+        assert_eq!(prefer("AI", "Author", "Content", author), ("Author".into(), "author_summary"));
+        assert_eq!(prefer("AI", " ", "Content", author), ("Content".into(), "content"));
+        assert_eq!(prefer("AI", "", "", author), ("AI".into(), "summary"));
+        assert_eq!(prefer("", "", "", author), ("".into(), "none"));
+    }
+
+    #[test]
+    fn ai_preference_falls_back_to_content() {
+        let ai = SummaryPreference::Ai;
+        assert_eq!(prefer("AI", "Author", "Content", ai), ("AI".into(), "summary"));
+        assert_eq!(prefer("\n", "Author", "Content", ai), ("Content".into(), "content"));
+        assert_eq!(prefer("", "Author", "", ai), ("".into(), "none"));
+    }
+
+    #[test]
+    fn longest_preference_skips_empty_sources() {
+        let longest = SummaryPreference::Longest;
+        assert_eq!(prefer("AI summary", "Author", "Content", longest), ("AI summary".into(), "summary"));
+        assert_eq!(prefer("AI", "Author summary", "C", longest), ("Author summary".into(), "author_summary"));
+        assert_eq!(prefer("AI", "", "Full content text", longest), ("Full content text".into(), "content"));
+        // 按字符数而不是字节数比较
+        assert_eq!(prefer("摘要摘要", "", "abcde", longest), ("abcde".into(), "content"));
+        assert_eq!(prefer(" ", "", "", longest), ("".into(), "none"));
+    }
+
+    fn fields() -> ContentFields<'static> {
+        ContentFields {
+            url: "https://polkadot.subsquare.io/referenda/42",
+            summary: "Fund the {title} initiative",
+            index: 42,
+            track: "Treasurer",
+            title: "Tooling",
+        }
+    }
+
+    #[test]
+    fn start_date_without_timezone_is_now() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 20, 0, 0).unwrap();
+        assert_eq!(start_date_millis(now, None), now.timestamp_millis() as u64);
+    }
+
+    #[test]
+    fn start_date_aligns_to_local_midnight() {
+        // 上海已是 3 月 11 日 04:00，当地零点为 UTC 3 月 10 日 16:00
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 20, 0, 0).unwrap();
+        let expected = Utc.with_ymd_and_hms(2024, 3, 10, 16, 0, 0).unwrap();
+        assert_eq!(
+            start_date_millis(now, Some(chrono_tz::Asia::Shanghai)),
+            expected.timestamp_millis() as u64
+        );
+    }
+
+    #[test]
+    fn default_content_is_url_then_summary() {
+        assert_eq!(
+            render_content(None, &fields()),
+            "https://polkadot.subsquare.io/referenda/42\n\nFund the {title} initiative"
         );
+    }
+
+    #[test]
+    fn template_substitutes_all_placeholders() {
+        let template = "## [{track}] #{index} {title}\n\n{summary}\n\n[SubSquare]({url}) · vote Aye/Nay";
+        assert_eq!(
+            render_content(Some(template), &fields()),
+            "## [Treasurer] #42 Tooling\n\nFund the {title} initiative\n\n\
+             [SubSquare](https://polkadot.subsquare.io/referenda/42) · vote Aye/Nay"
+        );
+    }
+
+    /// 固定返回同一高度的区块来源，不发出网络请求
+    struct FixedSource(u64);
+
+    #[async_trait::async_trait]
+    impl BlockSource for FixedSource {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        async fn latest_height(&self, _: &Client, _: &RateLimiter) -> anyhow::Result<u64> {
+            Ok(self.0)
+        }
+
+        async fn latest_block_time(&self, _: &Client, _: &RateLimiter) -> anyhow::Result<DateTime<Utc>> {
+            Ok(Utc::now())
+        }
+    }
+
+    #[test]
+    fn clock_skew_thresholds() {
+        let mut cfg = config();
+        assert_eq!(cfg.max_clock_skew, Some(Duration::from_secs(300)));
+        assert_eq!(classify_skew(Duration::from_secs(30), &cfg), ClockSkew::Within);
+        assert_eq!(classify_skew(Duration::from_secs(301), &cfg), ClockSkew::Warn);
+        assert_eq!(classify_skew(Duration::from_secs(86_400), &cfg), ClockSkew::Warn);
+
+        cfg.clock_skew_abort = Some(Duration::from_secs(3_600));
+        assert_eq!(classify_skew(Duration::from_secs(600), &cfg), ClockSkew::Warn);
+        assert_eq!(classify_skew(Duration::from_secs(3_601), &cfg), ClockSkew::Abort);
+
+        cfg.max_clock_skew = None;
+        assert_eq!(classify_skew(Duration::from_secs(600), &cfg), ClockSkew::Within);
+    }
+
+    #[test]
+    fn too_old_only_when_limit_set_and_exceeded() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let submitted = (now - ChronoDuration::days(40)).timestamp_millis();
+        let r: SubSquareReferendum = serde_json::from_value(serde_json::json!({
+            "referendumIndex": 1,
+            "track": 0,
+            "state": { "name": "Deciding" },
+            "indexer": { "blockHeight": 1, "blockTime": submitted },
+        }))
+        .unwrap();
+        let days = |d: u64| Some(Duration::from_secs(d * 24 * 60 * 60));
+
+        assert_eq!(too_old(&r, days(30), now).map(|a| a.num_days()), Some(40));
+        assert!(too_old(&r, days(60), now).is_none());
+        assert!(too_old(&r, None, now).is_none());
+        assert!(too_old(&referendum(None, None), days(30), now).is_none());
+    }
+
+    #[test]
+    fn lookback_floor_and_paging() {
+        assert_eq!(lookback_floor(None, 50), None);
+        assert_eq!(lookback_floor(Some(300), 50), Some(250));
+        assert_eq!(lookback_floor(Some(30), 50), Some(0));
+
+        // 没有游标时只拉第 1 页
+        assert!(is_last_page(&[300, 299], 2, None));
+        // 整页且都在窗口内时继续翻页
+        assert!(!is_last_page(&[300, 299], 2, Some(250)));
+        // 越过下界或不足一页时停止
+        assert!(is_last_page(&[251, 250], 2, Some(250)));
+        assert!(is_last_page(&[260], 2, Some(250)));
+    }
+
+    /// 模拟 SubSquare 的倒序分页：编号 `newest..=1`，每页 `page_size` 条，并记录请求过的页码
+    fn paged(newest: u32, page_size: usize, page: usize, requested: &std::cell::RefCell<Vec<usize>>) -> Vec<SubSquareReferendum> {
+        requested.borrow_mut().push(page);
+        let all: Vec<u32> = (1..=newest).rev().collect();
+        all.chunks(page_size)
+            .nth(page - 1)
+            .unwrap_or_default()
+            .iter()
+            .map(|&i| {
+                let mut r = referendum(Some("s"), None);
+                r.referendum_index = i;
+                r
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn incremental_fetch_walks_all_pages_on_fresh_db() {
+        let requested = std::cell::RefCell::new(Vec::new());
+        let referenda = collect_until_synced(
+            |page| std::future::ready(Ok(paged(25, 10, page, &requested))),
+            |_| std::future::ready(Ok(false)),
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(referenda.len(), 25);
+        assert_eq!(*requested.borrow(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn incremental_fetch_stops_at_first_synced_index() {
+        // 本地已同步到 #82：第 1 页为 100..=91 全部未同步，第 2 页 90..=81 含已同步的编号
+        let synced: HashSet<u32> = (1..=82).collect();
+        let requested = std::cell::RefCell::new(Vec::new());
+        let referenda = collect_until_synced(
+            |page| std::future::ready(Ok(paged(100, 10, page, &requested))),
+            |indices: Vec<u32>| std::future::ready(Ok(indices.iter().any(|i| synced.contains(i)))),
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(referenda.len(), 20);
+        assert_eq!(*requested.borrow(), vec![1, 2]);
+
+        // 已追平时只请求第 1 页
+        let synced: HashSet<u32> = (1..=100).collect();
+        requested.borrow_mut().clear();
+        collect_until_synced(
+            |page| std::future::ready(Ok(paged(100, 10, page, &requested))),
+            |indices: Vec<u32>| std::future::ready(Ok(indices.iter().any(|i| synced.contains(i)))),
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(*requested.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn cursor_advances_only_on_clean_rounds() {
+        assert_eq!(advance_cursor(None, &[], true), None);
+        assert_eq!(advance_cursor(None, &[5, 9, 7], true), Some(9));
+        assert_eq!(advance_cursor(Some(12), &[5, 9, 7], true), Some(12));
+        assert_eq!(advance_cursor(Some(8), &[5, 9, 7], true), Some(9));
+        assert_eq!(advance_cursor(Some(8), &[5, 9, 7], false), Some(8));
+        assert_eq!(advance_cursor(None, &[5, 9, 7], false), None);
+    }
+
+    #[test]
+    fn idempotency_key_is_stable_per_index_space_and_snapshot() {
+        let heights = HashMap::from([("polkadot".to_string(), 100), ("kusama".to_string(), 200)]);
+        let reordered = HashMap::from([("kusama".to_string(), 200), ("polkadot".to_string(), 100)]);
+        let key = idempotency_key(7, "main", &heights);
+        assert_eq!(key, idempotency_key(7, "main", &reordered));
+        assert_eq!(key.len(), 64);
+        assert_ne!(key, idempotency_key(8, "main", &heights));
+        assert_ne!(key, idempotency_key(7, "archive", &heights));
+        let moved = HashMap::from([("polkadot".to_string(), 101), ("kusama".to_string(), 200)]);
+        assert_ne!(key, idempotency_key(7, "main", &moved));
+    }
 
-        // 6.3 构造 networksConfig
-        let networks_config = NetworksConfig {
-            symbol: "DOT".into(),
-            decimals: 10,
-            networks: vec![
-                NetworkDetail {
-                    network: "polkadot".into(),
-                    ss58_format: 0,
-                    assets: vec![
-                        AssetConfig {
-                            symbol: "DOT".into(),
-                            decimals: 10,
-                            
-                        }
-                    ],
+    fn test_vars() -> HashMap<String, String> {
+        HashMap::from([
+            ("OPEN_SQUARE_SPACE".to_string(), "twodao".to_string()),
+            ("POSTGRES_URL".to_string(), "postgres://tdao@127.0.0.1/tdao".to_string()),
+            ("MNEMONIC".to_string(), "bottom drive obey lake curtain smoke basket hold race lonely fit walk".to_string()),
+            ("INCLUDE_NONCE".to_string(), "true".to_string()),
+        ])
+    }
+
+    fn config() -> Config {
+        Config::from_vars(test_vars()).unwrap()
+    }
+
+    #[test]
+    fn nonce_is_unique_and_signed_only_when_enabled() {
+        use crate::signing::{canonical_json, verify_payload};
+        let mut cfg = config();
+        let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type).unwrap();
+        let r = referendum(Some("s"), None);
+        let build = |cfg: &Config| build_proposal(&r, cfg, "twodao", HashMap::new());
+
+        let (first, second) = (build(&cfg), build(&cfg));
+        assert!(first.nonce.is_some() && second.nonce.is_some());
+        assert_ne!(first.nonce, second.nonce);
+
+        // nonce 在签名字节中，改动 nonce 后原签名失效
+        let nonce = first.nonce.clone().unwrap();
+        let payload = canonical_json(&first).unwrap();
+        assert!(payload.contains(&format!("\"nonce\":\"{}\"", nonce)));
+        let request = sign_request(&keypair, first, cfg.network.ss58_format()).unwrap();
+        assert!(verify_payload(&keypair, &payload, &request.signature).unwrap());
+        let tampered = payload.replace(&nonce, &uuid::Uuid::new_v4().to_string());
+        assert!(!verify_payload(&keypair, &tampered, &request.signature).unwrap());
+
+        cfg.include_nonce = false;
+        let plain = build(&cfg);
+        assert!(plain.nonce.is_none());
+        assert!(!canonical_json(&plain).unwrap().contains("nonce"));
+    }
+
+    /// OpenSquare 新建提案请求体的样例，固定字段名和嵌套结构
+    ///
+    /// 目前仍是按本服务输出整理的合成样例（公投 #1234、测试助记词的地址、签名为占位），
+    /// 尚未替换为 OpenSquare 真实接受过的请求体，因此只能发现结构上的意外改动，发现不了与 OpenSquare 期望的偏差。
+    ///
+    /// 替换 / 重新生成：
+    /// 1. 在 space_publishes 表中取一条本服务发布成功的记录，得到空间名和 OpenSquare 返回的 cid；
+    /// 2. 从 OpenSquare 取回该提案的原始请求体（签过名的 `data` 以及 `address`、`signature` 三个字段），
+    ///    原样覆盖 src/testdata/opensquare_new_proposal.json；
+    /// 3. 把该提案的地址 `<OPENSQUARE_BASE_URL>/space/<space>/proposal/<cid>` 写到下面的“来源”一行。
+    ///
+    /// 测试从请求体本身反推公投和配置（见 `golden_inputs`），换文件后无需改测试代码。
+    ///
+    /// 来源：无（合成样例）
+    const GOLDEN_NEW_PROPOSAL: &str = include_str!("testdata/opensquare_new_proposal.json");
+
+    /// 随时间、随机数或签名密钥变化的字段，不参与比较
+    const VOLATILE_FIELDS: [&str; 4] = ["startDate", "endDate", "timestamp", "nonce"];
+
+    /// 从请求体的 `data` 反推出生成它的 SubSquare 公投、配置和快照高度
+    ///
+    /// 编号、track 和标题取自 `title`（`[` 之前的部分视为 TITLE_PREFIX），摘要取自正文链接之后的部分，
+    /// 提交者取自 `realProposer`；空间、网络、networksConfig 和可选字段决定对应的环境变量
+    fn golden_inputs(data: &Value) -> (SubSquareReferendum, Config, HashMap<String, u64>) {
+        let str_at = |v: &Value| v.as_str().unwrap().to_string();
+        let title = data["title"].as_str().unwrap();
+        let (title_prefix, formatted) = title.split_at(title.find('[').unwrap());
+        let index = Track::parse_index(formatted).unwrap();
+        let (short, rest) = formatted[1..].split_once("] #").unwrap();
+        let title_text = rest.split_once(" - ").unwrap().1;
+        let track_id = (0..=u16::from(u8::MAX))
+            .find(|&id| Track::from_id(id).is_some_and(|t| t.short_name() == short))
+            .or_else(|| short.strip_prefix('T')?.parse().ok())
+            .unwrap();
+        let content = data["content"].as_str().unwrap();
+        let summary = content.split_once("\n\n").map_or("", |(_, summary)| summary);
+
+        let mut referendum = serde_json::json!({
+            "referendumIndex": index,
+            "title": title_text,
+            "track": track_id,
+            "state": { "name": "Deciding" },
+            "contentSummary": { "summary": summary },
+        });
+        if let Some(proposer) = data.get("realProposer") {
+            referendum["proposer"] = proposer.clone();
+        }
+
+        let networks_config = &data["networksConfig"];
+        let asset = &networks_config["networks"][0]["assets"][0];
+        let mut vars = test_vars();
+        vars.extend([
+            ("OPEN_SQUARE_SPACE".to_string(), str_at(&data["space"])),
+            ("NETWORK".to_string(), str_at(&data["proposerNetwork"])),
+            ("PROPOSAL_VERSION".to_string(), str_at(&data["version"])),
+            ("ACCESSIBILITY".to_string(), str_at(&networks_config["accessibility"])),
+            ("NETWORKS_CONFIG_VERSION".to_string(), str_at(&networks_config["version"])),
+            ("TOKEN_SYMBOL".to_string(), str_at(&networks_config["symbol"])),
+            ("TOKEN_DECIMALS".to_string(), networks_config["decimals"].to_string()),
+            ("INCLUDE_NONCE".to_string(), data.get("nonce").is_some().to_string()),
+        ]);
+        let strategies: Vec<String> = networks_config["strategies"].as_array().unwrap().iter().map(str_at).collect();
+        vars.insert("STRATEGIES".into(), strategies.join(","));
+        if !title_prefix.trim().is_empty() {
+            vars.insert("TITLE_PREFIX".into(), title_prefix.trim().into());
+        }
+        if let Some(threshold) = asset.get("votingThreshold") {
+            vars.insert("VOTING_THRESHOLD".into(), str_at(threshold));
+        }
+        if let Some(multiplier) = asset.get("multiplier") {
+            vars.insert("VOTE_MULTIPLIER".into(), multiplier.to_string());
+        }
+        if let Some(discussion) = data.get("discussion") {
+            vars.insert("SET_DISCUSSION_LINK".into(), "true".into());
+            vars.insert("DISCUSSION_LINK_PATTERN".into(), str_at(discussion).replace(&index.to_string(), "{index}"));
+        }
+
+        let heights = serde_json::from_value(data["snapshotHeights"].clone()).unwrap();
+        (serde_json::from_value(referendum).unwrap(), Config::from_vars(vars).unwrap(), heights)
+    }
+
+    #[test]
+    fn new_proposal_matches_golden_payload() {
+        let golden: Value = serde_json::from_str(GOLDEN_NEW_PROPOSAL).unwrap();
+        let (golden_referendum, cfg, heights) = golden_inputs(&golden["data"]);
+        let data = build_proposal(&golden_referendum, &cfg, &cfg.open_square_spaces[0], heights);
+        let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type).unwrap();
+        let built = canonical_value(&sign_request(&keypair, data, cfg.network.ss58_format()).unwrap()).unwrap();
+
+        let keys = |v: &Value| v.as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(keys(&built), keys(&golden));
+        assert_eq!(keys(&built["data"]), keys(&golden["data"]));
+        let duration = |v: &Value| v["data"]["endDate"].as_u64().unwrap() - v["data"]["startDate"].as_u64().unwrap();
+        assert_eq!(duration(&built), duration(&golden));
+
+        let strip = |v: &Value| {
+            let mut data = v["data"].clone();
+            for field in VOLATILE_FIELDS {
+                data.as_object_mut().unwrap().remove(field);
+            }
+            data
+        };
+        assert_eq!(strip(&built), strip(&golden));
+    }
+
+    #[test]
+    fn builtin_transformers_apply_in_order() {
+        let mut vars = HashMap::from([
+            ("OPEN_SQUARE_SPACE".to_string(), "twodao".to_string()),
+            ("POSTGRES_URL".to_string(), "postgres://tdao@127.0.0.1/tdao".to_string()),
+            ("MNEMONIC".to_string(), "bottom drive obey lake curtain smoke basket hold race lonely fit walk".to_string()),
+        ]);
+        assert!(Config::from_vars(vars.clone()).unwrap().transformers.is_empty());
+
+        vars.insert("PUBLISHED_TITLE_PREFIX".into(), " [TDAO] ".into());
+        vars.insert("CONTENT_FOOTER".into(), "Not financial advice.".into());
+        let cfg = Config::from_vars(vars).unwrap();
+        assert_eq!(cfg.transformers.len(), 2);
+
+        let r = referendum(Some("Summary\n"), None);
+        let mut data = build_proposal(&r, &cfg, "twodao", HashMap::new());
+        transform::apply(&cfg.transformers, &r, &mut data);
+        assert_eq!(data.title, "[TDAO] [R] #1 - t");
+        assert_eq!(data.content, "https://polkadot.subsquare.io/referenda/1\n\nSummary\n\nNot financial advice.");
+    }
+
+    /// 自定义转换器：把 track 编号写进正文
+    #[derive(Debug)]
+    struct TrackNote;
+
+    impl crate::transform::ProposalTransformer for TrackNote {
+        fn transform(&self, r: &SubSquareReferendum, data: &mut ProposalData) {
+            data.content.push_str(&format!("\n\ntrack {}", r.track_id));
+        }
+    }
+
+    #[test]
+    fn custom_transformer_can_be_registered() {
+        let mut cfg = config();
+        cfg.transformers.push(std::sync::Arc::new(crate::transform::ContentFooter("footer".into())));
+        cfg.transformers.push(std::sync::Arc::new(TrackNote));
+        let r = referendum(Some("S"), None);
+        let mut data = build_proposal(&r, &cfg, "twodao", HashMap::new());
+        transform::apply(&cfg.transformers, &r, &mut data);
+        assert!(data.content.ends_with("S\n\nfooter\n\ntrack 0"), "{}", data.content);
+    }
+
+    #[test]
+    fn refresh_keeps_vote_duration_and_replaces_snapshot() {
+        let cfg = config();
+        let old_heights = HashMap::from([("polkadot".to_string(), 100)]);
+        let mut data = build_proposal(&referendum(Some("S"), None), &cfg, "twodao", old_heights);
+        let day = ChronoDuration::days(1).num_milliseconds() as u64;
+        data.start_date -= 10 * day;
+        data.end_date -= 10 * day;
+        data.timestamp -= 10 * 24 * 60 * 60;
+        let (start, end, timestamp, nonce) = (data.start_date, data.end_date, data.timestamp, data.nonce.clone());
+
+        let fresh = HashMap::from([("polkadot".to_string(), 5_000)]);
+        refresh_proposal(&mut data, &cfg, fresh.clone());
+
+        assert_eq!(data.end_date - data.start_date, end - start);
+        assert!(data.start_date >= start + 9 * day);
+        assert!(data.timestamp > timestamp);
+        assert_eq!(data.snapshot_heights, fresh);
+        assert!(data.nonce.is_some() && data.nonce != nonce);
+    }
+
+    #[test]
+    fn track_config_overrides_vote_window_per_track() {
+        let mut cfg = config();
+        cfg.track_overrides = HashMap::from([
+            (34, TrackOverride { vote_duration_days: Some(45), ..Default::default() }),
+            (
+                30,
+                TrackOverride {
+                    vote_duration_days: Some(7),
+                    choices: Some(vec!["Aye".into(), "Nay".into()]),
+                    threshold: Some("10000000000".into()),
                 },
-            ],
-            accessibility: "whitelist".into(),
-            whitelist: vec![
-                "12mP4sjCfKbDyMRAEyLpkeHeoYtS5USY4x34n9NMwQrcEyoh".to_string(),
-                "167rjWHghVwBJ52mz8sNkqr5bKu5vpchbc9CBoieBhVX714h".to_string(),
-                "16ap6fdqS2rqFsyYah35hX1FH6rPNWtLqqXZDQC9x6GW141C".to_string(),
-                "14pa3BAYZLPvZfRDjWEfZXZWBVU45E67HUQEUxNCrdXGoata".to_string(),
-                "14qwyVVvW4Tuhq4Fvt2AHZqhbCtGfVb8HUY2xM2PKrzKsmZT".to_string(),
-            ],
-            strategies: vec![
-                "one-person-one-vote".into(),
-            ],
-            version: "4".into(),
+            ),
+        ]);
+        let on_track = |track: u16| -> SubSquareReferendum {
+            serde_json::from_value(serde_json::json!({
+                "referendumIndex": 1,
+                "title": "t",
+                "track": track,
+                "state": { "name": "Deciding" },
+            }))
+            .unwrap()
         };
+        let days = |d: &ProposalData| (d.end_date - d.start_date) / ChronoDuration::days(1).num_milliseconds() as u64;
+
+        let big = build_proposal(&on_track(34), &cfg, "twodao", HashMap::new());
+        let small = build_proposal(&on_track(30), &cfg, "twodao", HashMap::new());
+        let root = build_proposal(&on_track(0), &cfg, "twodao", HashMap::new());
+        assert_eq!((days(&big), days(&small), days(&root)), (45, 7, 30));
+        assert!(days(&big) > days(&small));
+
+        assert_eq!(big.choices, vec!["Aye", "Nay", "Abstain"]);
+        assert_eq!(small.choices, vec!["Aye", "Nay"]);
+        let threshold = |d: &ProposalData| d.networks_config.networks[0].assets[0].voting_threshold.clone();
+        assert_eq!(threshold(&big), None);
+        assert_eq!(threshold(&small).as_deref(), Some("10000000000"));
+    }
 
-        // 6.4 构造 snapshotHeights
-        let mut snapshot_heights = HashMap::new();
-        snapshot_heights.insert("polkadot".into(), snapshot);
-
-        // 6.5 构造 ProposalData
-        let data = ProposalData {
-            space:            cfg.open_square_space.clone(),
-            title:            display_title.clone(),
-            content:          content.clone(),
-            content_type:     "markdown".into(),
-            choice_type:      "single".into(),
-            choices:          vec!["Aye".into(), "Nay".into(), "Abstain".into()],
-            start_date,
-            end_date,
-            snapshot_heights,
-            real_proposer:    None,
-            proposer_network: "polkadot".into(),
-            version:          "5".into(),
-            timestamp:        now.timestamp() as u64,
-            networks_config,
-            discussion:       None,
+    /// 在本地端口上模拟一个 JSON HTTP 接口（OpenSquare、SubSquare、Subscan 等）：应答一次请求后退出，返回基础地址和收到的原始请求
+    fn mock_http(status: u16, body: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = read_request(&mut stream);
+            write_response(&mut stream, status, body);
+            request
+        });
+        (base_url, handle)
+    }
+
+    /// 按 `方法 路径前缀` 应答的多路由模拟服务器，持续接受请求直到测试进程退出
+    ///
+    /// 未匹配的请求返回 404；返回基础地址和按顺序记录的全部原始请求
+    fn mock_routes(
+        routes: Vec<(&'static str, u16, String)>,
+    ) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = requests.clone();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().map_while(Result::ok) {
+                let request = read_request(&mut stream);
+                let (status, body) = routes
+                    .iter()
+                    .find(|(prefix, _, _)| request.starts_with(prefix))
+                    .map_or((404, "{}"), |(_, status, body)| (*status, body.as_str()));
+                write_response(&mut stream, status, body);
+                log.lock().unwrap().push(request);
+            }
+        });
+        (base_url, requests)
+    }
+
+    /// 读取一个完整的 HTTP 请求（按 Content-Length 读完请求体）
+    fn read_request(stream: &mut std::net::TcpStream) -> String {
+        use std::io::Read;
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, rest)) = text.split_once("\r\n\r\n") {
+                let len: usize = head
+                    .lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                    .unwrap_or(0);
+                if rest.len() >= len {
+                    break;
+                }
+            }
+            if n == 0 {
+                break;
+            }
+        }
+        String::from_utf8(request).unwrap()
+    }
+
+    fn write_response(stream: &mut std::net::TcpStream, status: u16, body: &str) {
+        use std::io::Write;
+        write!(
+            stream,
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_POSTGRES_URL"]
+    async fn run_sync_against_mocked_upstreams() {
+        use crate::db::test_support::TestDb;
+        use crate::signing::{canonical_json, verify_payload};
+        let t = TestDb::new().await;
+
+        let referendum = serde_json::json!({
+            "referendumIndex": 42,
+            "title": "Fund tooling",
+            "track": 34,
+            "state": { "name": "Deciding" },
+            "proposer": "14pa3BAYZLPvZfRDjWEfZXZWBVU45E67HUQEUxNCrdXGoata",
+            "contentSummary": { "summary": "Build open tooling." },
+        });
+        // SubSquare、Subscan 和 OpenSquare 的接口路径互不重叠，共用一个模拟服务器
+        let (base_url, requests) = mock_routes(vec![
+            ("GET /gov2/referendums?", 200, serde_json::json!({ "items": [referendum] }).to_string()),
+            ("POST /api/scan/metadata", 200, r#"{"code":0,"data":{"blockNum":"1000","blockTime":0}}"#.to_string()),
+            ("POST /api/twodao/proposals", 200, r#"{"cid":"cid-42"}"#.to_string()),
+        ]);
+        let mut cfg = config();
+        cfg.subsquare_base_url = base_url.clone();
+        cfg.opensquare_base_url = base_url.clone();
+        cfg.subscan_base_url = base_url;
+
+        let client = Client::builder().no_proxy().build().unwrap();
+        let report = run_sync(&client, &RateLimiter::new(None), &HeightCache::new(Duration::ZERO), &t.db, &cfg)
+            .await
+            .unwrap();
+        assert_eq!((report.fetched, report.published, report.failed), (1, 1, 0), "{:?}", report);
+
+        // OpenSquare 收到的请求体签名覆盖规范化后的 data，快照高度为最新高度减默认偏移
+        let requests = requests.lock().unwrap().clone();
+        let publish = requests.iter().find(|r| r.starts_with("POST /api/twodao/proposals")).unwrap();
+        let body: Value = serde_json::from_str(publish.split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(body["data"]["title"], "[BS] #42 - Fund tooling");
+        assert_eq!(body["data"]["space"], "twodao");
+        assert!(body["data"]["content"].as_str().unwrap().ends_with("Build open tooling."));
+        let heights = &body["data"]["snapshotHeights"];
+        assert!(heights.as_object().unwrap().values().all(|h| h.as_u64() == Some(1000 - cfg.snapshot_offset)), "{}", heights);
+        let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type).unwrap();
+        let payload = canonical_json(&body["data"]).unwrap();
+        assert!(verify_payload(&keypair, &payload, body["signature"].as_str().unwrap()).unwrap());
+
+        assert_eq!(t.db.get_existing_indices().await.unwrap(), vec![42]);
+        assert_eq!(t.db.published_cids(42).await.unwrap(), vec![("twodao".to_string(), "cid-42".to_string())]);
+        t.cleanup().await;
+    }
+
+    fn update_data() -> ProposalUpdateData {
+        let cfg = config();
+        build_update(&referendum(Some("New summary"), None), &cfg, "twodao", "cid-old")
+    }
+
+    #[tokio::test]
+    async fn update_posts_signed_payload_with_original_cid() {
+        let (base_url, server) = mock_http(200, r#"{"cid":"cid-new"}"#);
+        let mut cfg = config();
+        cfg.opensquare_base_url = base_url;
+        let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type).unwrap();
+        let client = Client::builder().no_proxy().build().unwrap();
+
+        let new_cid = send_update(&client, &RateLimiter::new(None), &cfg, &keypair, update_data()).await.unwrap();
+        assert_eq!(new_cid, "cid-new");
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /api/twodao/proposals/cid-old/update "));
+        let body: crate::models::OpenSquareUpdateProposalRequest =
+            serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(body.data.proposal_cid, "cid-old");
+        assert!(body.data.content.contains("New summary"));
+        let payload = crate::signing::canonical_json(&body.data).unwrap();
+        assert!(crate::signing::verify_payload(&keypair, &payload, &body.signature).unwrap());
+    }
+
+    #[tokio::test]
+    async fn update_keeps_cid_when_response_has_none_and_surfaces_rejection() {
+        let mut cfg = config();
+        let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type).unwrap();
+        let client = Client::builder().no_proxy().build().unwrap();
+        let limiter = RateLimiter::new(None);
+
+        let (base_url, server) = mock_http(200, "{}");
+        cfg.opensquare_base_url = base_url;
+        assert_eq!(send_update(&client, &limiter, &cfg, &keypair, update_data()).await.unwrap(), "cid-old");
+        server.join().unwrap();
+
+        let (base_url, server) = mock_http(403, r#"{"message":"signature mismatch"}"#);
+        cfg.opensquare_base_url = base_url;
+        let err = send_update(&client, &limiter, &cfg, &keypair, update_data()).await.unwrap_err();
+        assert!(err.is_auth_rejection());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn amounts_use_token_decimals_and_grouping() {
+        assert_eq!(format_amount(100_000_000_000_000, 10), "10,000");
+        assert_eq!(format_amount(12_345_000_000_000, 10), "1,234.5");
+        assert_eq!(format_amount(1, 10), "0.0000000001");
+        assert_eq!(format_amount(0, 12), "0");
+        assert_eq!(format_amount(123_456_789, 0), "123,456,789");
+    }
+
+    #[test]
+    fn token_symbol_and_decimals_are_serialized_consistently() {
+        let assert_token = |cfg: &Config, symbol: &str, decimals: u8| {
+            let v = serde_json::to_value(build_proposal(&referendum(Some("s"), None), cfg, "twodao", HashMap::new())).unwrap();
+            let networks_config = &v["networksConfig"];
+            assert_eq!(networks_config["symbol"], symbol);
+            assert_eq!(networks_config["decimals"], decimals);
+            let asset = &networks_config["networks"][0]["assets"][0];
+            assert_eq!(asset["symbol"], symbol);
+            assert_eq!(asset["decimals"], decimals);
         };
 
-        // 6.6 签名 & 拼装请求
-        let payload = serde_json::to_string(&data)?;
-        let sig     = keypair.sign(payload.as_bytes());
-        let address = sp_core::sr25519::Public::from_raw(keypair.public().0)
-            .to_ss58check_with_version(
-                Ss58AddressFormat::from(Ss58AddressFormatRegistry::PolkadotAccount)
-            );
-        let request = OpenSquareNewProposalRequest {
-            data,
-            address:   address.clone(),
-            signature: format!("0x{}", hex::encode(sig)),
+        // 未配置时按 NETWORK 取原生代币
+        assert_token(&config(), "DOT", 10);
+
+        let cfg = Config::from_vars(HashMap::from([
+            ("OPEN_SQUARE_SPACE".to_string(), "twodao".to_string()),
+            ("POSTGRES_URL".to_string(), "postgres://tdao@127.0.0.1/tdao".to_string()),
+            ("MNEMONIC".to_string(), "bottom drive obey lake curtain smoke basket hold race lonely fit walk".to_string()),
+            ("TOKEN_SYMBOL".to_string(), " USDT ".to_string()),
+            ("TOKEN_DECIMALS".to_string(), "6".to_string()),
+        ]))
+        .unwrap();
+        assert_token(&cfg, "USDT", 6);
+    }
+
+    #[test]
+    fn spend_details_prepended_only_when_enabled_and_present() {
+        let spender: SubSquareReferendum = serde_json::from_value(serde_json::json!({
+            "referendumIndex": 9,
+            "title": "Fund tooling",
+            "track": 33,
+            "state": { "name": "Deciding" },
+            "contentSummary": { "summary": "Build things" },
+            "onchainData": { "treasuryInfo": { "amount": "100000000000000", "beneficiary": "15abc" } },
+        }))
+        .unwrap();
+        let mut cfg = config();
+        assert!(!build_content(&spender, &cfg).contains("Requested"));
+
+        cfg.include_spend_details = true;
+        let content = build_content(&spender, &cfg);
+        assert!(content.starts_with("Requested: 10,000 DOT → 15abc\n\n"), "{}", content);
+        assert!(content.ends_with("Build things"));
+        assert!(!build_content(&referendum(Some("S"), None), &cfg).contains("Requested"));
+    }
+
+    #[test]
+    fn track_line_prepended_when_enabled() {
+        let mut cfg = config();
+        let mut r = referendum(Some("Build things"), None);
+        r.track_id = 34;
+        assert!(!build_content(&r, &cfg).contains("Track:"));
+
+        cfg.include_track_in_content = true;
+        assert!(build_content(&r, &cfg).starts_with("Track: Big Spender\n\nhttps://"));
+        r.track_id = 99;
+        assert!(build_content(&r, &cfg).starts_with("Track: Track 99\n\n"));
+
+        // 与国库支出行同时开启时 track 在前
+        cfg.include_spend_details = true;
+        let spender: SubSquareReferendum = serde_json::from_value(serde_json::json!({
+            "referendumIndex": 9,
+            "track": 33,
+            "state": { "name": "Deciding" },
+            "onchainData": { "treasuryInfo": { "amount": "10000000000" } },
+        }))
+        .unwrap();
+        assert!(build_content(&spender, &cfg).starts_with("Track: Medium Spender\n\nRequested: 1 DOT\n\n"));
+    }
+
+    #[test]
+    fn content_type_round_trips_and_plain_strips_markdown() {
+        let mut cfg = config();
+        let r = referendum(Some("## Plan\n\n**Build** [docs](https://docs.io)"), None);
+        let payload = |cfg: &Config| {
+            let data = build_proposal(&r, cfg, "twodao", HashMap::new());
+            serde_json::from_str::<ProposalData>(&serde_json::to_string(&data).unwrap()).unwrap()
         };
 
-        // 6.7 日志打印
-       // info!("📨 签名地址: {}", address);
-        info!("🔗 请求 URL: https://voting.opensquare.io/api/{}/proposals", cfg.open_square_space);
-        // info!("📤 请求体: {}", to_string_pretty(&request)?);
-
-        // 6.8 发送
-        let res = client
-            .post(&format!("https://voting.opensquare.io/api/{}/proposals", cfg.open_square_space))
-            .json(&request)
-            .send()
-            .await?;
-        let status = res.status();
-        let body   = res.text().await.unwrap_or_default();
-        if !status.is_success() {
-            error!("❌ 发布失败 #{}：{} - {}", r.referendum_index, status, body);
-            continue;
+        let markdown = payload(&cfg);
+        assert_eq!(markdown.content_type, "markdown");
+        assert!(markdown.content.contains("## Plan\n\n**Build** [docs](https://docs.io)"), "{}", markdown.content);
+
+        cfg.content_type = ContentType::Plain;
+        let plain = payload(&cfg);
+        assert_eq!(plain.content_type, "plain");
+        assert!(plain.content.contains("Plan\n\nBuild docs (https://docs.io)"), "{}", plain.content);
+        assert!(!plain.content.contains("**") && !plain.content.contains("## "));
+        let update = build_update(&r, &cfg, "twodao", "cid");
+        assert_eq!((update.content_type.as_str(), update.content), ("plain", plain.content));
+    }
+
+    #[test]
+    fn circuit_breaker_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3);
+        assert!(!breaker.record(false));
+        assert!(!breaker.record(false));
+        assert!(!breaker.record(true));
+        assert!(!breaker.is_open());
+
+        assert!(!breaker.record(false));
+        assert!(!breaker.record(false));
+        assert!(breaker.record(false));
+        assert!(breaker.is_open());
+        // 已打开后的失败不再重复触发熔断日志
+        assert!(!breaker.record(false));
+
+        let disabled = CircuitBreaker::new(0);
+        for _ in 0..10 {
+            assert!(!disabled.record(false));
         }
-        info!("✅ 发布成功 #{}：{}", r.referendum_index, status);
+        assert!(!disabled.is_open());
+    }
 
-        // 6.9 插入 DB
-        db.insert_referendum(r.referendum_index).await?;
-        
-        info!("🗄 已插入本地数据库 #{}", r.referendum_index);
+    #[test]
+    fn publish_order_sorts_by_index() {
+        let batch = || -> Vec<SubSquareReferendum> {
+            [200, 150, 175]
+                .into_iter()
+                .map(|index| {
+                    let mut r = referendum(Some("S"), None);
+                    r.referendum_index = index;
+                    r
+                })
+                .collect()
+        };
+        let indices = |rs: &[SubSquareReferendum]| rs.iter().map(|r| r.referendum_index).collect::<Vec<_>>();
+
+        let mut asc = batch();
+        sort_for_publish(&mut asc, PublishOrder::Asc);
+        assert_eq!(indices(&asc), vec![150, 175, 200]);
 
+        let mut desc = batch();
+        sort_for_publish(&mut desc, PublishOrder::Desc);
+        assert_eq!(indices(&desc), vec![200, 175, 150]);
+        assert_eq!(config().publish_order, PublishOrder::Asc);
     }
 
-    Ok(())
+    #[test]
+    fn phase_timer_accumulates_per_phase() {
+        let timer = PhaseTimer::default();
+        let earlier = Instant::now() - Duration::from_millis(20);
+        timer.record(Phase::Post, earlier);
+        timer.record(Phase::Post, earlier);
+        timer.record(Phase::Db, Instant::now());
+
+        let timings = timer.timings();
+        assert!(timings.post >= Duration::from_millis(40));
+        assert!(timings.db < Duration::from_millis(20));
+        assert_eq!((timings.fetch, timings.snapshot, timings.sign), (Duration::ZERO, Duration::ZERO, Duration::ZERO));
+        assert!(timings.to_string().starts_with("拉取 0ns，快照 0ns，签名 0ns，发布 "));
+    }
+
+    #[tokio::test]
+    async fn fetch_single_referendum_found_and_not_found() {
+        let (client, limiter) = (Client::builder().no_proxy().build().unwrap(), RateLimiter::new(None));
+
+        let (base_url, server) = mock_http(
+            200,
+            r#"{"referendumIndex":42,"title":"Fund tooling","track":34,"state":{"name":"Deciding"}}"#,
+        );
+        let r = fetch_single_referendum(&client, &limiter, &base_url, 42).await.unwrap().unwrap();
+        assert_eq!((r.referendum_index, r.track_id), (42, 34));
+        assert!(server.join().unwrap().starts_with("GET /gov2/referendums/42 "));
+
+        let (base_url, server) = mock_http(404, r#"{"error":"Referendum not found"}"#);
+        assert!(fetch_single_referendum(&client, &limiter, &base_url, 9999).await.unwrap().is_none());
+        server.join().unwrap();
+
+        let (base_url, server) = mock_http(404, "{}");
+        let err = fetch_referendum(&client, &limiter, &base_url, 9999).await.unwrap_err();
+        assert!(err.to_string().contains("#9999"), "{}", err);
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_own_proposals_with_our_title_format_count_as_published() {
+        let mut vars = test_vars();
+        vars.insert("TITLE_PREFIX".into(), "TDAO".into());
+        vars.insert("PUBLISHED_TITLE_PREFIX".into(), "[mirror]".into());
+        let mut cfg = Config::from_vars(vars).unwrap();
+        let keypair = Signer::from_phrase(cfg.mnemonic.expose(), cfg.key_type).unwrap();
+        let ours = signer_address(&keypair, cfg.network.ss58_format()).unwrap();
+        let foreign = "14pa3BAYZLPvZfRDjWEfZXZWBVU45E67HUQEUxNCrdXGoata";
+
+        let items = serde_json::json!({ "items": [
+            // 别人发布的同名提案，不能阻止 #7 发布
+            { "title": "[mirror] TDAO [BS] #7 - Fund tooling", "address": foreign },
+            { "title": "[mirror] TDAO [BS] #8 - Fund tooling", "address": ours },
+            { "title": "[mirror] TDAO [T37] #9 - New track", "proposer": ours },
+            // 我们发的，但标题不是当前配置下的格式
+            { "title": "TDAO [BS] #10 - missing published prefix", "address": ours },
+            { "title": "[mirror] [BS] #11 - missing title prefix", "address": ours },
+            { "title": "[mirror] TDAO Re: [BS] #12 - discussion", "address": ours },
+        ]});
+        let (base_url, _) = mock_routes(vec![("GET /api/twodao/proposals?", 200, items.to_string())]);
+        cfg.opensquare_base_url = base_url;
+        let (client, limiter) = (Client::builder().no_proxy().build().unwrap(), RateLimiter::new(None));
+
+        let indices = fetch_opensquare_indices(&client, &limiter, &cfg, "twodao", &ours).await.unwrap();
+        assert_eq!(indices, vec![8, 9]);
+        let indices = fetch_opensquare_indices(&client, &limiter, &cfg, "twodao", foreign).await.unwrap();
+        assert_eq!(indices, vec![7]);
+    }
+
+    #[tokio::test]
+    async fn subscan_height_offset_against_mock() {
+        let (client, limiter) = (Client::builder().no_proxy().build().unwrap(), RateLimiter::new(None));
+        let subscan = |base_url: String| block_source::SubscanBlockSource { base_url, api_key: None };
+
+        let (base_url, server) = mock_http(200, r#"{"code":0,"data":{"blockNum":"1000"}}"#);
+        let height = get_latest_block_height(&client, &limiter, &subscan(base_url), &HeightCache::new(Duration::ZERO), "polkadot", 50, 1)
+            .await
+            .unwrap();
+        assert_eq!(height, 950);
+        assert!(server.join().unwrap().starts_with("POST /api/scan/metadata "));
+
+        // 偏移等于高度：快照为 0，被 MIN_SNAPSHOT_HEIGHT 拒绝
+        let (base_url, server) = mock_http(200, r#"{"code":0,"data":{"blockNum":"50"}}"#);
+        let err = get_latest_block_height(&client, &limiter, &subscan(base_url), &HeightCache::new(Duration::ZERO), "polkadot", 50, 1)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("MIN_SNAPSHOT_HEIGHT"), "{}", err);
+        server.join().unwrap();
+
+        let (base_url, server) = mock_http(200, r#"{"code":0,"data":{"blockNum":"n/a"}}"#);
+        assert!(get_latest_block_height(&client, &limiter, &subscan(base_url), &HeightCache::new(Duration::ZERO), "polkadot", 0, 0)
+            .await
+            .is_err());
+        server.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn latest_minus_offset_snapshot() {
+        let (client, limiter) = (Client::builder().no_proxy().build().unwrap(), RateLimiter::new(None));
+        let cache = HeightCache::new(Duration::ZERO);
+        let source = FixedSource(1_000);
+        let latest = get_latest_block_height(&client, &limiter, &source, &cache, "polkadot", 50, 1)
+            .await
+            .unwrap();
+        assert_eq!(latest, 950);
+        assert_eq!(SnapshotMode::LatestMinusOffset.resolve(latest, Some(900)).unwrap(), 950);
+
+        // 高度倒退到 10：远低于此前见过的 1000
+        let err = get_latest_block_height(&client, &limiter, &FixedSource(10), &cache, "polkadot", 50, 1)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("低于此前见过的 1000"), "{}", err);
+
+        // 首次见到的网络只受 MIN_SNAPSHOT_HEIGHT 约束
+        let fresh = HeightCache::new(Duration::ZERO);
+        let err = get_latest_block_height(&client, &limiter, &FixedSource(10), &fresh, "kusama", 50, 1)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("MIN_SNAPSHOT_HEIGHT"), "{}", err);
+        let low = get_latest_block_height(&client, &limiter, &FixedSource(10), &fresh, "westend", 50, 0)
+            .await
+            .unwrap();
+        assert_eq!(low, 0);
+    }
 }
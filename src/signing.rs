@@ -0,0 +1,325 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sp_core::crypto::{Ss58AddressFormat, Ss58Codec};
+use sp_core::{ed25519, sr25519, Pair};
+
+use crate::config::KeyType;
+use crate::models::{OpenSquareNewProposalRequest, OpenSquareUpdateProposalRequest, ProposalData, ProposalUpdateData};
+
+/// 递归地将所有对象的键按字典序重排
+///
+/// 无论 serde_json 是否开启 `preserve_order`，按排序后的顺序重新插入都能得到稳定的键序，
+/// 因此签名字节不再依赖结构体字段顺序或 HashMap 的迭代顺序
+pub fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut sorted = Map::new();
+            for (k, v) in entries {
+                sorted.insert(k, canonicalize(v));
+            }
+            Value::Object(sorted)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(canonicalize).collect()),
+        other => other,
+    }
+}
+
+/// 序列化为键有序的紧凑 JSON，作为签名字节
+pub fn canonical_json<T: Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_string(&canonicalize(serde_json::to_value(value)?))?)
+}
+
+/// 序列化为键有序的 JSON 值，作为请求体发送，保证服务端看到的键序与签名时一致
+pub fn canonical_value<T: Serialize>(value: &T) -> Result<Value> {
+    Ok(canonicalize(serde_json::to_value(value)?))
+}
+
+/// 签名密钥对，算法由 KEY_TYPE 决定
+///
+/// 两种算法的签名都是 64 字节，以不带类型前缀的 `0x` 十六进制发送；
+/// OpenSquare 验签时会按地址公钥依次尝试各算法，因此无需附加 MultiSignature 的类型字节。
+/// 两种密钥对都有数百字节且大小不一，都装箱，免得枚举按较大的一种占用空间
+pub enum Signer {
+    Sr25519(Box<sr25519::Pair>),
+    Ed25519(Box<ed25519::Pair>),
+}
+
+impl Signer {
+    /// 由助记词（可带派生路径）生成密钥对；ed25519 只支持硬派生 `//`
+    pub fn from_phrase(phrase: &str, key_type: KeyType) -> Result<Self> {
+        let invalid = |e| anyhow::anyhow!("无法由 MNEMONIC 生成 {} 密钥：{:?}", key_type, e);
+        Ok(match key_type {
+            KeyType::Sr25519 => Signer::Sr25519(Box::new(sr25519::Pair::from_string(phrase, None).map_err(invalid)?)),
+            KeyType::Ed25519 => Signer::Ed25519(Box::new(ed25519::Pair::from_string(phrase, None).map_err(invalid)?)),
+        })
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        match self {
+            Signer::Sr25519(_) => KeyType::Sr25519,
+            Signer::Ed25519(_) => KeyType::Ed25519,
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match self {
+            Signer::Sr25519(pair) => AsRef::<[u8]>::as_ref(&pair.sign(message)).to_vec(),
+            Signer::Ed25519(pair) => AsRef::<[u8]>::as_ref(&pair.sign(message)).to_vec(),
+        }
+    }
+
+    fn verify(&self, signature: &[u8], message: &[u8]) -> bool {
+        match self {
+            Signer::Sr25519(pair) => sr25519::Signature::try_from(signature)
+                .is_ok_and(|sig| sr25519::Pair::verify(&sig, message, &pair.public())),
+            Signer::Ed25519(pair) => ed25519::Signature::try_from(signature)
+                .is_ok_and(|sig| ed25519::Pair::verify(&sig, message, &pair.public())),
+        }
+    }
+}
+
+/// SS58 前缀只有 14 位，更大的值会被静默截断成别的网络的地址
+const MAX_SS58_FORMAT: u16 = 0x3fff;
+
+/// 签名者的 SS58 地址，`ss58_format` 为网络前缀（Polkadot 0、Kusama 2），超出 14 位时报错
+pub fn signer_address(signer: &Signer, ss58_format: u16) -> Result<String> {
+    if ss58_format > MAX_SS58_FORMAT {
+        anyhow::bail!("SS58 前缀 {} 无效：最大为 {}", ss58_format, MAX_SS58_FORMAT);
+    }
+    let format = Ss58AddressFormat::custom(ss58_format);
+    Ok(match signer {
+        Signer::Sr25519(pair) => pair.public().to_ss58check_with_version(format),
+        Signer::Ed25519(pair) => pair.public().to_ss58check_with_version(format),
+    })
+}
+
+/// 对载荷签名，返回 `0x` 开头的十六进制签名
+pub fn sign_payload(signer: &Signer, payload: &str) -> String {
+    format!("0x{}", hex::encode(signer.sign(payload.as_bytes())))
+}
+
+/// 用签名者自己的公钥校验 `sign_payload` 产生的签名
+pub fn verify_payload(signer: &Signer, payload: &str, signature: &str) -> Result<bool> {
+    let bytes = hex::decode(signature.trim_start_matches("0x"))?;
+    if bytes.len() != 64 {
+        anyhow::bail!("签名长度不正确：{} 字节", bytes.len());
+    }
+    Ok(signer.verify(&bytes, payload.as_bytes()))
+}
+
+/// 对提案签名并拼装 OpenSquare 请求，签名字节为规范化 JSON
+pub fn sign_request(
+    signer: &Signer,
+    data: ProposalData,
+    ss58_format: u16,
+) -> Result<OpenSquareNewProposalRequest> {
+    let payload = canonical_json(&data)?;
+    Ok(OpenSquareNewProposalRequest {
+        signature: sign_payload(signer, &payload),
+        address: signer_address(signer, ss58_format)?,
+        data,
+    })
+}
+
+/// 对提案更新签名并拼装请求，签名方式与 `sign_request` 相同
+pub fn sign_update(
+    signer: &Signer,
+    data: ProposalUpdateData,
+    ss58_format: u16,
+) -> Result<OpenSquareUpdateProposalRequest> {
+    let payload = canonical_json(&data)?;
+    Ok(OpenSquareUpdateProposalRequest {
+        signature: sign_payload(signer, &payload),
+        address: signer_address(signer, ss58_format)?,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHRASE: &str = "bottom drive obey lake curtain smoke basket hold race lonely fit walk//Alice";
+
+    /// 随机嵌套 JSON，对象的键以随机顺序插入
+    fn random_value(rng: &mut impl rand::Rng, depth: u32) -> Value {
+        const KEYS: &[&str] = &["a", "B", "b", "_", "10", "2", "é", "键", "snapshotHeights", "title", ""];
+        match rng.gen_range(0..if depth == 0 { 4 } else { 6 }) {
+            0 => Value::Null,
+            1 => Value::Bool(rng.gen()),
+            2 => Value::from(rng.gen::<i64>()),
+            3 => Value::from(KEYS[rng.gen_range(0..KEYS.len())].repeat(rng.gen_range(0..3))),
+            4 => Value::Array((0..rng.gen_range(0..4)).map(|_| random_value(rng, depth - 1)).collect()),
+            _ => {
+                let mut keys: Vec<&str> = KEYS.to_vec();
+                keys.truncate(rng.gen_range(0..KEYS.len()));
+                let mut map = Map::new();
+                for i in (1..keys.len()).rev() {
+                    keys.swap(i, rng.gen_range(0..=i));
+                }
+                for key in keys {
+                    map.insert(key.to_string(), random_value(rng, depth - 1));
+                }
+                Value::Object(map)
+            }
+        }
+    }
+
+    /// 所有层级的对象键都按字典序排列
+    fn keys_sorted(v: &Value) -> bool {
+        match v {
+            Value::Object(map) => {
+                let keys: Vec<&String> = map.keys().collect();
+                keys.windows(2).all(|w| w[0] < w[1]) && map.values().all(keys_sorted)
+            }
+            Value::Array(items) => items.iter().all(keys_sorted),
+            _ => true,
+        }
+    }
+
+    #[test]
+    fn canonical_json_is_sorted_stable_and_lossless() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x5129);
+        for _ in 0..500 {
+            let value = random_value(&mut rng, 4);
+            let json = canonical_json(&value).unwrap();
+            let parsed: Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, value);
+            assert!(keys_sorted(&canonicalize(value.clone())));
+            // 反序列化后再规范化得到相同字节，签名不受往返影响
+            assert_eq!(canonical_json(&parsed).unwrap(), json);
+        }
+    }
+
+    fn fixed_proposal() -> ProposalData {
+        use crate::models::{AssetConfig, NetworkDetail, NetworksConfig};
+        ProposalData {
+            space: "twodao".into(),
+            title: "[BS] #1234 - Fund tooling".into(),
+            content: "https://polkadot.subsquare.io/referenda/1234\n\nBuild \"open\" tooling.".into(),
+            content_type: "markdown".into(),
+            choice_type: "single".into(),
+            choices: vec!["Aye".into(), "Nay".into(), "Abstain".into()],
+            start_date: 1_717_200_000_000,
+            end_date: 1_719_792_000_000,
+            // HashMap 的迭代顺序不固定，规范化后必须按键排序
+            snapshot_heights: [("polkadot".to_string(), 21_000_000), ("kusama".to_string(), 23_000_000)].into(),
+            real_proposer: Some(Value::String("14pa3BAYZLPvZfRDjWEfZXZWBVU45E67HUQEUxNCrdXGoata".into())),
+            proposer_network: "polkadot".into(),
+            version: "5".into(),
+            timestamp: 1_717_200_000,
+            networks_config: NetworksConfig {
+                symbol: "DOT".into(),
+                decimals: 10,
+                networks: vec![NetworkDetail {
+                    network: "polkadot".into(),
+                    ss58_format: 0,
+                    assets: vec![AssetConfig {
+                        symbol: "DOT".into(),
+                        decimals: 10,
+                        voting_threshold: None,
+                        multiplier: None,
+                    }],
+                }],
+                strategies: vec!["one-person-one-vote".into()],
+                version: "4".into(),
+                accessibility: "public".into(),
+                whitelist: None,
+            },
+            discussion: None,
+            nonce: None,
+        }
+    }
+
+    #[test]
+    fn signed_bytes_are_pinned() {
+        let expected = concat!(
+            r#"{"choiceType":"single","choices":["Aye","Nay","Abstain"],"#,
+            r#""content":"https://polkadot.subsquare.io/referenda/1234\n\nBuild \"open\" tooling.","contentType":"markdown","#,
+            r#""endDate":1719792000000,"#,
+            r#""networksConfig":{"accessibility":"public","decimals":10,"#,
+            r#""networks":[{"assets":[{"decimals":10,"symbol":"DOT"}],"network":"polkadot","ss58Format":0}],"#,
+            r#""strategies":["one-person-one-vote"],"symbol":"DOT","version":"4"},"#,
+            r#""proposerNetwork":"polkadot","realProposer":"14pa3BAYZLPvZfRDjWEfZXZWBVU45E67HUQEUxNCrdXGoata","#,
+            r#""snapshotHeights":{"kusama":23000000,"polkadot":21000000},"#,
+            r#""space":"twodao","startDate":1717200000000,"timestamp":1717200000,"#,
+            r#""title":"[BS] #1234 - Fund tooling","version":"5"}"#,
+        );
+        assert_eq!(canonical_json(&fixed_proposal()).unwrap(), expected);
+
+        // ed25519 签名是确定性的，可以连同签名一起固定；签名必须覆盖上面这串字节
+        let signer = Signer::from_phrase(PHRASE, KeyType::Ed25519).unwrap();
+        let request = sign_request(&signer, fixed_proposal(), 0).unwrap();
+        assert!(verify_payload(&signer, expected, &request.signature).unwrap());
+        assert_eq!(
+            request.signature,
+            "0x559c5af77bc9468c431b810db13069fa33b720fdda70ea87af0196482c5723d0\
+             a88ebcf2a83f3426d1c7571b08239c7dbf555657168234957e9ad51c9be38003"
+        );
+    }
+
+    #[test]
+    fn both_key_types_sign_and_verify() {
+        for key_type in [KeyType::Sr25519, KeyType::Ed25519] {
+            let signer = Signer::from_phrase(PHRASE, key_type).unwrap();
+            assert_eq!(signer.key_type(), key_type);
+            let signature = sign_payload(&signer, "{\"a\":1}");
+            assert!(signature.starts_with("0x"));
+            assert_eq!(signature.len(), 2 + 128, "{:?} 签名应为 64 字节", key_type);
+            assert!(verify_payload(&signer, "{\"a\":1}", &signature).unwrap());
+            assert!(!verify_payload(&signer, "{\"a\":2}", &signature).unwrap());
+        }
+        assert!(verify_payload(&Signer::from_phrase(PHRASE, KeyType::Sr25519).unwrap(), "x", "0x00").is_err());
+    }
+
+    #[test]
+    fn ed25519_signature_verifies_against_derived_address() {
+        let signer = Signer::from_phrase(PHRASE, KeyType::Ed25519).unwrap();
+        let address = signer_address(&signer, 0).unwrap();
+        let (public, format) = ed25519::Public::from_ss58check_with_version(&address).unwrap();
+        assert_eq!(u16::from(format), 0);
+
+        let signature = sign_payload(&signer, "{\"a\":1}");
+        let bytes = hex::decode(signature.trim_start_matches("0x")).unwrap();
+        let signature = ed25519::Signature::try_from(bytes.as_slice()).unwrap();
+        assert!(ed25519::Pair::verify(&signature, b"{\"a\":1}", &public));
+        assert!(!ed25519::Pair::verify(&signature, b"{\"a\":2}", &public));
+    }
+
+    #[test]
+    fn address_prefix_follows_network() {
+        use crate::network::Network;
+        let signer = Signer::from_phrase(PHRASE, KeyType::Sr25519).unwrap();
+        // 同一把 Alice 公钥在不同前缀下的地址
+        assert_eq!(signer_address(&signer, 0).unwrap(), "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5");
+        assert_eq!(signer_address(&signer, 2).unwrap(), "HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F");
+        assert_eq!(signer_address(&signer, 42).unwrap(), "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY");
+        assert_eq!(Network::Polkadot.ss58_format(), 0);
+        assert_eq!(Network::Kusama.ss58_format(), 2);
+
+        let request = sign_request(&signer, fixed_proposal(), Network::Kusama.ss58_format()).unwrap();
+        assert_eq!(request.address, "HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F");
+
+        assert!(signer_address(&signer, MAX_SS58_FORMAT).is_ok());
+        let err = signer_address(&signer, MAX_SS58_FORMAT + 1).unwrap_err();
+        assert!(err.to_string().contains("SS58 前缀"), "{}", err);
+        assert!(sign_request(&signer, fixed_proposal(), u16::MAX).is_err());
+        assert!("westend".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn address_depends_on_key_type() {
+        // Alice 的开发密钥：sr25519 与 ed25519 派生出不同的公钥
+        let sr = Signer::from_phrase(PHRASE, KeyType::Sr25519).unwrap();
+        let ed = Signer::from_phrase(PHRASE, KeyType::Ed25519).unwrap();
+        assert_eq!(signer_address(&sr, 42).unwrap(), "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY");
+        assert_eq!(signer_address(&ed, 42).unwrap(), "5FA9nQDVg267DEd8m1ZypXLBnvN7SFxYwV7ndqSYGiN9TTpu");
+
+        // ed25519 不支持软派生
+        assert!(Signer::from_phrase("bottom drive obey lake curtain smoke basket hold race lonely fit walk/soft", KeyType::Ed25519).is_err());
+    }
+}
@@ -0,0 +1,39 @@
+use std::fmt;
+
+use crate::models::{ProposalData, SubSquareReferendum};
+
+/// 签名前对提案做最后调整的钩子，如加标题标记、追加免责声明
+///
+/// `Config::transformers` 中的转换器按顺序作用于每个空间的 `ProposalData`，之后才规范化并签名；
+/// 只影响发布到 OpenSquare 的载荷，本地记录的标题和内容哈希仍按未转换的内容计算。
+/// 嵌入方可在 `Config::from_env` 之后追加自己的实现
+pub trait ProposalTransformer: fmt::Debug + Send + Sync {
+    fn transform(&self, r: &SubSquareReferendum, data: &mut ProposalData);
+}
+
+/// 在标题前加标记，以空格分隔（PUBLISHED_TITLE_PREFIX）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitlePrefix(pub String);
+
+impl ProposalTransformer for TitlePrefix {
+    fn transform(&self, _: &SubSquareReferendum, data: &mut ProposalData) {
+        data.title = format!("{} {}", self.0, data.title);
+    }
+}
+
+/// 在正文末尾追加一段文字，与正文空一行（CONTENT_FOOTER）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentFooter(pub String);
+
+impl ProposalTransformer for ContentFooter {
+    fn transform(&self, _: &SubSquareReferendum, data: &mut ProposalData) {
+        data.content = format!("{}\n\n{}", data.content.trim_end(), self.0);
+    }
+}
+
+/// 按顺序应用全部转换器
+pub fn apply(transformers: &[std::sync::Arc<dyn ProposalTransformer>], r: &SubSquareReferendum, data: &mut ProposalData) {
+    for transformer in transformers {
+        transformer.transform(r, data);
+    }
+}